@@ -1,6 +1,6 @@
-use std::{env, io};
-use std::fs::File;
-use bank_account_parser;
+use std::env;
+use bank_account_parser::format_detection::open_any;
+use bank_account_parser::reconcile::reconcile;
 use bank_account_parser::transactions_holder::TransactionHolder;
 
 fn help() {
@@ -13,60 +13,14 @@ fn help() {
     println!("   compare examples/data/camt053.exmpl examples/data/csv.exmpl");
 }
 
-fn get_holder(path: &String) -> Option<TransactionHolder> {
-    let Some(filename) = path.split("/").last() else {
-        println!("Не удалось получить название файла из параметра {}", &path);
-        return None;
-    };
-
-    if filename == "camt053.exmpl" {
-        let Ok(file) = File::open(&path) else {
-            panic!("Не удалось открыть файл {path}")
-        };
-        let mut reader = io::BufReader::new(file);
-
-        match bank_account_parser::camt053_format::Camt053Format::from_read(&mut reader) {
-            Ok(obj) => {
-                return Some(TransactionHolder::new(obj));
-            },
-            Err(e) => {
-                println!("{e}");
-                panic!("Непредвиденная ошибка!!!")
-            }
-        };
-
-    } else if filename == "mt940.exmpl" {
-        let Ok(file) = File::open(&path) else {
-            panic!("Не удалось открыть файл {path}")
-        };
-        let mut reader = io::BufReader::new(file);
-
-        match bank_account_parser::mt940_format::MT940Format::from_read(&mut reader) {
-            Ok(obj) => {
-                return Some(TransactionHolder::new(obj));
-            },
-            Err(e) => {
-                println!("{e}");
-                panic!("Непредвиденная ошибка!!!")
-            }
-        };
-    } else if filename == "csv.exmpl" {
-        let Ok(file) = File::open(&path) else {
-            panic!("Не удалось открыть файл {path}")
-        };
-        let mut reader = io::BufReader::new(file);
-
-        match bank_account_parser::csv_format::CSVFormat::from_read(&mut reader) {
-            Ok(obj) => {
-                return Some(TransactionHolder::new(obj));
-            },
-            Err(e) => {
-                println!("{e}");
-                panic!("Непредвиденная ошибка!!!")
-            }
-        };
+fn get_holder(path: &str) -> Option<TransactionHolder> {
+    match open_any(path) {
+        Ok(holder) => Some(holder),
+        Err(e) => {
+            println!("{e}");
+            None
+        }
     }
-    None
 }
 
 fn main() {
@@ -87,26 +41,20 @@ fn main() {
         return;
     };
 
-    let mut iter_holder1 = holder1.into_iter();
-    let mut iter_holder2 = holder2.into_iter();
+    let result = reconcile(&holder1, &holder2);
 
-    // Обход транзакций
-    loop {
-        if let Some(t1) = iter_holder1.next() {
-            let Some(t2) = iter_holder2.next() else {
-                println!("Транзакция ({}) есть в {} но нет в {}", t1, args[2], args[3]);
-                return;
-            };
-            if t1 == t2 {
-                continue;
-            }
-        } else if let Some(t2) = iter_holder2.next() {
-            println!("Транзакция {} есть в {} но нет в {}", t2, args[3], args[2]);
-            return;
-        }
-        else {
-            break
-        };
+    for near in &result.near_matches {
+        let diff: Vec<String> = near.differences.iter().map(|d| d.to_string()).collect();
+        println!("Транзакция ({}) из {} отличается от ({}) из {}: {}", near.left, args[2], near.right, args[3], diff.join("; "));
+    }
+    for t in &result.unmatched_left {
+        println!("Транзакция ({}) есть в {} но нет в {}", t, args[2], args[3]);
+    }
+    for t in &result.unmatched_right {
+        println!("Транзакция ({}) есть в {} но нет в {}", t, args[3], args[2]);
+    }
+
+    if result.is_identical() {
+        println!("Транзакции идентичны");
     }
-    println!("Транзакции идентичны");
 }
\ No newline at end of file
@@ -1,4 +1,3 @@
-use bank_account_parser;
 use std::{env, io};
 use std::fs::File;
 use std::io::Write;
@@ -30,7 +29,7 @@ fn main() {
     };
 
     if filename == "camt053.exmpl" {
-        let Ok(file) = File::open(&path) else {
+        let Ok(file) = File::open(path) else {
             panic!("Не удалось открыть файл {path}")
         };
         let mut reader = io::BufReader::new(file);
@@ -48,7 +47,7 @@ fn main() {
 
         match result.write_to(&mut out) {
             Ok(_) => {
-                out.write("\n".as_ref()).unwrap();
+                out.write_all("\n".as_ref()).unwrap();
             },
             Err(e) => {
                 println!("{e}");
@@ -56,7 +55,7 @@ fn main() {
             }
         }
     } else if filename == "mt940.exmpl" {
-        let Ok(file) = File::open(&path) else {
+        let Ok(file) = File::open(path) else {
             panic!("Не удалось открыть файл {path}")
         };
         let mut reader = io::BufReader::new(file);
@@ -74,7 +73,7 @@ fn main() {
 
         match result.write_to(&mut out) {
             Ok(_) => {
-                out.write("\n".as_ref()).unwrap();
+                out.write_all("\n".as_ref()).unwrap();
             },
             Err(e) => {
                 println!("{e}");
@@ -0,0 +1,301 @@
+//! Соответствие кодов банковских операций ISO 20022 и SWIFT.
+//!
+//! CAMT описывает операцию кодом `BkTxCd/Domn` из трёх частей (домен, семейство, подсемейство,
+//! например `PMNT/RCDT/DMCT`), MT940 — трёхбуквенным кодом после `N` в поле `:61:` (`TRF`, `CHK`,
+//! `DDT`, `COM`, `INT`...). [`BankTxCodeMap`] переводит коды в обе стороны по встроенной таблице,
+//! которую можно дополнить и переопределить своими правилами:
+//!
+//! ```
+//! use bank_account_parser::bank_tx_code::{BankTxCodeMap, DomainCode};
+//!
+//! let mut codes = BankTxCodeMap::default();
+//! codes.insert("PMNT/RCDT/*", "NWI").unwrap();
+//! let iso: DomainCode = "PMNT/RCDT/ESCT".parse().unwrap();
+//! assert_eq!(codes.to_swift(&iso), Some("NWI"));
+//! ```
+//!
+//! Файл правил содержит по одному правилу в строке: `PMNT/RCDT/DMCT=TRF`; `*` вместо
+//! подсемейства подходит к любому подсемейству, строки с `#` в начале — комментарии.
+
+use crate::common::debit_credit::DebitOrCredit;
+use crate::error::{FormatError, GeneratorFormatError};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+
+/// Код операции ISO 20022 (`BkTxCd/Domn`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DomainCode {
+    /// `Domn/Cd`
+    pub domain: String,
+    /// `Domn/Fmly/Cd`
+    pub family: String,
+    /// `Domn/Fmly/SubFmlyCd`
+    pub sub_family: String,
+}
+
+impl DomainCode {
+    pub fn new(domain: &str, family: &str, sub_family: &str) -> Self {
+        Self {
+            domain: domain.to_string(),
+            family: family.to_string(),
+            sub_family: sub_family.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DomainCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.domain, self.family, self.sub_family)
+    }
+}
+
+impl FromStr for DomainCode {
+    type Err = FormatError;
+
+    /// Разобрать код вида `PMNT/RCDT/DMCT`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_domain(s).ok_or_else(|| BankTxCodeMap::data_format_error(&bad_domain(s)))
+    }
+}
+
+fn parse_domain(s: &str) -> Option<DomainCode> {
+    match s.trim().split('/').collect::<Vec<_>>()[..] {
+        [domain, family, sub_family] if [domain, family, sub_family].iter().all(|c| is_code(c)) => {
+            Some(DomainCode::new(domain, family, sub_family))
+        }
+        _ => None,
+    }
+}
+
+fn bad_domain(s: &str) -> String {
+    format!("ожидался код вида PMNT/RCDT/DMCT, получено \"{s}\"")
+}
+
+/// Четыре заглавные латинские буквы или цифры.
+fn is_code(code: &str) -> bool {
+    code.len() == 4 && code.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+/// Правило соответствия: код ISO (без подсемейства — любое подсемейство) и код SWIFT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    domain: String,
+    family: String,
+    sub_family: Option<String>,
+    swift: String,
+}
+
+impl Rule {
+    fn matches(&self, code: &DomainCode, exact: bool) -> bool {
+        self.domain == code.domain
+            && self.family == code.family
+            && match &self.sub_family {
+                Some(sub) => sub == &code.sub_family,
+                None => !exact,
+            }
+    }
+
+    /// Подходит ли правило к направлению проводки: семейства поступлений (`RCDT`, `RDDT`...) и
+    /// подсемейство взноса наличных `CDPT` — только к кредиту, семейства списаний (`ICDT`, `IDDT`...)
+    /// и подсемейство снятия наличных `CWDL` — только к дебету, остальные к любому.
+    fn fits(&self, direction: DebitOrCredit) -> bool {
+        let credit = matches!(direction, DebitOrCredit::Credit | DebitOrCredit::ReverseCredit);
+        match (self.family.as_str(), self.sub_family.as_deref()) {
+            ("RCDT" | "RDDT" | "RCHQ" | "MCOP", _) | (_, Some("CDPT")) => credit,
+            ("ICDT" | "IDDT" | "ICHQ" | "MDOP", _) | (_, Some("CWDL")) => !credit,
+            _ => true,
+        }
+    }
+
+    fn domain_code(&self) -> DomainCode {
+        DomainCode::new(&self.domain, &self.family, self.sub_family.as_deref().unwrap_or("OTHR"))
+    }
+}
+
+/// Встроенная таблица: домен, семейство, подсемейство (пустое — любое), код SWIFT.
+/// При переводе кода SWIFT в ISO берётся первое правило с подходящим направлением.
+const BUILTIN: &[(&str, &str, &str, &str)] = &[
+    ("PMNT", "RCDT", "DMCT", "TRF"),
+    ("PMNT", "RCDT", "ESCT", "TRF"),
+    ("PMNT", "RCDT", "XBCT", "TRF"),
+    ("PMNT", "RCDT", "SALA", "SAL"),
+    ("PMNT", "RCDT", "STDO", "STO"),
+    ("PMNT", "RCDT", "", "TRF"),
+    ("PMNT", "ICDT", "DMCT", "TRF"),
+    ("PMNT", "ICDT", "ESCT", "TRF"),
+    ("PMNT", "ICDT", "XBCT", "TRF"),
+    ("PMNT", "ICDT", "SALA", "SAL"),
+    ("PMNT", "ICDT", "STDO", "STO"),
+    ("PMNT", "ICDT", "", "TRF"),
+    ("PMNT", "RDDT", "ESDD", "DDT"),
+    ("PMNT", "RDDT", "BBDD", "DDT"),
+    ("PMNT", "RDDT", "", "DDT"),
+    ("PMNT", "IDDT", "ESDD", "DDT"),
+    ("PMNT", "IDDT", "BBDD", "DDT"),
+    ("PMNT", "IDDT", "", "DDT"),
+    ("PMNT", "RCHQ", "CCHQ", "CHK"),
+    ("PMNT", "RCHQ", "", "CHK"),
+    ("PMNT", "ICHQ", "CCHQ", "CHK"),
+    ("PMNT", "ICHQ", "", "CHK"),
+    ("PMNT", "CNTR", "CWDL", "CAS"),
+    ("PMNT", "CNTR", "CDPT", "CAS"),
+    ("PMNT", "CCRD", "POSD", "POP"),
+    ("PMNT", "CCRD", "CWDL", "CAS"),
+    ("PMNT", "MCOP", "OTHR", "MSC"),
+    ("PMNT", "MDOP", "OTHR", "MSC"),
+    ("ACMT", "MCOP", "INTR", "INT"),
+    ("ACMT", "MDOP", "INTR", "INT"),
+    ("ACMT", "MDOP", "CHRG", "CHG"),
+    ("ACMT", "MDOP", "COMM", "COM"),
+    ("ACMT", "MDOP", "TAXE", "TAX"),
+    ("ACMT", "MCOP", "OTHR", "MSC"),
+    ("ACMT", "MDOP", "OTHR", "MSC"),
+    ("FORX", "SPOT", "", "FEX"),
+    ("FORX", "FWRD", "", "FEX"),
+    ("SECU", "CORP", "DVCA", "DIV"),
+    ("SECU", "SETT", "", "SEC"),
+    ("LDAS", "FTDP", "", "LDP"),
+    ("LDAS", "NTDP", "", "LDP"),
+    ("XTND", "NTAV", "NTAV", "MSC"),
+];
+
+/// Код ISO для проводок, по которым неизвестен ни код ISO, ни код SWIFT.
+pub fn not_available() -> DomainCode {
+    DomainCode::new("XTND", "NTAV", "NTAV")
+}
+
+/// Таблица соответствия кодов ISO 20022 и SWIFT: правила пользователя, затем встроенная таблица.
+#[derive(Debug, Default, Clone)]
+pub struct BankTxCodeMap {
+    overrides: Vec<Rule>,
+}
+
+impl GeneratorFormatError for BankTxCodeMap {
+    const ERROR_PREFIX: &'static str = "Ошибка разбора таблицы кодов операций";
+}
+
+impl BankTxCodeMap {
+    /// Добавить правило `iso` ↔ `swift`; `iso` — код вида `PMNT/RCDT/DMCT` или `PMNT/RCDT/*`,
+    /// `swift` — код из трёх букв, допускается с `N` впереди. Правила пользователя проверяются
+    /// раньше встроенных, более поздние — раньше более ранних.
+    pub fn insert(&mut self, iso: &str, swift: &str) -> Result<(), FormatError> {
+        let rule = Self::parse_rule(iso, swift).map_err(|e| Self::data_format_error(&e))?;
+        self.overrides.insert(0, rule);
+        Ok(())
+    }
+
+    fn parse_rule(iso: &str, swift: &str) -> Result<Rule, String> {
+        let swift = swift.trim();
+        let swift = if swift.len() == 4 { swift.strip_prefix('N').unwrap_or(swift) } else { swift };
+        if swift.len() != 3 || !swift.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(format!("неверный код SWIFT \"{swift}\""));
+        }
+        let (code, any_sub_family) = match iso.trim().strip_suffix("/*") {
+            Some(family) => (parse_domain(&format!("{family}/OTHR")), true),
+            None => (parse_domain(iso), false),
+        };
+        let code = code.ok_or_else(|| bad_domain(iso))?;
+        Ok(Rule {
+            sub_family: (!any_sub_family).then_some(code.sub_family),
+            domain: code.domain,
+            family: code.family,
+            swift: swift.to_string(),
+        })
+    }
+
+    /// Прочитать правила пользователя из файла вида `PMNT/RCDT/DMCT=TRF`.
+    pub fn from_read<R: Read>(read: &mut R) -> Result<Self, FormatError> {
+        let mut map = Self::default();
+        for (n, line) in BufReader::new(read).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((iso, swift)) = line.split_once('=') else {
+                return Err(Self::data_format_error(&format!("строка {}: ожидалось ISO=SWIFT", n + 1)));
+            };
+            let rule = Self::parse_rule(iso, swift)
+                .map_err(|e| Self::data_format_error(&format!("строка {}: {e}", n + 1)))?;
+            map.overrides.insert(0, rule);
+        }
+        Ok(map)
+    }
+
+    fn rules(&self) -> impl Iterator<Item = Rule> + '_ {
+        let builtin = BUILTIN.iter().map(|&(domain, family, sub, swift)| Rule {
+            domain: domain.to_string(),
+            family: family.to_string(),
+            sub_family: (!sub.is_empty()).then(|| sub.to_string()),
+            swift: swift.to_string(),
+        });
+        self.overrides.iter().cloned().chain(builtin)
+    }
+
+    /// Код SWIFT для кода ISO. В правилах пользователя и затем во встроенной таблице сначала ищется
+    /// правило с тем же подсемейством, потом правило на всё семейство.
+    pub fn to_swift(&self, code: &DomainCode) -> Option<&str> {
+        let user = |exact| self.overrides.iter().find(|r| r.matches(code, exact)).map(|r| r.swift.as_str());
+        let builtin = |exact| {
+            BUILTIN
+                .iter()
+                .find(|&&(domain, family, sub, _)| {
+                    domain == code.domain
+                        && family == code.family
+                        && if exact { sub == code.sub_family } else { sub.is_empty() }
+                })
+                .map(|&(_, _, _, swift)| swift)
+        };
+        user(true).or_else(|| user(false)).or_else(|| builtin(true)).or_else(|| builtin(false))
+    }
+
+    /// Код ISO для кода SWIFT `swift` проводки с направлением `direction`.
+    pub fn to_iso(&self, swift: &str, direction: DebitOrCredit) -> Option<DomainCode> {
+        self.rules()
+            .find(|r| r.swift == swift && r.fits(direction))
+            .map(|r| r.domain_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn builtin_codes_map_both_ways() {
+        let codes = BankTxCodeMap::default();
+        assert_eq!(codes.to_swift(&"PMNT/RCDT/DMCT".parse().unwrap()), Some("TRF"));
+        assert_eq!(codes.to_swift(&"PMNT/RCDT/VCOM".parse().unwrap()), Some("TRF"));
+        assert_eq!(codes.to_swift(&"ACMT/MDOP/CHRG".parse().unwrap()), Some("CHG"));
+        assert_eq!(codes.to_swift(&"XXXX/YYYY/ZZZZ".parse().unwrap()), None);
+
+        assert_eq!(codes.to_iso("TRF", DebitOrCredit::Credit), Some(DomainCode::new("PMNT", "RCDT", "DMCT")));
+        assert_eq!(codes.to_iso("TRF", DebitOrCredit::Debit), Some(DomainCode::new("PMNT", "ICDT", "DMCT")));
+        assert_eq!(codes.to_iso("DDT", DebitOrCredit::Debit), Some(DomainCode::new("PMNT", "IDDT", "ESDD")));
+        assert_eq!(codes.to_iso("INT", DebitOrCredit::Credit), Some(DomainCode::new("ACMT", "MCOP", "INTR")));
+        assert_eq!(codes.to_iso("CAS", DebitOrCredit::Credit), Some(DomainCode::new("PMNT", "CNTR", "CDPT")));
+        assert_eq!(codes.to_iso("CAS", DebitOrCredit::Debit), Some(DomainCode::new("PMNT", "CNTR", "CWDL")));
+        assert_eq!(codes.to_iso("QQQ", DebitOrCredit::Credit), None);
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let rules = "# свои коды\nPMNT/RCDT/ESCT=NSEP\n\nPMNT/ICDT/*=TRN\n";
+        let codes = BankTxCodeMap::from_read(&mut Cursor::new(rules)).unwrap();
+        assert_eq!(codes.to_swift(&"PMNT/RCDT/ESCT".parse().unwrap()), Some("SEP"));
+        assert_eq!(codes.to_swift(&"PMNT/RCDT/DMCT".parse().unwrap()), Some("TRF"));
+        assert_eq!(codes.to_swift(&"PMNT/ICDT/DMCT".parse().unwrap()), Some("TRN"));
+        assert_eq!(codes.to_swift(&"PMNT/ICDT/BOOK".parse().unwrap()), Some("TRN"));
+        assert_eq!(codes.to_iso("SEP", DebitOrCredit::Credit), Some(DomainCode::new("PMNT", "RCDT", "ESCT")));
+        assert_eq!(codes.to_iso("TRN", DebitOrCredit::Debit), Some(DomainCode::new("PMNT", "ICDT", "OTHR")));
+        assert_eq!(codes.to_iso("TRN", DebitOrCredit::Credit), None);
+
+        let err = BankTxCodeMap::from_read(&mut Cursor::new("PMNT/RCDT=TRF")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Ошибка разбора таблицы кодов операций : строка 1: ожидался код вида PMNT/RCDT/DMCT, получено \"PMNT/RCDT\""
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Отчёт по счёту в течение дня camt.052 (`BkToCstmrAcctRpt/Rpt`).
+//!
+//! Отчёт устроен так же, как выписка CAMT.053: тот же заголовок группы, те же реквизиты счёта,
+//! остатки и проводки `Ntry`, только выписка называется `Rpt`, а остатки в ней необязательны.
+//! Поэтому [`Camt052Format`] хранит документ в [`Camt053Format`] и читает его тем же разбором
+//! тегов, а поиск и правку тегов даёт через [`Camt052Format::document`].
+//!
+//! ```no_run
+//! use bank_account_parser::camt052_format::Camt052Format;
+//! use bank_account_parser::mt942_format::MT942Format;
+//! use std::fs::File;
+//!
+//! let camt = Camt052Format::from_read(&mut File::open("report.xml").unwrap()).unwrap();
+//! MT942Format::from(camt).write_to(&mut std::io::stdout()).unwrap();
+//! ```
+
+use crate::bank_tx_code::BankTxCodeMap;
+use crate::camt053_format::{Camt053Format, Camt053Version, CamtMessage, XmlWriteOptions};
+use crate::error::{FormatError, GeneratorFormatError};
+use crate::mt940_format::MT940Format;
+use crate::statement::{CollectedStatements, Statements};
+use crate::transactions_holder::{CollectedTransactions, ReadMode, TransactionsReader};
+use std::io::Write;
+
+#[derive(Debug, Clone)]
+pub struct Camt052Format {
+    document: Camt053Format,
+}
+
+impl GeneratorFormatError for Camt052Format {
+    const ERROR_PREFIX: &'static str = "Ошибка разбора формата camt052";
+}
+
+impl Camt052Format {
+    /// Разобрать camt.052 из потока ввода, см. [`Camt053Format::from_read`].
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если XML некорректен или документ не является отчётом
+    /// `BkToCstmrAcctRpt` (например, это выписка camt.053).
+    ///
+    pub fn from_read<R: std::io::Read>(r: &mut R) -> Result<Camt052Format, FormatError> {
+        let document = Camt053Format::from_read(r)?;
+        match document.message() {
+            Some(CamtMessage::Report) => Ok(Self { document }),
+            _ => Err(Self::data_format_error(
+                "в документе нет отчёта BkToCstmrAcctRpt или Rpt",
+            )),
+        }
+    }
+
+    /// Записать отчёт в XML, см. [`Camt053Format::write_to`].
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> Result<(), FormatError> {
+        self.document.write_to(writer)
+    }
+
+    /// Записать отчёт в XML с параметрами `options`, см. [`Camt053Format::write_with`].
+    pub fn write_with<W: Write>(&self, writer: &mut W, options: &XmlWriteOptions) -> Result<(), FormatError> {
+        self.document.write_with(writer, options)
+    }
+
+    /// Версия сообщения по пространству имён `camt.052.001.NN`.
+    pub fn version(&self) -> Option<Camt053Version> {
+        self.document.message_version(CamtMessage::Report)
+    }
+
+    /// Подготовить отчёт к записи в версии `version`, см. [`Camt053Format::set_version`].
+    pub fn set_version(&mut self, version: Camt053Version) {
+        self.document.set_message_version(CamtMessage::Report, version);
+    }
+
+    /// Дерево тегов отчёта с поиском по путям и правкой.
+    pub fn document(&self) -> &Camt053Format {
+        &self.document
+    }
+
+    /// Дерево тегов отчёта для правки.
+    pub fn document_mut(&mut self) -> &mut Camt053Format {
+        &mut self.document
+    }
+
+    /// Дерево тегов отчёта.
+    pub fn into_document(self) -> Camt053Format {
+        self.document
+    }
+
+    /// Выписки отчёта, см. [`Camt053Format::into_statements`].
+    pub fn into_statements(self, mode: ReadMode) -> Result<CollectedStatements, FormatError> {
+        self.document.statements_of(CamtMessage::Report, mode)
+    }
+}
+
+impl From<Camt052Format> for Statements {
+    fn from(value: Camt052Format) -> Self {
+        value.document.collect_statements(CamtMessage::Report).statements
+    }
+}
+
+/// Выписки в документ `camt.052.001.02` с тем же порядком элементов, что и у CAMT.053.
+impl From<Statements> for Camt052Format {
+    fn from(v: Statements) -> Self {
+        Self::from_statements_with(v, &BankTxCodeMap::default())
+    }
+}
+
+impl Camt052Format {
+    /// Выписки в отчёт, как `From<Statements>`, с таблицей кодов операций `codes`.
+    pub fn from_statements_with(v: Statements, codes: &BankTxCodeMap) -> Self {
+        Self {
+            document: Camt053Format::from_statements(v, CamtMessage::Report, codes),
+        }
+    }
+}
+
+impl From<MT940Format> for Camt052Format {
+    fn from(v: MT940Format) -> Self {
+        Statements::from(v).into()
+    }
+}
+
+impl TransactionsReader for Camt052Format {
+    fn read_transactions(&self, mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+        self.document.transactions_of(CamtMessage::Report, mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camt053_schema;
+    use crate::common::debit_credit::DebitOrCredit;
+    use rust_decimal::Decimal;
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn example() -> Camt052Format {
+        Camt052Format::from_read(&mut File::open("./examples/data/camt052.exmpl").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_report_into_statements_and_transactions() {
+        let camt = example();
+        assert_eq!(camt.version(), Some(Camt053Version::V08));
+        assert_eq!(camt.document().version(), None);
+
+        let statements = Statements::from(camt.clone());
+        let [report] = statements.statements.as_slice() else {
+            panic!("ожидался один отчёт");
+        };
+        assert_eq!(report.id, "RPT-0001");
+        assert_eq!(report.account.id, "NL81ASNB9999999999");
+        assert_eq!(report.statement_no, "3");
+        assert_eq!(report.additional_info.as_deref(), Some("Intraday report"));
+        assert_eq!(
+            report.created.map(|d| d.to_rfc3339()).as_deref(),
+            Some("2020-01-01T12:30:00+01:00")
+        );
+        // промежуточный остаток ITBD попадает во входящий
+        assert_eq!(
+            report.opening_balance.as_ref().map(|b| b.amount),
+            Some(Decimal::from_str("1200.00").unwrap())
+        );
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.entries[0].customer_ref, "INV-1");
+        assert_eq!(report.entries[1].debit_credit_indicator, DebitOrCredit::Debit);
+
+        let transactions = camt.read_transactions(ReadMode::Strict).unwrap().transactions;
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].amount, Decimal::from_str("250.00").unwrap());
+        assert_eq!(transactions[0].description.as_deref(), Some("Invoice 1"));
+        assert_eq!(transactions[1].bank_ref.as_deref(), Some("BANKREF2"));
+    }
+
+    #[test]
+    fn statement_is_not_a_report() {
+        let statement = "<BkToCstmrStmt><GrpHdr/><Stmt><Id>1</Id></Stmt></BkToCstmrStmt>";
+        assert!(Camt052Format::from_read(&mut Cursor::new(statement)).is_err());
+
+        let bare = "<Rpt><Id>1</Id><Ntry><Amt Ccy=\"EUR\">1</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry></Rpt>";
+        let camt = Camt052Format::from_read(&mut Cursor::new(bare)).unwrap();
+        assert_eq!(camt.read_transactions(ReadMode::Strict).unwrap().transactions.len(), 1);
+    }
+
+    #[test]
+    fn writes_report_from_statements() {
+        let statements = Statements::from(example());
+        let mut camt = Camt052Format::from(statements.clone());
+        assert!(camt053_schema::validate(camt.document()).is_empty());
+        assert_eq!(camt.version(), Some(Camt053Version::V02));
+        camt.set_version(Camt053Version::V08);
+
+        let mut written = Vec::new();
+        camt.write_to(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.contains(
+            "<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:camt.052.001.08\"><BkToCstmrAcctRpt><GrpHdr>"
+        ));
+        assert!(written.contains("<Rpt><Id>RPT-0001</Id><ElctrncSeqNb>3</ElctrncSeqNb><CreDtTm>2020-01-01T12:30:00+01:00</CreDtTm>"));
+        assert!(written.contains("<AddtlRptInf>Intraday report</AddtlRptInf></Rpt>"));
+
+        // коды ISO, которых не было в исходной выписке, записываются по встроенной таблице
+        let mut expected = statements;
+        expected.map_bank_tx_codes(&BankTxCodeMap::default());
+        let read = Camt052Format::from_read(&mut Cursor::new(written)).unwrap();
+        assert_eq!(Statements::from(read), expected);
+    }
+}
@@ -1,31 +1,260 @@
-use crate::camt053_iterator::Camt053Iter;
+use crate::bank_tx_code::{self, BankTxCodeMap};
+use crate::camt053_iterator::{path_of, Camt053Iter, TagView};
+use crate::camt053_query::Camt053Path;
+use crate::camt053_tree::Element;
 use crate::error::{FormatError, GeneratorFormatError};
 use crate::common::debit_credit::DebitOrCredit;
-use crate::mt940_format::{AvailableBalance, MT940Format};
-use crate::transactions_holder::{Transaction, TransactionsReader};
-use chrono::NaiveDate;
-use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
-use quick_xml::{Reader, Writer};
+use crate::mt940_format::MT940Format;
+use crate::statement::{Balance, CollectedStatements, Entry, Statement, Statements};
+use crate::transactions_holder::{
+    CollectedTransactions, Counterparty, EntryStatus, ReadMode, RowDiagnostic, TransactionsReader,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime};
+use quick_xml::escape::{partial_escape, unescape};
+use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesPI, BytesStart, BytesText, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::{NsReader, Writer};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::Zero;
-use std::cell::RefCell;
+use std::fmt;
 use std::io::{BufReader, Write};
-use std::rc::{Rc, Weak};
-use uuid::Uuid;
-
-#[derive(Debug, Default)]
-pub struct Tag {
-    pub name: String,
-    pub text: Option<String>,
-    pub attrs: Vec<(String, String)>,
-    pub childrens: Vec<Rc<RefCell<Tag>>>,
-    pub parent: Weak<RefCell<Tag>>,
+
+pub use crate::camt053_tree::{NodeId, Tag, TagTree};
+
+/// Версия сообщения CAMT.053 (`camt.053.001.NN`); номера версий camt.052 совпадают.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Camt053Version {
+    V02,
+    V03,
+    V04,
+    V05,
+    V06,
+    V07,
+    V08,
+    V09,
+    V10,
+    V11,
 }
 
-#[derive(Default)]
-#[derive(Debug)]
+impl Camt053Version {
+    pub const ALL: [Camt053Version; 10] = [
+        Camt053Version::V02,
+        Camt053Version::V03,
+        Camt053Version::V04,
+        Camt053Version::V05,
+        Camt053Version::V06,
+        Camt053Version::V07,
+        Camt053Version::V08,
+        Camt053Version::V09,
+        Camt053Version::V10,
+        Camt053Version::V11,
+    ];
+
+    /// Номер версии: 2 для `camt.053.001.02`.
+    pub fn number(self) -> u8 {
+        self as u8 + 2
+    }
+
+    /// Пространство имён `Document`: `urn:iso:std:iso:20022:tech:xsd:camt.053.001.NN`.
+    pub fn namespace(self) -> String {
+        CamtMessage::Statement.namespace(self)
+    }
+
+    /// Определить версию по пространству имён CAMT.053.
+    pub fn from_namespace(namespace: &str) -> Option<Self> {
+        match CamtMessage::from_namespace(namespace)? {
+            (CamtMessage::Statement, version) => Some(version),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Camt053Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&CamtMessage::Statement.schema(*self))
+    }
+}
+
+impl std::str::FromStr for Camt053Version {
+    type Err = FormatError;
+
+    /// Принимает `camt.053.001.08`, `001.08`, `08` или `8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = s.trim().rsplit('.').next().unwrap_or_default();
+        number
+            .parse::<u8>()
+            .ok()
+            .and_then(|n| Self::ALL.into_iter().find(|v| v.number() == n))
+            .ok_or_else(|| {
+                Camt053Format::unknown_value_error(&format!(
+                    "неизвестная версия {s}, поддерживаются версии с camt.053.001.02 по camt.053.001.11"
+                ))
+            })
+    }
+}
+
+/// Вид сообщения CAMT об операциях по счёту. Сообщения устроены одинаково и отличаются
+/// именами корневого тега, тега выписки и пространством имён.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CamtMessage {
+    /// `camt.052` — отчёт по счёту в течение дня (`BkToCstmrAcctRpt/Rpt`).
+    Report,
+    /// `camt.053` — выписка по счёту (`BkToCstmrStmt/Stmt`).
+    Statement,
+    /// `camt.054` — уведомление о списании или зачислении (`BkToCstmrDbtCdtNtfctn/Ntfctn`).
+    Notification,
+}
+
+impl CamtMessage {
+    pub const ALL: [CamtMessage; 3] = [CamtMessage::Report, CamtMessage::Statement, CamtMessage::Notification];
+
+    /// Номер сообщения: `052`, `053`.
+    pub fn code(self) -> &'static str {
+        match self {
+            CamtMessage::Report => "052",
+            CamtMessage::Statement => "053",
+            CamtMessage::Notification => "054",
+        }
+    }
+
+    /// Тег сообщения под `Document`.
+    pub fn root_tag(self) -> &'static str {
+        match self {
+            CamtMessage::Report => "BkToCstmrAcctRpt",
+            CamtMessage::Statement => "BkToCstmrStmt",
+            CamtMessage::Notification => "BkToCstmrDbtCdtNtfctn",
+        }
+    }
+
+    /// Тег одной выписки (отчёта) по счёту.
+    pub fn statement_tag(self) -> &'static str {
+        match self {
+            CamtMessage::Report => "Rpt",
+            CamtMessage::Statement => "Stmt",
+            CamtMessage::Notification => "Ntfctn",
+        }
+    }
+
+    /// Тег дополнительной информации в конце выписки.
+    pub fn additional_info_tag(self) -> &'static str {
+        match self {
+            CamtMessage::Report => "AddtlRptInf",
+            CamtMessage::Statement => "AddtlStmtInf",
+            CamtMessage::Notification => "AddtlNtfctnInf",
+        }
+    }
+
+    /// Имя схемы версии `version`: `camt.052.001.08`.
+    pub fn schema(self, version: Camt053Version) -> String {
+        format!("camt.{}.001.{:02}", self.code(), version.number())
+    }
+
+    /// Пространство имён `Document` версии `version`.
+    pub fn namespace(self, version: Camt053Version) -> String {
+        format!("urn:iso:std:iso:20022:tech:xsd:{}", self.schema(version))
+    }
+
+    /// Определить сообщение и версию по пространству имён.
+    pub fn from_namespace(namespace: &str) -> Option<(Self, Camt053Version)> {
+        let schema = namespace.trim().strip_prefix("urn:iso:std:iso:20022:tech:xsd:")?;
+        Self::ALL.into_iter().find_map(|message| {
+            let version = Camt053Version::ALL
+                .into_iter()
+                .find(|&v| message.schema(v) == schema)?;
+            Some((message, version))
+        })
+    }
+}
+
+/// XML-декларация документа: `<?xml version="1.0" encoding="UTF-8"?>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlDeclaration {
+    pub version: String,
+    pub encoding: Option<String>,
+    pub standalone: Option<String>,
+}
+
+impl Default for XmlDeclaration {
+    fn default() -> Self {
+        Self {
+            version: "1.0".to_string(),
+            encoding: Some("UTF-8".to_string()),
+            standalone: None,
+        }
+    }
+}
+
+/// Параметры записи XML для [`Camt053Format::write_with`].
+///
+/// Значение по умолчанию пишет документ как [`Camt053Format::write_to`]: в одну строку,
+/// с XML-декларацией, если она была в прочитанном документе.
+#[derive(Debug, Clone, Default)]
+pub struct XmlWriteOptions {
+    /// Ширина отступа вложенных тегов в пробелах; `0` — весь документ в одну строку.
+    pub indent: usize,
+    /// Писать XML-декларацию: `None` — как в прочитанном документе (в каноническом
+    /// виде — не писать), `Some(true)` — всегда, `Some(false)` — никогда.
+    pub declaration: Option<bool>,
+    /// Значение атрибута `encoding` в декларации вместо прочитанного. Текст всегда
+    /// пишется в UTF-8 без перекодирования, поэтому другая кодировка допускается, только
+    /// если в документе нет символов вне ASCII.
+    pub encoding: Option<String>,
+    /// Канонический вид: атрибуты по алфавиту (объявления пространств имён первыми),
+    /// пробелы в тексте и значениях атрибутов схлопнуты, CDATA записывается обычным
+    /// текстом, пустые теги — парой `<Tag></Tag>`. Два документа с одинаковыми тегами,
+    /// атрибутами и текстом записываются побайтно одинаково.
+    pub canonical: bool,
+}
+
+impl XmlWriteOptions {
+    /// Документ с отступом `indent` пробелов.
+    pub fn pretty(indent: usize) -> Self {
+        Self {
+            indent,
+            ..Default::default()
+        }
+    }
+
+    /// Канонический вид без отступов и декларации.
+    pub fn canonical() -> Self {
+        Self {
+            canonical: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Схлопнуть пробельные символы в один пробел.
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug, Clone)]
 pub struct Camt053Format {
-    root: Rc<RefCell<Tag>>,
+    tree: TagTree,
+    root: NodeId,
+    declaration: Option<XmlDeclaration>,
+    /// Инструкции обработки, стоящие перед корневым тегом.
+    prolog: Vec<String>,
+    /// Разворачивать пакетные проводки при чтении, см. [`Camt053Format::set_expand_batches`].
+    expand_batches: bool,
+    /// Таблица кодов операций для чтения, см. [`Camt053Format::set_bank_tx_codes`].
+    bank_tx_codes: BankTxCodeMap,
+}
+
+impl Default for Camt053Format {
+    fn default() -> Self {
+        let mut tree = TagTree::new();
+        let root = tree.push(None, Tag::default());
+        Self {
+            tree,
+            root,
+            declaration: None,
+            prolog: Vec::new(),
+            expand_batches: false,
+            bank_tx_codes: BankTxCodeMap::default(),
+        }
+    }
 }
 
 impl GeneratorFormatError for Camt053Format {
@@ -43,10 +272,66 @@ impl Camt053Format {
         true
     }
 
+    pub(crate) fn read_tag<R>(
+        reader: &NsReader<R>,
+        e: &BytesStart,
+        tree: &mut TagTree,
+        parent: Option<NodeId>,
+    ) -> Result<NodeId, FormatError> {
+        let namespace = match reader.resolver().resolve_element(e.name()).0 {
+            ResolveResult::Bound(ns) => Some(String::from_utf8_lossy(ns.as_ref()).to_string()),
+            ResolveResult::Unbound | ResolveResult::Unknown(_) => None,
+        };
+        let mut tag = Tag {
+            name: String::from_utf8_lossy(e.name().as_ref()).to_string(),
+            namespace,
+            ..Default::default()
+        };
+        for attr_result in e.attributes() {
+            let value = attr_result.ok().and_then(|a| {
+                let key = String::from_utf8_lossy(a.key.as_ref()).to_string();
+                a.unescape_value().ok().map(|v| (key, v.to_string()))
+            });
+            let Some(attr) = value else {
+                return Err(Self::unknown_value_error(
+                    format!("не удалось распарсить атрибуты тега {}", tag.name).as_str(),
+                ));
+            };
+            tag.attrs.push(attr);
+        }
+        Ok(tree.push(parent, tag))
+    }
+
+    /// Добавить текст к тегу `tag`; текст вне тегов допустим, только если он пробельный.
+    pub(crate) fn append_text(tree: &mut TagTree, tag: Option<NodeId>, text: &str) -> Result<(), FormatError> {
+        let Some(tag) = tag else {
+            if text.trim().is_empty() {
+                return Ok(());
+            }
+            Err(Self::data_format_error(
+                format!("не найден тег которому принадлежит текст {}", text.trim()).as_str(),
+            ))?
+        };
+        tree[tag].text.get_or_insert_with(String::new).push_str(text);
+        Ok(())
+    }
+
+    fn read_declaration(e: &BytesDecl) -> XmlDeclaration {
+        let text = |v: &[u8]| String::from_utf8_lossy(v).to_string();
+        XmlDeclaration {
+            version: e.version().map(|v| text(&v)).unwrap_or_else(|_| "1.0".to_string()),
+            encoding: e.encoding().and_then(Result::ok).map(|v| text(&v)),
+            standalone: e.standalone().and_then(Result::ok).map(|v| text(&v)),
+        }
+    }
+
     /// Разобрать CAMT.053 (ISO 20022) из потока ввода и построить дерево XML-тегов.
     ///
     /// Метод читает XML, строит внутреннее дерево [`Tag`] с родителями/детьми и возвращает [`Camt053Format`].
-    /// Текстовые узлы нормализуются с `trim_text(true)`, то есть пробельные узлы отбрасываются.
+    /// Текст тега собирается из текстовых узлов, ссылок на сущности (`&amp;`, `&#233;`) и секций CDATA,
+    /// после чего обрезается по краям; пробельные узлы между тегами отбрасываются.
+    /// XML-декларация и инструкции обработки перед корневым тегом сохраняются для [`Self::write_to`],
+    /// комментарии, DOCTYPE и инструкции обработки внутри документа пропускаются.
     ///
     /// # Ошибки
     /// Возвращает [`FormatError`], если XML некорректен, содержит неподдерживаемые/битые атрибуты
@@ -54,139 +339,344 @@ impl Camt053Format {
     ///
     pub fn from_read<R: std::io::Read>(r: &mut R) -> Result<Camt053Format, FormatError> {
         let buf_reader = BufReader::new(r);
-        let mut reader = Reader::from_reader(buf_reader);
-        reader.config_mut().trim_text(true);
+        let mut reader = NsReader::from_reader(buf_reader);
         let mut buf = Vec::new();
 
-        // Создаем ВИРТУАЛЬНЫЙ корневой тег, который будет хранить всех детей
-        let virtual_root = Rc::new(RefCell::new(Tag::default()));
-
-        let mut previous_tag = Rc::clone(&virtual_root); // начинаем с виртуального корня
+        let mut tree = TagTree::new();
+        // Открытый тег; `None` — уровень документа, вне корневого тега
+        let mut previous_tag: Option<NodeId> = None;
+        let mut declaration = None;
+        let mut prolog = Vec::new();
 
         loop {
             match reader.read_event_into(&mut buf) {
                 Ok(Event::Start(e)) => {
-                    let new_tag = Rc::new(RefCell::new(Tag {
-                        name: String::from_utf8_lossy(e.name().as_ref()).to_string(),
-                        text: None,
-                        attrs: Vec::new(),
-                        childrens: Vec::new(),
-                        parent: Rc::downgrade(&previous_tag),
-                    }));
-
-                    {
-                        let mut new_tag_mut = new_tag.borrow_mut();
-                        for attr_result in e.attributes() {
-                            let attr = match attr_result {
-                                Ok(a) => a,
-                                Err(_) => {
-                                    return Err(Self::unknown_value_error(
-                                        format!(
-                                            "не удалось распарсить атрибуты тега {}",
-                                            new_tag_mut.name
-                                        )
-                                        .as_str(),
-                                    ));
-                                }
-                            };
-                            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                            let value = String::from_utf8_lossy(&attr.value).to_string();
-                            new_tag_mut.attrs.push((key, value));
-                        }
-                    }
-                    previous_tag
-                        .borrow_mut()
-                        .childrens
-                        .push(Rc::clone(&new_tag));
-                    previous_tag = new_tag;
+                    previous_tag = Some(Self::read_tag(&reader, &e, &mut tree, previous_tag)?);
+                }
+                Ok(Event::Empty(e)) => {
+                    let tag = Self::read_tag(&reader, &e, &mut tree, previous_tag)?;
+                    tree[tag].self_closing = true;
                 }
                 Ok(Event::Text(e)) => {
                     let text = String::from_utf8_lossy(e.as_ref()).to_string();
-                    let mut tag_mut = previous_tag.borrow_mut();
-                    if tag_mut.name.is_empty() {
-                        Err(Self::data_format_error(
-                            format!("не найден тег которому принадлежит текст {text}").as_str(),
+                    Self::append_text(&mut tree, previous_tag, &text)?;
+                }
+                Ok(Event::GeneralRef(e)) => {
+                    let name = String::from_utf8_lossy(e.as_ref()).to_string();
+                    let reference = format!("&{name};");
+                    let Ok(text) = unescape(&reference) else {
+                        Err(Self::unknown_value_error(
+                            format!("неизвестная ссылка на сущность {reference}").as_str(),
                         ))?
                     };
-                    tag_mut.text = Some(text);
+                    Self::append_text(&mut tree, previous_tag, &text)?;
+                }
+                Ok(Event::CData(e)) => {
+                    let text = String::from_utf8_lossy(e.as_ref()).to_string();
+                    Self::append_text(&mut tree, previous_tag, &text)?;
+                    if let Some(tag) = previous_tag {
+                        tree[tag].cdata = true;
+                    }
                 }
                 Ok(Event::End(e)) => {
                     let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    previous_tag = {
-                        let tag = previous_tag.borrow();
-                        if tag.name.is_empty() || tag.name != name {
-                            Err(Self::data_format_error(
-                                format!(
-                                    "найден закрывающийся тег {name} но ожидался тег {}",
-                                    tag.name.as_str()
-                                )
-                                .as_str(),
-                            ))?;
-                        }
-                        let Some(result) = tag.parent.upgrade() else {
-                            Err(Self::unknown_error("ошибка чтения данных"))?
-                        };
-                        result
+                    let Some(tag) = previous_tag.filter(|&t| tree[t].name == name) else {
+                        Err(Self::data_format_error(
+                            format!(
+                                "найден закрывающийся тег {name} но ожидался тег {}",
+                                previous_tag.map(|t| tree[t].name.as_str()).unwrap_or_default()
+                            )
+                            .as_str(),
+                        ))?
+                    };
+                    tree[tag].trim_text();
+                    previous_tag = tree.parent(tag);
+                }
+                Ok(Event::Decl(e)) => declaration = Some(Self::read_declaration(&e)),
+                Ok(Event::PI(e)) => {
+                    if tree.is_empty() {
+                        prolog.push(String::from_utf8_lossy(e.as_ref()).to_string());
                     }
                 }
-                Ok(Event::Comment(_)) => continue,
+                Ok(Event::Comment(_)) | Ok(Event::DocType(_)) => continue,
                 Ok(Event::Eof) => break,
                 Err(e) => Err(Self::read_write_error(
                     format!("не удалось разложить xml файл на теги {}", e).as_str(),
                 ))?,
-                _ => {
-                    Err(Self::read_write_error(r#"при разборе файла встретился неизвестный раздел.
-                    Сейчас поддерживаются блоки <...>, </...>, комментарии,
-                    текст относящийся к тегам и символы завершения файла."#))?
-                }
             }
             buf.clear();
         }
 
-        let root = {
-            let tag = previous_tag.borrow();
-            if tag.childrens.is_empty() {
-                Err(Self::data_format_error(
-                    "не удалось прочитать ни одного тега",
-                ))?
-            };
-            tag.childrens[0].clone()
+        // корневой тег должен быть закрыт
+        if tree.is_empty() || previous_tag.is_some() {
+            Err(Self::data_format_error(
+                "не удалось прочитать ни одного тега",
+            ))?
         };
-        Ok(Self { root })
+        Ok(Self {
+            tree,
+            // первый прочитанный тег — корневой
+            root: NodeId::FIRST,
+            declaration,
+            prolog,
+            expand_batches: false,
+            bank_tx_codes: BankTxCodeMap::default(),
+        })
     }
 
     fn write<W: std::io::Write>(
         &self,
         writer: &mut Writer<W>,
-        tag: &Rc<RefCell<Tag>>,
+        id: NodeId,
+        options: &XmlWriteOptions,
     ) -> Result<(), FormatError> {
-        let tag = tag.borrow();
+        let tag = &self.tree[id];
         let mut root = BytesStart::new(tag.name.clone());
-        for attr in &tag.attrs {
-            let (key, value) = attr; // Разбираем кортеж
-            root.push_attribute((key.as_str(), value.as_str()));
+        let mut attrs: Vec<&(String, String)> = tag.attrs.iter().collect();
+        if options.canonical {
+            attrs.sort_by_key(|(key, _)| (!key.starts_with("xmlns"), key.as_str()));
+        }
+        for (key, value) in attrs {
+            if options.canonical {
+                root.push_attribute((key.as_str(), normalize_whitespace(value).as_str()));
+            } else {
+                root.push_attribute((key.as_str(), value.as_str()));
+            }
+        }
+        if tag.self_closing && !options.canonical && tag.text.is_none() && tag.childrens.is_empty() {
+            writer.write_event(Event::Empty(root))?;
+            return Ok(());
         }
         writer.write_event(Event::Start(root))?;
         if let Some(ref text) = tag.text {
-            writer.write_event(Event::Text(BytesText::new(text)))?;
+            if options.canonical {
+                let text = normalize_whitespace(text);
+                writer.write_event(Event::Text(BytesText::from_escaped(partial_escape(text.as_str()))))?;
+            } else if tag.cdata && !text.contains("]]>") {
+                writer.write_event(Event::CData(BytesCData::new(text.as_str())))?;
+            } else {
+                writer.write_event(Event::Text(BytesText::from_escaped(partial_escape(text.as_str()))))?;
+            }
         }
-        for child in &tag.childrens {
-            self.write(writer, child)?;
+        for &child in &tag.childrens {
+            self.write(writer, child, options)?;
         }
         writer.write_event(Event::End(BytesEnd::new(tag.name.clone())))?;
         Ok(())
     }
 
+    /// Вид сообщения по корневому тегу (`BkToCstmrStmt`, `BkToCstmrAcctRpt` с обёрткой `Document`
+    /// или без неё, либо сразу `Stmt`/`Rpt`), а если он не подошёл — по пространству имён.
+    pub fn message(&self) -> Option<CamtMessage> {
+        let root = self.tree[self.root].local_name();
+        let by_tag = CamtMessage::ALL.into_iter().find(|m| {
+            root == m.root_tag() || root == m.statement_tag() || self.tree.child(self.root, m.root_tag()).is_some()
+        });
+        by_tag.or_else(|| self.namespaces().find_map(|ns| CamtMessage::from_namespace(&ns)).map(|(m, _)| m))
+    }
+
+    /// Пространства имён `Document` и тега сообщения под ним.
+    fn namespaces(&self) -> impl Iterator<Item = String> + '_ {
+        let message = CamtMessage::ALL
+            .into_iter()
+            .find_map(|m| self.tree.child(self.root, m.root_tag()))
+            .and_then(|c| self.tree[c].namespace.clone());
+        self.tree[self.root].namespace.clone().into_iter().chain(message)
+    }
+
+    /// Версия сообщения по пространству имён `Document` (или `BkToCstmrStmt`).
+    ///
+    /// Возвращает `None`, если пространство имён не объявлено или не относится к CAMT.053.
+    pub fn version(&self) -> Option<Camt053Version> {
+        self.message_version(CamtMessage::Statement)
+    }
+
+    /// Версия сообщения `message` по пространству имён документа.
+    pub fn message_version(&self, message: CamtMessage) -> Option<Camt053Version> {
+        self.namespaces()
+            .filter_map(|ns| CamtMessage::from_namespace(&ns))
+            .find_map(|(m, version)| (m == message).then_some(version))
+    }
+
+    /// Подготовить документ к записи в версии `version`.
+    ///
+    /// Если корневым тегом является `Document`, в нём заменяется объявление пространства имён
+    /// (и `xsi:schemaLocation`, если оно есть), иначе дерево оборачивается в
+    /// `<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.NN">`.
+    pub fn set_version(&mut self, version: Camt053Version) {
+        self.set_message_version(CamtMessage::Statement, version);
+    }
+
+    /// То же, что [`Self::set_version`], с пространством имён сообщения `message`.
+    ///
+    /// Элементы, строение которых зависит от версии, приводятся к версии `version` так же, как при
+    /// записи модели [`crate::camt053_model`]: статус проводки с 001.08 пишется как `Sts/Cd`,
+    /// участники `RltdPties` с 001.08 — внутри `Pty`, BIC в `FinInstnId` с 001.04 — как `BICFI`.
+    pub fn set_message_version(&mut self, message: CamtMessage, version: Camt053Version) {
+        let namespace = message.namespace(version);
+        if self.tree[self.root].local_name() != "Document" {
+            let mut document = Tag::new("Document");
+            document.attrs.push(("xmlns".to_string(), namespace.clone()));
+            let document = self.tree.push(None, document);
+            self.tree.attach(self.root, document, 0);
+            self.root = document;
+        }
+
+        let root = &mut self.tree[self.root];
+        let old = root.namespace.clone();
+        let xmlns = match root.prefix() {
+            Some(prefix) => format!("xmlns:{prefix}"),
+            None => "xmlns".to_string(),
+        };
+        match root.attrs.iter_mut().find(|(k, _)| *k == xmlns) {
+            Some((_, v)) => *v = namespace.clone(),
+            None => root.attrs.push((xmlns, namespace.clone())),
+        }
+        if let Some((_, location)) = root.attrs.iter_mut().find(|(k, _)| k.ends_with(":schemaLocation")) {
+            *location = format!("{namespace} {}.xsd", message.schema(version));
+        }
+        self.set_namespace(self.root, old.as_deref(), &namespace);
+        self.set_version_structure(self.root, version);
+    }
+
+    /// Привести поддерево `id` к строению версии `version`, см. [`Self::set_message_version`].
+    fn set_version_structure(&mut self, id: NodeId, version: Camt053Version) {
+        for child in self.tree.children(id).to_vec() {
+            self.set_version_structure(child, version);
+        }
+        let parent = self.tree.parent(id).map(|p| self.tree[p].local_name().to_string());
+        let name = self.tree[id].local_name().to_string();
+        match (parent.as_deref(), name.as_str()) {
+            (Some("Ntry"), "Sts") => {
+                let code = self.tree.child(id, "Cd");
+                if version >= Camt053Version::V08 {
+                    if code.is_none() && let Some(text) = self.tree[id].text.take() {
+                        let code = self.tree.insert(id, 0, Tag { text: Some(text), ..Tag::new("Cd") });
+                        self.adopt_namespace(code);
+                    }
+                } else if let Some(code) = code {
+                    self.tree.detach(code);
+                    self.tree[id].text = self.tree[code].text.clone();
+                }
+            }
+            (Some("RltdPties"), "Dbtr" | "Cdtr" | "UltmtDbtr" | "UltmtCdtr") => {
+                let party = self.tree.child(id, "Pty");
+                if version >= Camt053Version::V08 {
+                    if party.is_none() && !self.tree.children(id).is_empty() {
+                        let children = self.tree.children(id).to_vec();
+                        let party = self.tree.insert(id, 0, Tag::new("Pty"));
+                        self.adopt_namespace(party);
+                        for (index, child) in children.into_iter().enumerate() {
+                            self.tree.attach(child, party, index);
+                        }
+                    }
+                } else if let Some(party) = party {
+                    let position = self.tree.children(id).iter().position(|&c| c == party).unwrap_or_default();
+                    for (index, child) in self.tree.children(party).to_vec().into_iter().enumerate() {
+                        self.tree.attach(child, id, position + index);
+                    }
+                    self.tree.detach(party);
+                }
+            }
+            (Some("FinInstnId"), "BIC" | "BICFI") => {
+                let local = if version >= Camt053Version::V04 { "BICFI" } else { "BIC" };
+                let tag = &mut self.tree[id];
+                tag.name = match tag.prefix() {
+                    Some(prefix) => format!("{prefix}:{local}"),
+                    None => local.to_string(),
+                };
+            }
+            _ => (),
+        }
+    }
+
+    /// Заменить пространство имён `old` (или отсутствующее) на `new` во всём поддереве.
+    fn set_namespace(&mut self, id: NodeId, old: Option<&str>, new: &str) {
+        let tag = &mut self.tree[id];
+        if tag.namespace.is_none() || tag.namespace.as_deref() == old {
+            tag.namespace = Some(new.to_string());
+        }
+        for child in tag.childrens.clone() {
+            self.set_namespace(child, old, new);
+        }
+    }
+
+    /// XML-декларация прочитанного документа.
+    pub fn declaration(&self) -> Option<&XmlDeclaration> {
+        self.declaration.as_ref()
+    }
+
     /// Записать текущее дерево CAMT.053 обратно в XML.
     ///
-    /// Если корневой узел является “виртуальным” (пустое имя тега, как после [`from_read`]),
-    /// то в выход будет записано **его содержимое** (дети), а не пустой контейнер.
+    /// Если документ был прочитан с XML-декларацией и инструкциями обработки перед корневым тегом,
+    /// они записываются перед деревом в том же виде.
     ///
     /// # Ошибки
     /// Возвращает [`FormatError`] при ошибке записи в `writer` или при ошибке сериализации XML.
     ///
     pub fn write_to<W: Write>(&mut self, writer: &mut W) -> Result<(), FormatError> {
-        self.write(&mut Writer::new(writer), &self.root)
+        self.write_with(writer, &XmlWriteOptions::default())
+    }
+
+    /// Записать дерево в XML с отступами, декларацией и каноническим видом по `options`.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`] при ошибке записи в `writer` или при ошибке сериализации XML,
+    /// а также если декларация объявляет кодировку, отличную от UTF-8, а в документе есть
+    /// символы вне ASCII.
+    ///
+    pub fn write_with<W: Write>(&self, writer: &mut W, options: &XmlWriteOptions) -> Result<(), FormatError> {
+        let mut writer = if options.indent > 0 {
+            Writer::new_with_indent(writer, b' ', options.indent)
+        } else {
+            Writer::new(writer)
+        };
+        let declaration = match options.declaration {
+            Some(false) => None,
+            Some(true) => Some(self.declaration.clone().unwrap_or_default()),
+            None if options.canonical => None,
+            None => self.declaration.clone(),
+        };
+        if let Some(mut decl) = declaration {
+            if let Some(encoding) = &options.encoding {
+                decl.encoding = Some(encoding.clone());
+            }
+            if let Some(encoding) = decl.encoding.as_deref()
+                && !["UTF-8", "UTF8"].iter().any(|e| encoding.eq_ignore_ascii_case(e))
+                && !self.is_ascii()
+            {
+                return Err(Self::unknown_value_error(&format!(
+                    "документ с символами вне ASCII нельзя записать в кодировке {encoding}, текст пишется в UTF-8"
+                )));
+            }
+            writer.write_event(Event::Decl(BytesDecl::new(
+                &decl.version,
+                decl.encoding.as_deref(),
+                decl.standalone.as_deref(),
+            )))?;
+            writer.write_event(Event::Text(BytesText::from_escaped("\n")))?;
+        }
+        for pi in &self.prolog {
+            writer.write_event(Event::PI(BytesPI::new(pi.as_str())))?;
+            writer.write_event(Event::Text(BytesText::from_escaped("\n")))?;
+        }
+        self.write(&mut writer, self.root, options)?;
+        if options.indent > 0 {
+            writer.get_mut().write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// В документе (тегах, атрибутах, тексте и инструкциях перед корнем) только символы ASCII.
+    fn is_ascii(&self) -> bool {
+        fn subtree(tree: &TagTree, id: NodeId) -> bool {
+            let tag = &tree[id];
+            tag.name.is_ascii()
+                && tag.text.as_deref().is_none_or(str::is_ascii)
+                && tag.attrs.iter().all(|(k, v)| k.is_ascii() && v.is_ascii())
+                && tag.childrens.iter().all(|&c| subtree(tree, c))
+        }
+        self.prolog.iter().all(|pi| pi.is_ascii()) && subtree(&self.tree, self.root)
     }
 
     /// Получить итератор (обход в глубину, pre-order) по всем тегам документа.
@@ -195,380 +685,1057 @@ impl Camt053Format {
     /// - `path()` — путь вида `A/B/C`
     /// - `text()` — текстовое содержимое (пустая строка, если отсутствует)
     ///
-    pub fn get_iter(&self) -> Camt053Iter {
-        Camt053Iter::new(self.root.clone())
+    pub fn get_iter(&self) -> Camt053Iter<'_> {
+        Camt053Iter::new(&self.tree, self.root)
     }
 
-    fn set_parent(start: &Rc<RefCell<Tag>>) {
-        for child in start.borrow_mut().childrens.iter() {
-            child.borrow_mut().parent = Rc::downgrade(start);
-            Self::set_parent(child);
+    /// Теги документа по пути из [`crate::camt053_query`], например
+    /// `Stmt/Ntry[CdtDbtInd='CRDT']/Amt` или `//Refs/EndToEndId`.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError::UnknownValueFormat`], если путь записан неверно.
+    ///
+    pub fn select(&self, path: &str) -> Result<Vec<TagView<'_>>, FormatError> {
+        let path: Camt053Path = path.parse()?;
+        Ok(path
+            .select(&self.tree, self.root, None)
+            .into_iter()
+            .map(|id| TagView::new(&self.tree, id, path_of(&self.tree, id)))
+            .collect())
+    }
+
+    /// Первый тег по пути, см. [`Camt053Format::select`].
+    pub fn select_first(&self, path: &str) -> Result<Option<TagView<'_>>, FormatError> {
+        Ok(self.select(path)?.into_iter().next())
+    }
+
+    /// Единственный тег по пути, см. [`Camt053Format::select`]. Нужен, чтобы адресовать
+    /// правку: `camt.set_text(camt.find("Stmt/Acct/Id/IBAN")?, "DE89...")`.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если путь записан неверно, не найден или указывает
+    /// на несколько тегов.
+    ///
+    pub fn find(&self, path: &str) -> Result<NodeId, FormatError> {
+        match self.select(path)?.as_slice() {
+            [tag] => Ok(tag.node()),
+            [] => Err(Self::data_format_error(&format!("по пути {path} нет тегов"))),
+            tags => Err(Self::data_format_error(&format!(
+                "путь {path} указывает на {} тегов, ожидался один",
+                tags.len()
+            ))),
         }
     }
 
+    /// Разворачивать ли пакетные проводки при чтении выписок и транзакций.
+    ///
+    /// Пакетная проводка — `Ntry` с несколькими `NtryDtls/TxDtls` (обычно с `Btch`), например
+    /// зарплатная ведомость или инкассо. По умолчанию она читается одной проводкой на сумму
+    /// `Ntry`, а `AddtlTxInf` всех `TxDtls` склеиваются. Развёрнутая, она даёт по проводке на
+    /// каждый `TxDtls` с его суммой (`Amt` или `AmtDtls/TxAmt/Amt`), референсами и
+    /// контрагентом; даты и код операции берутся из `Ntry`. Проводка, у какого-либо `TxDtls`
+    /// которой нет своей суммы, не разворачивается.
+    pub fn set_expand_batches(&mut self, expand: bool) {
+        self.expand_batches = expand;
+    }
+
+    /// Таблица кодов операций, по которой при чтении выписок распознаётся код SWIFT в
+    /// `Prtry/Issr`, как его писали прежние версии конвертера. По умолчанию — встроенная.
+    pub fn set_bank_tx_codes(&mut self, codes: BankTxCodeMap) {
+        self.bank_tx_codes = codes;
+    }
+
+    /// Дерево тегов документа.
+    pub fn tree(&self) -> &TagTree {
+        &self.tree
+    }
+
+    /// Корневой тег документа.
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Документ из готового дерева тегов с корнем `root`.
+    pub(crate) fn from_tree(tree: TagTree, root: NodeId) -> Self {
+        Self {
+            tree,
+            root,
+            declaration: None,
+            prolog: Vec::new(),
+            expand_batches: false,
+            bank_tx_codes: BankTxCodeMap::default(),
+        }
+    }
 }
 
-impl From<MT940Format> for Camt053Format {
-    fn from(v: MT940Format) -> Self {
-        let crt_with_text = |name: &str, text: Option<String>| {
-            Rc::new(RefCell::new(Tag {
-                name: name.to_string(),
-                text,
-                attrs: Vec::new(),
-                childrens: Vec::new(),
-                parent: Weak::new(),
-            }))
-        };
+/// Правка дерева. Родители и дети меняются согласованно; теги для правки находятся
+/// через [`Camt053Format::find`] или [`Camt053Format::select`].
+impl Camt053Format {
+    /// Тег документа; удалённые теги остаются в арене, но недостижимы от корня и тоже отвергаются.
+    fn check(&self, id: NodeId) -> Result<&Tag, FormatError> {
+        self.tree
+            .get(id)
+            .filter(|_| self.tree.contains(self.root, id))
+            .ok_or_else(|| Self::unknown_error(&format!("тег {} не принадлежит документу", id.index())))
+    }
 
-        let crt_with_child = |name: &str, child: &[Rc<RefCell<Tag>>]| {
-            Rc::new(RefCell::new(Tag {
-                name: name.to_string(),
-                text: None,
-                attrs: Vec::new(),
-                childrens: child.to_vec(),
-                parent: Weak::new(),
-            }))
-        };
+    /// Заменить текст тега; пустая строка удаляет текст.
+    pub fn set_text(&mut self, id: NodeId, text: &str) -> Result<(), FormatError> {
+        self.check(id)?;
+        self.tree[id].text = Some(text.to_string()).filter(|t| !t.is_empty());
+        Ok(())
+    }
 
-        let debi_cred_name = |x: &DebitOrCredit| -> &str {
-            match x {
-                DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => "DBIT",
-                DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => "CRDT",
-            }
-        };
+    /// Установить значение атрибута, добавив его при отсутствии.
+    pub fn set_attr(&mut self, id: NodeId, name: &str, value: &str) -> Result<(), FormatError> {
+        self.check(id)?;
+        let attrs = &mut self.tree[id].attrs;
+        match attrs.iter_mut().find(|(k, _)| k == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => attrs.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
 
-        let root = crt_with_child(
-            "BkToCstmrStmt",
-            [crt_with_child(
-                "GrpHdr",
-                [crt_with_text("MsgId", Some(Uuid::new_v4().to_string()))].as_ref(),
-            )].as_ref(),
-        );
+    /// Удалить атрибут; возвращает его прежнее значение.
+    pub fn remove_attr(&mut self, id: NodeId, name: &str) -> Result<Option<String>, FormatError> {
+        self.check(id)?;
+        let attrs = &mut self.tree[id].attrs;
+        Ok(attrs
+            .iter()
+            .position(|(k, _)| k == name)
+            .map(|pos| attrs.remove(pos).1))
+    }
 
-        let root_ref = Rc::clone(&root);
+    /// Вставить тег ребёнком `parent` на позицию `index` (`None` — последним).
+    ///
+    /// Тег без префикса и пространства имён получает их от `parent`, поэтому
+    /// `Tag::new("AddtlStmtInf")` под `ns2:Stmt` запишется как `ns2:AddtlStmtInf`.
+    pub fn insert_child(&mut self, parent: NodeId, index: Option<usize>, tag: Tag) -> Result<NodeId, FormatError> {
+        let index = self.child_index(parent, index)?;
+        let id = self.tree.insert(parent, index, tag);
+        self.adopt_namespace(id);
+        Ok(id)
+    }
 
-        for transaction in v.into_iter() {
-            let stmt = crt_with_text("Stmt", None);
-            let mut stmt_child: Vec<Rc<RefCell<Tag>>>;
+    /// Вставить копию поддерева `id` из `other` ребёнком `parent` на позицию `index`
+    /// (`None` — последним).
+    pub fn insert_subtree(
+        &mut self,
+        parent: NodeId,
+        index: Option<usize>,
+        other: &TagTree,
+        id: NodeId,
+    ) -> Result<NodeId, FormatError> {
+        if other.get(id).is_none() {
+            return Err(Self::unknown_error(&format!("тег {} не принадлежит дереву", id.index())));
+        }
+        let index = self.child_index(parent, index)?;
+        let copy = self.tree.copy_from(None, other, id);
+        self.tree.attach(copy, parent, index);
+        self.adopt_namespace(copy);
+        Ok(copy)
+    }
 
-            {
-                // <Id>
-                let id = crt_with_text("Id", Some(transaction.transaction_ref_no.clone()));
-                let elctrn = crt_with_text("ElctrncSeqNb", Some(transaction.statement_no.clone()));
-                stmt_child = vec![id, elctrn];
+    /// Удалить тег вместе с детьми.
+    pub fn remove(&mut self, id: NodeId) -> Result<(), FormatError> {
+        self.check(id)?;
+        if id == self.root {
+            return Err(Self::unknown_value_error("корневой тег документа нельзя удалить"));
+        }
+        self.tree.detach(id);
+        Ok(())
+    }
 
-                let mut seq_nb = String::new();
-                transaction
-                    .sequence_no
-                    .as_deref()
-                    .map(|seq| format!("{}", seq.trim()))
-                    .inspect(|s| seq_nb.push_str(s));
+    /// Перенести тег вместе с детьми ребёнком `parent` на позицию `index` (`None` — последним).
+    pub fn move_node(&mut self, id: NodeId, parent: NodeId, index: Option<usize>) -> Result<(), FormatError> {
+        self.check(id)?;
+        if id == self.root || self.tree.contains(id, parent) {
+            return Err(Self::unknown_value_error(&format!(
+                "тег {} нельзя перенести внутрь него самого",
+                self.tree[id].name
+            )));
+        }
+        // позиция считается среди детей parent без переносимого тега
+        let siblings = self.check(parent)?.childrens.iter().filter(|&&c| c != id).count();
+        let index = index.unwrap_or(siblings);
+        if index > siblings {
+            return Err(Self::unknown_value_error(&format!(
+                "позиция {index} за пределами детей тега {} ({siblings})",
+                self.tree[parent].name
+            )));
+        }
+        self.tree.attach(id, parent, index);
+        Ok(())
+    }
 
-                if !seq_nb.is_empty() {
-                    stmt_child.push(crt_with_text("LglSeqNb", Some(seq_nb)));
+    fn child_index(&self, parent: NodeId, index: Option<usize>) -> Result<usize, FormatError> {
+        let tag = self.check(parent)?;
+        let len = tag.childrens.len();
+        match index {
+            None => Ok(len),
+            Some(index) if index <= len => Ok(index),
+            Some(index) => Err(Self::unknown_value_error(&format!(
+                "позиция {index} за пределами детей тега {} ({len})",
+                tag.name
+            ))),
+        }
+    }
 
-                }
-                if let Some(info) = transaction.information_to_account_owner.clone() {
-                    stmt_child.push(crt_with_text("AddtlStmtInf", Some(info)));
-                }
+    /// Передать новым тегам поддерева `id` префикс и пространство имён родителя.
+    fn adopt_namespace(&mut self, id: NodeId) {
+        let Some(parent) = self.tree.parent(id) else {
+            return;
+        };
+        let namespace = self.tree[parent].namespace.clone();
+        let prefix = self.tree[parent].prefix().map(str::to_string);
+        let tag = &mut self.tree[id];
+        if tag.namespace.is_some() {
+            return;
+        }
+        tag.namespace = namespace;
+        if let (Some(prefix), None) = (prefix, tag.prefix()) {
+            tag.name = format!("{prefix}:{}", tag.name);
+        }
+        for child in tag.childrens.clone() {
+            self.adopt_namespace(child);
+        }
+    }
+}
 
-            }
+impl Camt053Format {
+    fn balance_slot<'a>(code: &str, statement: &'a mut Statement) -> &'a mut Option<Balance> {
+        match code {
+            "CLBD" => &mut statement.closing_balance,
+            "CLAV" => &mut statement.closing_available_balance,
+            "FWAV" => &mut statement.forward_available_balance,
+            // OPBD и неизвестные коды балансов
+            _ => &mut statement.opening_balance,
+        }
+    }
 
-            {
-                // <FrToDt>
-                stmt_child.push(crt_with_child(
-                    "FrToDt",
-                    [
-                        crt_with_text(
-                            "FrDtTm",
-                            Some(transaction.opening_balance.balance.date.format("%Y-%m-%d").to_string()),
-                        ),
-                        crt_with_text(
-                            "ToDtTm",
-                            Some(transaction.closing_balance.balance.date.format("%Y-%m-%d").to_string()),
-                        ),
-                    ].as_ref(),
-                ));
-            }
+    fn parse_date(text: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(text.get(..10).unwrap_or(text), "%Y-%m-%d").ok()
+    }
 
-            {
-                // <Acct>
-                let acct = crt_with_text("Acct", None);
-                if Self::looks_like_iban(&transaction.account_id) {
-                    acct.borrow_mut().childrens.push(crt_with_child(
-                        "Id",
-                        [crt_with_text("IBAN", Some(transaction.account_id.clone()))].as_ref(),
-                    ));
-                } else {
-                    acct.borrow_mut().childrens.push(crt_with_child(
-                        "Id",
-                        [crt_with_child(
-                            "Othr",
-                            [crt_with_text("Id", Some(transaction.account_id.clone()))].as_ref(),
-                        )].as_ref(),
-                    ))
-                }
+    /// Дата и время ISO 8601; время без часового пояса считается временем UTC.
+    fn parse_date_time(text: &str) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(text).ok().or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|d| d.and_utc().fixed_offset())
+        })
+    }
 
-                acct.borrow_mut().childrens.push(crt_with_text(
-                    "Ccy",
-                    Some(transaction.opening_balance.balance.iso_currency_code.clone()),
-                ));
-                stmt_child.push(acct)
-            }
+    fn parse_indicator(text: &str) -> DebitOrCredit {
+        match text {
+            "CRDT" => DebitOrCredit::Credit,
+            // DBIT и неизвестные значения
+            _ => DebitOrCredit::Debit,
+        }
+    }
 
-            {
-                let balance2tag = |bal: &AvailableBalance, _type: &str| {
-                    let amt = crt_with_text("Amt", Some(bal.amount.to_string()));
-                    amt.borrow_mut()
-                        .attrs
-                        .push(("Ccy".to_string(), bal.iso_currency_code.clone()));
-
-                    crt_with_child(
-                        "Bal",
-                        [
-                            amt,
-                            crt_with_text("CdtDbtInd", Some(debi_cred_name(&bal.debit_credit_indicator).to_string())),
-                            crt_with_child(
-                                "Dt",
-                                [crt_with_text(
-                                    "Dt",
-                                    Some(bal.date.format("%Y-%m-%d").to_string()),
-                                )].as_ref(),
-                            ),
-                            crt_with_child(
-                                "Tp",
-                                [crt_with_child(
-                                    "CdOrPrtry",
-                                    [crt_with_text("Cd", Some(_type.to_string()))].as_ref(),
-                                )].as_ref(),
-                            ),
-                        ].as_ref(),
-                    )
-                };
+    /// Направление с учётом `RvslInd`: сторно по кредиту (отмена списания) — `ReverseCredit`, MT940 `RD`,
+    /// сторно по дебету (отмена поступления) — `ReverseDebit`, MT940 `RC`.
+    fn with_reversal(direction: DebitOrCredit, reversal: bool) -> DebitOrCredit {
+        match (direction, reversal) {
+            (DebitOrCredit::Credit, true) => DebitOrCredit::ReverseCredit,
+            (DebitOrCredit::Debit, true) => DebitOrCredit::ReverseDebit,
+            (direction, _) => direction,
+        }
+    }
+}
 
-                // <Bal>
-                let op: AvailableBalance = transaction.opening_balance.clone().into();
-                stmt_child.push(balance2tag(&op, "OPBD"));
-                let cb: AvailableBalance = transaction.closing_balance.clone().into();
-                stmt_child.push(balance2tag(&cb, "CLBD"));
-                if let Some(bal) = &transaction.closing_available_balance {
-                    stmt_child.push(balance2tag(&bal, "CLAV"));
-                }
-                if let Some(bal) = &transaction.forward_available_balance {
-                    stmt_child.push(balance2tag(&bal, "FWAV"));
+impl Camt053Format {
+    /// Реквизиты выписки по тегу `Stmt` (`Rpt`): счёт, номера и остатки. Проводки `Ntry` не читаются.
+    pub(crate) fn statement_header(tree: &TagTree, stmt: NodeId) -> Statement {
+        let mut st = Statement::default();
+        let mut balance: Option<(String, Balance)> = None;
+
+        let flush_balance = |statement: &mut Statement, balance: &mut Option<(String, Balance)>| {
+            if let Some((code, b)) = balance.take() {
+                let known = matches!(code.as_str(), "OPBD" | "CLBD" | "CLAV" | "FWAV");
+                let slot = Camt053Format::balance_slot(&code, statement);
+                if known || slot.is_none() {
+                    *slot = Some(b);
                 }
             }
+        };
 
-            {
-                // <TxsSummry>
-                let mut cbt = 0;
-                let mut cbt_sum: Decimal = Decimal::zero();
-                let mut dbt = 0;
-                let mut dbt_sum: Decimal = Decimal::zero();
-                for x in &transaction.statement_lines {
-                    if x.ext_debit_credit_indicator == DebitOrCredit::Credit
-                        || x.ext_debit_credit_indicator == DebitOrCredit::ReverseCredit
-                    {
-                        cbt += 1;
-                        cbt_sum += x.amount;
-                    } else if x.ext_debit_credit_indicator == DebitOrCredit::Debit
-                        || x.ext_debit_credit_indicator == DebitOrCredit::ReverseDebit
-                    {
-                        dbt += 1;
-                        dbt_sum += x.amount;
+        for &child in tree.children(stmt).iter().filter(|&&c| tree[c].local_name() != "Ntry") {
+            for tag in Camt053Iter::new(tree, child) {
+                match tag.path().as_str() {
+                    "/Id" => st.id = tag.text(),
+                    "/Acct/Id/IBAN" | "/Acct/Id/Othr/Id" => st.account.id = tag.text(),
+                    "/Acct/Ccy" => st.account.currency = Some(tag.text()),
+                    "/ElctrncSeqNb" => st.statement_no = tag.text(),
+                    "/LglSeqNb" if !tag.text().is_empty() => st.sequence_no = Some(tag.text()),
+                    "/CreDtTm" => st.created = Camt053Format::parse_date_time(&tag.text()),
+                    "/AddtlStmtInf" | "/AddtlRptInf" | "/AddtlNtfctnInf" if !tag.text().is_empty() => st.additional_info = Some(tag.text()),
+                    "/Bal" => {
+                        flush_balance(&mut st, &mut balance);
+                        balance = Some((String::new(), Balance::default()));
+                    }
+                    "/Bal/Tp/CdOrPrtry/Cd" => {
+                        if let Some((code, _)) = &mut balance {
+                            *code = tag.text();
+                        }
+                    }
+                    "/Bal/CdtDbtInd" => {
+                        if let Some((_, b)) = &mut balance {
+                            b.debit_credit_indicator = Camt053Format::parse_indicator(&tag.text());
+                        }
+                    }
+                    "/Bal/Dt/Dt" | "/Bal/Dt/DtTm" => {
+                        if let Some((_, b)) = &mut balance
+                            && let Some(d) = Camt053Format::parse_date(&tag.text())
+                        {
+                            b.date = d;
+                        }
                     }
+                    "/Bal/Amt" => {
+                        if let Some((_, b)) = &mut balance {
+                            if let Ok(amount) = tag.text().replace(",", ".").parse() {
+                                b.amount = amount;
+                            }
+                            if let Some(curr) = tag.get_attr("Ccy") {
+                                b.currency = curr;
+                            }
+                        }
+                    }
+                    _ => (),
                 }
-
-                stmt_child.push(crt_with_child(
-                    "TxsSummry",
-                    [
-                        crt_with_child(
-                            "TtlCdtNtries",
-                            [
-                                crt_with_text("NbOfNtries", Some(cbt.to_string())),
-                                crt_with_text("Sum", Some(cbt_sum.to_string())),
-                            ].as_ref(),
-                        ),
-                        crt_with_child(
-                            "TtlDbtNtries",
-                            [
-                                crt_with_text("NbOfNtries", Some(dbt.to_string())),
-                                crt_with_text("Sum", Some(dbt_sum.to_string())),
-                            ].as_ref(),
-                        ),
-                        crt_with_child(
-                            "TtlNtries",
-                            [
-                                crt_with_text(
-                                    "NbOfNtries",
-                                    Some(transaction.statement_lines.len().to_string()),
-                                ),
-                                crt_with_text(
-                                    "TtlNetNtryAmt",
-                                    Some((cbt_sum - dbt_sum).to_string()),
-                                ),
-                                crt_with_text(
-                                    "CdtDbtInd",
-                                    Some((if (cbt_sum - dbt_sum) > 0.into() {
-                                        "CRDT"
-                                    } else {
-                                        "DBIT"
-                                    }).to_string())
-                                ),
-                            ].as_ref())
-                    ].as_ref(),
-                ))
             }
+        }
+        flush_balance(&mut st, &mut balance);
+        st
+    }
 
-            {
-                // <Ntry>
-                for stat in &transaction.statement_lines {
-                    let amt = crt_with_text("Amt", Some(stat.amount.to_string()));
-                    amt.borrow_mut().attrs.push((
-                        "Ccy".to_string(),
-                        transaction.opening_balance.balance.iso_currency_code.clone(),
-                    ));
-                    let mut cd_text = stat.customer_ref.clone();
-                    if let Some(sup_det) = &stat.supplementary_details {
-                        cd_text += "/";
-                        cd_text += sup_det.as_str();
-                    };
+    /// Проводка выписки по тегу `Ntry` и первая ошибка её разбора; `row` — порядковый номер
+    /// проводки в документе (с 1).
+    pub(crate) fn statement_entry(
+        tree: &TagTree,
+        ntry: NodeId,
+        row: usize,
+        codes: &BankTxCodeMap,
+    ) -> (Entry, Option<RowDiagnostic>) {
+        PendingEntry::read(Camt053Iter::new(tree, ntry), row, false, codes)
+    }
+
+    /// Проводки выписки по тегу `Ntry`: пакетная при `expand` разворачивается по `TxDtls`,
+    /// см. [`Camt053Format::set_expand_batches`]; `index` — номер `Ntry` в выписке (с 1),
+    /// `row` — в документе, его получают все части пакета.
+    pub(crate) fn statement_entries(
+        tree: &TagTree,
+        ntry: NodeId,
+        index: usize,
+        row: usize,
+        expand: bool,
+        codes: &BankTxCodeMap,
+    ) -> Vec<(Entry, Option<RowDiagnostic>)> {
+        if expand && let Some(parts) = Self::batch_parts(tree, ntry) {
+            let batch = Self::batch_key(tree, ntry, index);
+            return parts
+                .into_iter()
+                .map(|tx| {
+                    let (entry, error) = PendingEntry::read(Self::part_tags(tree, ntry, tx), row, true, codes);
+                    (
+                        Entry {
+                            batch: Some(batch.clone()),
+                            ..entry
+                        },
+                        error,
+                    )
+                })
+                .collect();
+        }
+        vec![Self::statement_entry(tree, ntry, row, codes)]
+    }
 
-                    let mut val = "false";
-                    if stat.ext_debit_credit_indicator == DebitOrCredit::ReverseCredit
-                        || stat.ext_debit_credit_indicator == DebitOrCredit::ReverseDebit
-                    {
-                        val = "true";
+    /// `TxDtls` пакетной проводки `ntry`, если их больше одного и у каждого есть своя сумма.
+    fn batch_parts(tree: &TagTree, ntry: NodeId) -> Option<Vec<NodeId>> {
+        let parts: Vec<NodeId> = tree
+            .children(ntry)
+            .iter()
+            .filter(|&&c| tree[c].local_name() == "NtryDtls")
+            .flat_map(|&c| tree.children(c).iter().copied())
+            .filter(|&c| tree[c].local_name() == "TxDtls")
+            .collect();
+        let has_amount = |tx: NodeId| {
+            tree.child(tx, "Amt").is_some()
+                || tree
+                    .child(tx, "AmtDtls")
+                    .and_then(|a| tree.child(a, "TxAmt"))
+                    .and_then(|a| tree.child(a, "Amt"))
+                    .is_some()
+        };
+        (parts.len() > 1 && parts.iter().all(|&tx| has_amount(tx))).then_some(parts)
+    }
+
+    /// Идентификатор пакета: `Btch/PmtInfId`, иначе `AcctSvcrRef` проводки, иначе её номер `index`.
+    fn batch_key(tree: &TagTree, ntry: NodeId, index: usize) -> String {
+        ["/Ntry/NtryDtls/Btch/PmtInfId", "/Ntry/AcctSvcrRef"]
+            .into_iter()
+            .find_map(|path| {
+                Camt053Iter::new(tree, ntry)
+                    .find(|tag| tag.path() == path)
+                    .map(|tag| tag.text().trim().to_string())
+                    .filter(|text| !text.is_empty())
+            })
+            .unwrap_or_else(|| index.to_string())
+    }
+
+    /// Теги части `tx` пакетной проводки `ntry` с путями, как у обычной проводки: теги `Ntry`
+    /// без `NtryDtls`, затем теги `tx` под `/Ntry/NtryDtls/TxDtls`. Сумма и признак
+    /// дебета/кредита `TxDtls` идут под путями `/Ntry/Amt` и `/Ntry/CdtDbtInd` после общих
+    /// и заменяют их.
+    fn part_tags<'a>(tree: &'a TagTree, ntry: NodeId, tx: NodeId) -> impl Iterator<Item = TagView<'a>> {
+        let shared = tree
+            .children(ntry)
+            .iter()
+            .filter(|&&c| tree[c].local_name() != "NtryDtls")
+            .flat_map(move |&c| Camt053Iter::new(tree, c))
+            .map(move |tag| TagView::new(tree, tag.node(), format!("/Ntry{}", tag.path())));
+        let own = Camt053Iter::new(tree, tx).map(move |tag| {
+            let path = match tag.path().as_str() {
+                "/TxDtls/Amt" | "/TxDtls/AmtDtls/TxAmt/Amt" => "/Ntry/Amt".to_string(),
+                "/TxDtls/CdtDbtInd" => "/Ntry/CdtDbtInd".to_string(),
+                path => format!("/Ntry/NtryDtls{path}"),
+            };
+            TagView::new(tree, tag.node(), path)
+        });
+        shared.chain(own)
+    }
+}
+
+impl Camt053Format {
+    /// Теги, путь к которым от первого на пути `Stmt` (`Rpt` и т.п. по `message`) равен `tail`:
+    /// `""` — сами выписки, `"/Ntry"` — их проводки.
+    fn statement_tags<'a>(&'a self, message: CamtMessage, tail: &'a str) -> impl Iterator<Item = TagView<'a>> {
+        let marker = format!("/{}", message.statement_tag());
+        self.get_iter().filter(move |tag| {
+            let path = tag.path();
+            path.find(&marker).is_some_and(|s| path[s + marker.len()..] == *tail)
+        })
+    }
+
+    /// Выписки сообщения `message` в формато-независимой модели. Проводки, которые не удалось
+    /// разобрать, в выписки не попадают и возвращаются в `diagnostics`.
+    pub(crate) fn collect_statements(&self, message: CamtMessage) -> CollectedStatements {
+        let related_path = format!("/{}/GrpHdr/OrgnlBizQry/MsgId", message.root_tag());
+        let related_ref = self
+            .get_iter()
+            .find(|tag| tag.path().ends_with(&related_path))
+            .map(|tag| tag.text());
+
+        let tree = self.tree();
+        let mut collected = CollectedStatements::default();
+        let mut row = 0;
+        for tag in self.statement_tags(message, "") {
+            let stmt = tag.node();
+            let mut st = Camt053Format::statement_header(tree, stmt);
+            st.related_ref = related_ref.clone();
+            let entries = tree.children(stmt).iter().filter(|&&c| tree[c].local_name() == "Ntry");
+            for (i, &c) in entries.enumerate() {
+                row += 1;
+                for (entry, error) in
+                    Camt053Format::statement_entries(tree, c, i + 1, row, self.expand_batches, &self.bank_tx_codes)
+                {
+                    match error {
+                        None => st.entries.push(entry),
+                        Some(d) => collected.diagnostics.push(d),
                     }
+                }
+            }
+            collected.statements.statements.push(st);
+        }
+        collected
+    }
+
+    /// Выписки сообщения `message`, см. [`Camt053Format::into_statements`].
+    pub(crate) fn statements_of(&self, message: CamtMessage, mode: ReadMode) -> Result<CollectedStatements, FormatError> {
+        let collected = self.collect_statements(message);
+        match collected.diagnostics.first() {
+            Some(d) if mode == ReadMode::Strict => Err(Self::data_format_error(&d.to_string())),
+            _ => Ok(collected),
+        }
+    }
+
+    /// Транзакции из проводок выписок сообщения `message`, как у [`Statements`].
+    pub(crate) fn transactions_of(&self, message: CamtMessage, mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+        let CollectedStatements { statements, diagnostics } = self.statements_of(message, mode)?;
+        Ok(CollectedTransactions {
+            diagnostics,
+            ..statements.read_transactions(mode)?
+        })
+    }
+
+    /// Выписки документа; сообщение определяется по корневому тегу, по умолчанию — `BkToCstmrStmt`.
+    ///
+    /// # Ошибки
+    /// В режиме [`ReadMode::Strict`] возвращает [`FormatError`] при первой проводке, которую не удалось
+    /// разобрать. В режиме [`ReadMode::Lenient`] такие проводки в выписки не попадают и возвращаются
+    /// в `diagnostics`.
+    pub fn into_statements(self, mode: ReadMode) -> Result<CollectedStatements, FormatError> {
+        self.statements_of(self.message().unwrap_or(CamtMessage::Statement), mode)
+    }
+}
+
+/// Выписки документа; проводки, которые не удалось разобрать, пропускаются,
+/// см. [`Camt053Format::into_statements`].
+impl From<Camt053Format> for Statements {
+    fn from(value: Camt053Format) -> Self {
+        value.collect_statements(value.message().unwrap_or(CamtMessage::Statement)).statements
+    }
+}
+
+/// Выписки в документ `camt.053.001.02` с порядком элементов по схеме.
+///
+/// `GrpHdr/MsgId` — референс первой выписки, `CreDtTm` — дата исходящего остатка
+/// (в MT940 и CSV нет времени создания сообщения), поэтому результат воспроизводим.
+/// У выписки без остатков и проводок `CreDtTm` не записывается, у выписки без валюты —
+/// `Acct/Ccy`; такие документы не проходят [`crate::camt053_schema::validate`].
+impl From<Statements> for Camt053Format {
+    fn from(v: Statements) -> Self {
+        Self::from_statements_with(v, &BankTxCodeMap::default())
+    }
+}
+
+impl Camt053Format {
+    /// Выписки в документ, как `From<Statements>`, но недостающие коды ISO берутся из таблицы
+    /// `codes`, а не из встроенной.
+    pub fn from_statements_with(v: Statements, codes: &BankTxCodeMap) -> Self {
+        Self::from_statements(v, CamtMessage::Statement, codes)
+    }
 
-                    let ntry = crt_with_child(
-                        "Ntry",
-                        [
-                            amt,
-                            crt_with_text(
-                                "CdtDbtInd",
-                                Some(debi_cred_name(&stat.ext_debit_credit_indicator).to_string()),
-                            ),
-                            crt_with_text("RvslInd", Some(val.to_string())),
-                            crt_with_child(
-                                "ValDt",
-                                [crt_with_text(
-                                    "Dt",
-                                    Some(stat.value_date.format("%Y-%m-%d").to_string()),
-                                )].as_ref(),
-                            ),
-                            crt_with_text("Sts", Some("BOOK".to_string())),
-                            crt_with_child(
-                                "BkTxCd",
-                                [crt_with_child(
-                                    "Prtry",
-                                    [
-                                        crt_with_text("Cd", Some(cd_text.to_string())),
-                                        crt_with_text("Issr", Some(stat.transaction_type_ident_code.clone())),
-                                    ].as_ref(),
-                                )].as_ref(),
-                            ),
-                        ].as_ref(),
+    /// Выписки в документ сообщения `message` версии 001.02, см. `From<Statements>`.
+    pub(crate) fn from_statements(v: Statements, message: CamtMessage, codes: &BankTxCodeMap) -> Self {
+        let date = |d: NaiveDate| d.format("%Y-%m-%d").to_string();
+        let date_time = |d: NaiveDate| format!("{}T00:00:00", date(d));
+        let indicator = |x: &DebitOrCredit| match x {
+            DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => "DBIT",
+            DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => "CRDT",
+        };
+        let is_credit = |x: &DebitOrCredit| matches!(x, DebitOrCredit::Credit | DebitOrCredit::ReverseCredit);
+        let date_element = |name: &str, d: NaiveDate| Element::new(name).child(Element::text("Dt", date(d)));
+        let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+
+        // дата выписки: исходящий остаток, иначе входящий, иначе последняя проводка
+        let statement_date = |statement: &Statement| {
+            statement
+                .closing_balance
+                .as_ref()
+                .or(statement.opening_balance.as_ref())
+                .map(|b| b.date)
+                .or_else(|| statement.entries.iter().map(|e| e.value_date).max())
+        };
+
+        let message_id = v
+            .statements
+            .iter()
+            .find_map(|s| non_empty(&s.id))
+            .unwrap_or_else(|| "NONREF".to_string());
+        let created = v.statements.iter().filter_map(statement_date).max().map(date_time);
+
+        let mut body = Element::new(message.root_tag()).child(
+            Element::new("GrpHdr")
+                .child(Element::text("MsgId", message_id))
+                .text_opt("CreDtTm", created.as_ref()),
+        );
+
+        for statement in &v.statements {
+            let currency = statement.currency().unwrap_or_default().to_string();
+
+            let account_id = if Self::looks_like_iban(&statement.account.id) {
+                Element::text("IBAN", statement.account.id.as_str())
+            } else {
+                Element::new("Othr").child(Element::text("Id", statement.account.id.as_str()))
+            };
+            let acct = Element::new("Acct")
+                .child(Element::new("Id").child(account_id))
+                .text_opt("Ccy", non_empty(&currency).as_ref());
+
+            let period = match (&statement.opening_balance, &statement.closing_balance) {
+                (None, None) => None,
+                (from, to) => {
+                    let from = from.as_ref().or(to.as_ref()).map(|b| b.date).unwrap_or_default();
+                    let to = to.as_ref().or(statement.opening_balance.as_ref()).map(|b| b.date).unwrap_or_default();
+                    Some(
+                        Element::new("FrToDt")
+                            .child(Element::text("FrDtTm", date_time(from)))
+                            .child(Element::text("ToDtTm", format!("{}T23:59:59", date(to)))),
+                    )
+                }
+            };
+
+            let balances = [
+                (&statement.opening_balance, "OPBD"),
+                (&statement.closing_balance, "CLBD"),
+                (&statement.closing_available_balance, "CLAV"),
+                (&statement.forward_available_balance, "FWAV"),
+            ];
+            // в уведомлениях camt.054 остатков нет
+            let balances = balances.into_iter().filter(|_| message != CamtMessage::Notification);
+            let balances = balances.filter_map(|(bal, code)| {
+                let bal = bal.as_ref()?;
+                let ccy = if bal.currency.is_empty() { &currency } else { &bal.currency };
+                Some(
+                    Element::new("Bal")
+                        .child(Element::new("Tp").child(Element::new("CdOrPrtry").child(Element::text("Cd", code))))
+                        .child(Element::text("Amt", bal.amount.to_string()).attr("Ccy", ccy))
+                        .child(Element::text("CdtDbtInd", indicator(&bal.debit_credit_indicator)))
+                        .child(date_element("Dt", bal.date)),
+                )
+            });
+
+            // соседние проводки одного пакета с одним направлением собираются в один Ntry
+            let groups: Vec<&[Entry]> = statement
+                .entries
+                .chunk_by(|a, b| {
+                    a.batch.is_some()
+                        && a.batch == b.batch
+                        && is_credit(&a.debit_credit_indicator) == is_credit(&b.debit_credit_indicator)
+                })
+                .collect();
+            let (credits, debits): (Vec<&[Entry]>, Vec<&[Entry]>) =
+                groups.iter().partition(|g| is_credit(&g[0].debit_credit_indicator));
+            let total = |group: &[Entry]| group.iter().map(|e| e.amount).sum::<Decimal>();
+            let credit_sum: Decimal = credits.iter().map(|g| total(g)).sum();
+            let debit_sum: Decimal = debits.iter().map(|g| total(g)).sum();
+            let net = credit_sum - debit_sum;
+            let number_and_sum = |name: &str, count: usize, sum: Decimal| {
+                Element::new(name)
+                    .child(Element::text("NbOfNtries", count.to_string()))
+                    .child(Element::text("Sum", sum.to_string()))
+            };
+            let summary = Element::new("TxsSummry")
+                .child(
+                    Element::new("TtlNtries")
+                        .child(Element::text("NbOfNtries", groups.len().to_string()))
+                        .child(Element::text("TtlNetNtryAmt", net.abs().to_string()))
+                        .child(Element::text("CdtDbtInd", if net > Decimal::zero() { "CRDT" } else { "DBIT" })),
+                )
+                .child(number_and_sum("TtlCdtNtries", credits.len(), credit_sum))
+                .child(number_and_sum("TtlDbtNtries", debits.len(), debit_sum));
+
+            let party_account = |id: &String| {
+                if Self::looks_like_iban(id) {
+                    Element::text("IBAN", id.as_str())
+                } else {
+                    Element::new("Othr").child(Element::text("Id", id.as_str()))
+                }
+            };
+            // контрагент — плательщик поступления или получатель списания; `amount` — сумма части пакета
+            let tx_details = |stat: &Entry, amount: Option<Element>| {
+                let (party, agent) = if is_credit(&stat.debit_credit_indicator) {
+                    ("Dbtr", "DbtrAgt")
+                } else {
+                    ("Cdtr", "CdtrAgt")
+                };
+                let counterparty = stat.counterparty.clone().unwrap_or_default();
+                let inn = counterparty.inn.as_ref().map(|inn| {
+                    Element::new("Id").child(
+                        Element::new("OrgId").child(
+                            Element::new("Othr")
+                                .child(Element::text("Id", inn.as_str()))
+                                .child(Element::new("SchmeNm").child(Element::text("Cd", "TXID"))),
+                        ),
+                    )
+                });
+                let name_or_inn = counterparty.name.is_some() || inn.is_some();
+                let parties = Element::new("RltdPties")
+                    .child_opt(name_or_inn.then(|| Element::new(party).text_opt("Nm", counterparty.name.as_ref()).child_opt(inn)))
+                    .child_opt(
+                        counterparty
+                            .account
+                            .as_ref()
+                            .map(|a| Element::new(&format!("{party}Acct")).child(Element::new("Id").child(party_account(a)))),
                     );
-                    if let Some(entry) = stat.entry_date {
-                        ntry.borrow_mut().childrens.push(crt_with_child(
-                            "BookgDt",
-                            [crt_with_text(
-                                "Dt",
-                                Some(entry.format("%Y-%m-%d").to_string()),
-                            )].as_ref(),
-                        ))
-                    }
+                let agents = counterparty.bic.as_ref().map(|bic| {
+                    Element::new("RltdAgts").child(
+                        Element::new(agent).child(Element::new("FinInstnId").child(Element::text("BIC", bic.as_str()))),
+                    )
+                });
+                let remittance = (stat.remittance_info.is_some() || stat.creditor_ref.is_some()).then(|| {
+                    Element::new("RmtInf")
+                        .text_opt("Ustrd", stat.remittance_info.as_ref())
+                        .child_opt(stat.creditor_ref.as_ref().map(|r| {
+                            Element::new("Strd").child(Element::new("CdtrRefInf").child(Element::text("Ref", r.as_str())))
+                        }))
+                });
+
+                Element::new("TxDtls")
+                    .child(
+                        Element::new("Refs")
+                            .text_opt("InstrId", stat.document_number.as_ref())
+                            .text_opt("EndToEndId", non_empty(&stat.customer_ref).as_ref())
+                            .text_opt("TxId", stat.bank_ref.as_ref()),
+                    )
+                    .child_opt(amount.map(|a| Element::new("AmtDtls").child(Element::new("TxAmt").child(a))))
+                    .child_opt((!parties.children.is_empty()).then_some(parties))
+                    .child_opt(agents)
+                    .child_opt(remittance)
+                    .text_opt("AddtlTxInf", stat.additional_info.as_ref())
+            };
+            // код ISO в Domn (недостающий — по таблице codes), код SWIFT в Prtry с Issr SWIFT
+            let bank_tx_code = |stat: &Entry| {
+                let swift = non_empty(&stat.transaction_type_code);
+                let domain = stat
+                    .domain_code
+                    .clone()
+                    .or_else(|| codes.to_iso(swift.as_deref()?, stat.debit_credit_indicator))
+                    .or_else(|| swift.is_none().then(bank_tx_code::not_available));
+                Element::new("BkTxCd")
+                    .child_opt(domain.map(|d| {
+                        Element::new("Domn").child(Element::text("Cd", d.domain)).child(
+                            Element::new("Fmly")
+                                .child(Element::text("Cd", d.family))
+                                .child(Element::text("SubFmlyCd", d.sub_family)),
+                        )
+                    }))
+                    .child_opt(swift.map(|code| {
+                        Element::new("Prtry")
+                            .child(Element::text("Cd", format!("N{code}")))
+                            .child(Element::text("Issr", "SWIFT"))
+                    }))
+            };
+            let entry = |stat: &Entry| {
+                let reversal = matches!(
+                    stat.debit_credit_indicator,
+                    DebitOrCredit::ReverseCredit | DebitOrCredit::ReverseDebit
+                );
+
+                Element::new("Ntry")
+                    .child(
+                        Element::text("Amt", stat.amount.to_string())
+                            .attr("Ccy", stat.currency.as_deref().unwrap_or(&currency)),
+                    )
+                    .child(Element::text("CdtDbtInd", indicator(&stat.debit_credit_indicator)))
+                    .child(Element::text("RvslInd", reversal.to_string()))
+                    .child(Element::text("Sts", stat.status.code()))
+                    .child_opt(stat.booking_date.map(|d| date_element("BookgDt", d)))
+                    .child(date_element("ValDt", stat.value_date))
+                    .text_opt("AcctSvcrRef", stat.bank_ref.as_ref())
+                    .child(bank_tx_code(stat))
+                    .child(Element::new("NtryDtls").child(tx_details(stat, None)))
+                    .text_opt("AddtlNtryInf", stat.supplementary_details.as_ref())
+            };
+            // пакет: сумма Ntry и Btch/TtlAmt — итог частей, суммы частей в TxDtls/AmtDtls/TxAmt,
+            // даты, код операции и AddtlNtryInf — первой части
+            let batch_entry = |parts: &[Entry]| {
+                let first = &parts[0];
+                let amount = |name: &str, value: Decimal, stat: &Entry| {
+                    Element::text(name, value.to_string()).attr("Ccy", stat.currency.as_deref().unwrap_or(&currency))
+                };
+                let details = parts.iter().map(|part| tx_details(part, Some(amount("Amt", part.amount, part))));
+                let reversal = matches!(
+                    first.debit_credit_indicator,
+                    DebitOrCredit::ReverseCredit | DebitOrCredit::ReverseDebit
+                );
+
+                Element::new("Ntry")
+                    .child(amount("Amt", total(parts), first))
+                    .child(Element::text("CdtDbtInd", indicator(&first.debit_credit_indicator)))
+                    .child(Element::text("RvslInd", reversal.to_string()))
+                    .child(Element::text("Sts", first.status.code()))
+                    .child_opt(first.booking_date.map(|d| date_element("BookgDt", d)))
+                    .child(date_element("ValDt", first.value_date))
+                    .child(bank_tx_code(first))
+                    .child(
+                        Element::new("NtryDtls")
+                            .child(
+                                Element::new("Btch")
+                                    .text_opt("PmtInfId", first.batch.as_ref())
+                                    .child(Element::text("NbOfTxs", parts.len().to_string()))
+                                    .child(amount("TtlAmt", total(parts), first))
+                                    .child(Element::text("CdtDbtInd", indicator(&first.debit_credit_indicator))),
+                            )
+                            .children(details),
+                    )
+                    .text_opt("AddtlNtryInf", first.supplementary_details.as_ref())
+            };
+            let entries = groups.iter().map(|group| match group {
+                [stat] => entry(stat),
+                parts => batch_entry(parts),
+            });
+
+            let legal_sequence = statement.sequence_no.as_deref().and_then(non_empty);
+            let created = statement
+                .created
+                .map(|d| d.format("%Y-%m-%dT%H:%M:%S%:z").to_string())
+                .or_else(|| statement_date(statement).map(date_time));
+            body = body.child(
+                Element::new(message.statement_tag())
+                    .child(Element::text("Id", statement.id.as_str()))
+                    .text_opt("ElctrncSeqNb", non_empty(&statement.statement_no).as_ref())
+                    .text_opt("LglSeqNb", legal_sequence.as_ref())
+                    .text_opt("CreDtTm", created.as_ref())
+                    .child_opt(period)
+                    .child(acct)
+                    .children(balances)
+                    .child(summary)
+                    .children(entries)
+                    .text_opt(message.additional_info_tag(), statement.additional_info.as_ref()),
+            );
+        }
 
-                    if let Some(s) = &stat.supplementary_details {
-                        ntry.borrow_mut().childrens.push(crt_with_text("AddtlTxInf", Some(s.clone())))
-                    }
+        let version = Camt053Version::V02;
+        let document = Element::new("Document")
+            .attr("xmlns", &message.namespace(version))
+            .child(body);
+        let (tree, root) = document.into_tree();
+        let mut camt = Self::from_tree(tree, root);
+        camt.set_message_version(message, version);
+        camt.declaration = Some(XmlDeclaration::default());
+        camt
+    }
+}
 
-                    let refs = crt_with_child(
-                        "Refs",
-                        [crt_with_text("EndToEndId", Some(stat.customer_ref.clone()))].as_ref(),
-                        );
-                    if let Some(bank) = &stat.bank_ref {
-                        ntry.borrow_mut()
-                            .childrens
-                            .push(crt_with_text("AcctSvcrRef", Some(bank.clone())));
-                        refs.borrow_mut()
-                            .childrens
-                            .push(crt_with_text("TxId", Some(bank.clone())));
-                    }
+impl From<MT940Format> for Camt053Format {
+    fn from(v: MT940Format) -> Self {
+        Statements::from(v).into()
+    }
+}
 
-                    ntry.borrow_mut().childrens.push(crt_with_child(
-                        "NtryDtls",
-                        [crt_with_child(
-                            "TxDtls",
-                            [
-                                refs,
-                                crt_with_text("AddtlTxInf", stat.information_to_account_owner.clone()),
-                            ].as_ref(),
-                        )].as_ref()
-                    ));
-
-                    stmt_child.push(ntry);
+/// Участники операции из `TxDtls/RltdPties` и `TxDtls/RltdAgts`.
+#[derive(Default)]
+struct RelatedParties {
+    debtor: Counterparty,
+    creditor: Counterparty,
+    /// Кандидат в ИНН, подтверждаемый последующим `SchmeNm`.
+    inn_candidate: Option<(bool, String)>,
+}
+
+impl RelatedParties {
+    fn party(&mut self, debtor: bool) -> &mut Counterparty {
+        if debtor { &mut self.debtor } else { &mut self.creditor }
+    }
+
+    /// Разобрать тег по пути от `TxDtls`: `RltdPties/Dbtr/Nm`, `RltdPties/CdtrAcct/Id/IBAN`,
+    /// `RltdPties/Dbtr/Id/OrgId/Othr/Id`, `RltdAgts/DbtrAgt/FinInstnId/BIC` и т.п.
+    fn apply(&mut self, path: &str, text: String) {
+        if let Some(agent) = path.strip_prefix("RltdAgts/") {
+            match agent {
+                "DbtrAgt/FinInstnId/BIC" | "DbtrAgt/FinInstnId/BICFI" => self.debtor.bic = Some(text),
+                "CdtrAgt/FinInstnId/BIC" | "CdtrAgt/FinInstnId/BICFI" => self.creditor.bic = Some(text),
+                _ => (),
+            }
+            return;
+        }
+        let Some(path) = path.strip_prefix("RltdPties/") else { return };
+        let (debtor, tail) = if let Some(t) = path.strip_prefix("Dbtr") {
+            (true, t)
+        } else if let Some(t) = path.strip_prefix("Cdtr") {
+            (false, t)
+        } else {
+            return;
+        };
+        let tail = tail.replace("/Pty/", "/");
+
+        match tail.as_str() {
+            "/Nm" => self.party(debtor).name = Some(text),
+            "Acct/Id/IBAN" | "Acct/Id/Othr/Id" => self.party(debtor).account = Some(text),
+            "/Id/OrgId/Othr/Id" | "/Id/PrvtId/Othr/Id" => self.inn_candidate = Some((debtor, text)),
+            "/Id/OrgId/Othr/SchmeNm/Cd"
+            | "/Id/PrvtId/Othr/SchmeNm/Cd"
+            | "/Id/OrgId/Othr/SchmeNm/Prtry"
+            | "/Id/PrvtId/Othr/SchmeNm/Prtry" => {
+                if (text == "TXID" || text == "INN")
+                    && let Some((d, inn)) = self.inn_candidate.take()
+                {
+                    self.party(d).inn = Some(inn);
                 }
             }
-
-            stmt.borrow_mut().childrens = stmt_child;
-            root_ref.borrow_mut().childrens.push(stmt)
+            _ => (),
         }
-        Self::set_parent(&root_ref);
+    }
 
-        Self { root: root_ref }
+    /// Контрагент: плательщик для поступлений, получатель для списаний.
+    fn counterparty(self, direction: DebitOrCredit) -> Option<Counterparty> {
+        let counterparty = match direction {
+            DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => self.debtor,
+            DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => self.creditor,
+        };
+        (!counterparty.is_empty()).then_some(counterparty)
     }
 }
 
-impl TransactionsReader for Camt053Format {
-    fn collect_transactions(&self) -> Vec<Transaction> {
-        let mut transactions = Vec::new();
-        let mut transaction = None;
-        for tag in self.get_iter() {
-            let path = tag.path();
-            let Some(s) = path.find("/Stmt") else { continue };
-            match &path[s..] {
-                "/Stmt/Ntry" => {
-                    if let Some(t) = transaction {
-                        transactions.push(t);
+/// Проводка CAMT, собираемая по мере обхода дерева тегов.
+#[derive(Default)]
+struct PendingEntry {
+    /// Порядковый номер `Ntry` в документе (с 1).
+    row: usize,
+    /// Первая ошибка разбора в проводке.
+    error: Option<RowDiagnostic>,
+    has_amount: bool,
+    has_indicator: bool,
+    reversal: bool,
+    entry: Entry,
+    parties: RelatedParties,
+    domain: Vec<String>,
+    proprietary: Option<String>,
+    issuer: Option<String>,
+    remittance: Vec<String>,
+    end_to_end_id: Option<String>,
+    /// Первый из `PmtInfId`, `InstrId` и `MndtId` — референс клиента, если нет `EndToEndId`.
+    other_ref: Option<String>,
+    /// Референс банка `Ntry/AcctSvcrRef`.
+    entry_ref: Option<String>,
+    /// Первый референс банка из `TxDtls/Refs`.
+    details_ref: Option<String>,
+    /// Проводка — часть развёрнутого пакета: референс её `TxDtls` важнее общего `AcctSvcrRef`.
+    part: bool,
+}
+
+impl PendingEntry {
+    fn fail(&mut self, tag: &str, raw_value: &str, reason: &str) {
+        if self.error.is_none() {
+            self.error = Some(RowDiagnostic::new(self.row, tag, raw_value, reason));
+        }
+    }
+
+    fn parse_date(&mut self, tag: &str, text: &str) -> Option<NaiveDate> {
+        let date = Camt053Format::parse_date(text);
+        if date.is_none() {
+            self.fail(tag, text, "не удалось разобрать дату");
+        }
+        date
+    }
+
+    fn finish(self, codes: &BankTxCodeMap) -> (Entry, Option<RowDiagnostic>) {
+        let error = self
+            .error
+            .or_else(|| {
+                (!self.has_amount).then(|| RowDiagnostic::new(self.row, "Ntry/Amt", "", "в проводке нет суммы"))
+            })
+            .or_else(|| {
+                (!self.has_indicator).then(|| {
+                    RowDiagnostic::new(self.row, "Ntry/CdtDbtInd", "", "в проводке нет признака дебета/кредита")
+                })
+            });
+
+        let mut e = self.entry;
+        e.debit_credit_indicator = Camt053Format::with_reversal(e.debit_credit_indicator, self.reversal);
+        e.counterparty = self.parties.counterparty(e.debit_credit_indicator);
+        e.remittance_info = (!self.remittance.is_empty()).then(|| self.remittance.join(" "));
+        // EndToEndId NOTPROVIDED остаётся референсом, только если другого нет, как NONREF в MT940
+        e.customer_ref = match self.end_to_end_id {
+            Some(id) if id != "NOTPROVIDED" => id,
+            id => self.other_ref.or(id).unwrap_or_default(),
+        };
+        e.bank_ref = if self.part {
+            self.details_ref.or(self.entry_ref)
+        } else {
+            self.entry_ref.or(self.details_ref)
+        };
+        e.domain_code = self.domain.join("/").parse().ok();
+        e.transaction_type_code = match (self.proprietary, self.issuer) {
+            (Some(code), Some(issuer)) if issuer == "SWIFT" => code.strip_prefix('N').unwrap_or(&code).to_string(),
+            // прежние версии конвертера писали код SWIFT в Prtry/Issr; у банков там обычно свой код эмитента
+            (_, Some(code))
+                if e.domain_code.is_none()
+                    && codes.to_iso(&code, e.debit_credit_indicator).is_some() =>
+            {
+                code
+            }
+            _ => String::new(),
+        };
+        (e, error)
+    }
+
+    /// Разобрать проводку по тегам с путями от `Ntry`; `row` — её порядковый номер в документе (с 1),
+    /// `part` — теги части пакета, см. `Camt053Format::part_tags`.
+    ///
+    /// Вместе с проводкой возвращается первая ошибка разбора: неразобранная сумма, дата или признак
+    /// дебета/кредита, нет суммы или признака. Проводка с ошибкой заполнена, насколько удалось.
+    fn read<'a>(
+        tags: impl Iterator<Item = TagView<'a>>,
+        row: usize,
+        part: bool,
+        codes: &BankTxCodeMap,
+    ) -> (Entry, Option<RowDiagnostic>) {
+        let mut e = PendingEntry {
+            row,
+            part,
+            ..Default::default()
+        };
+        for tag in tags {
+            let Some(rest) = tag.path().strip_prefix("/Ntry") else { continue };
+            match rest {
+                "/Amt" => {
+                    let text = tag.text();
+                    match text.replace(",", ".").parse() {
+                        Ok(amount) => {
+                            e.entry.amount = amount;
+                            e.has_amount = true;
+                        }
+                        Err(_) => e.fail("Ntry/Amt", &text, "не удалось разобрать сумму"),
                     }
-                    transaction = Some(Transaction::default());
+                    e.entry.currency = tag.get_attr("Ccy");
                 }
-                "/Stmt/Ntry/Amt" => {
-                    if let Some(t) = &mut transaction {
-                        if let Ok(amount) = tag.text().replace(",", ".").parse() {
-                            t.amount = amount;
-                        }
-                        if let Some(curr) = tag.get_attr("Ccy") {
-                            t.currency = curr;
-                        }
+                "/CdtDbtInd" => {
+                    let text = tag.text();
+                    if text == "CRDT" || text == "DBIT" {
+                        e.entry.debit_credit_indicator = Camt053Format::parse_indicator(&text);
+                        e.has_indicator = true;
+                    } else {
+                        e.fail("Ntry/CdtDbtInd", &text, "ожидалось CRDT или DBIT");
                     }
                 }
-                "/Stmt/Ntry/CdtDbtInd" => {
-                    if let Some(t) = &mut transaction {
-                        match tag.text().as_str() {
-                            "DBIT" => t.operation_type = DebitOrCredit::Debit,
-                            "CRDT" => t.operation_type = DebitOrCredit::Credit,
-                            _ => t.operation_type = DebitOrCredit::Debit,
-                        }
+                "/RvslInd" => e.reversal = tag.text().trim() == "true",
+                "/Sts" | "/Sts/Cd" => e.entry.status = EntryStatus::from_code(&tag.text()),
+                "/ValDt/Dt" | "/ValDt/DtTm" => {
+                    if let Some(d) = e.parse_date(&format!("Ntry{rest}"), &tag.text()) {
+                        e.entry.value_date = d;
                     }
                 }
-                "/Stmt/Ntry/ValDt/Dt" => {
-                    let val = tag.text();
-                    if let Some(t) = &mut transaction
-                        && let Ok(d) = NaiveDate::parse_from_str(&val[..10], "%Y-%m-%d")
-                    {
-                        t.date = d;
+                "/BookgDt/Dt" | "/BookgDt/DtTm" => {
+                    e.entry.booking_date = e.parse_date(&format!("Ntry{rest}"), &tag.text());
+                }
+                "/AcctSvcrRef" => e.entry_ref = Some(tag.text()),
+                // AddtlTxInf прямо в Ntry писали прежние версии конвертера
+                "/AddtlNtryInf" | "/AddtlTxInf" => e.entry.supplementary_details = Some(tag.text()),
+                "/BkTxCd/Domn/Cd" | "/BkTxCd/Domn/Fmly/Cd" | "/BkTxCd/Domn/Fmly/SubFmlyCd" => {
+                    e.domain.push(tag.text())
+                }
+                "/BkTxCd/Prtry/Cd" => e.proprietary = Some(tag.text()),
+                "/BkTxCd/Prtry/Issr" => e.issuer = Some(tag.text()),
+                "/NtryDtls/TxDtls/Refs/EndToEndId" => e.end_to_end_id = Some(tag.text()),
+                "/NtryDtls/TxDtls/Refs/InstrId" => {
+                    e.entry.document_number = Some(tag.text());
+                    e.other_ref.get_or_insert_with(|| tag.text());
+                }
+                "/NtryDtls/TxDtls/Refs/PmtInfId" | "/NtryDtls/TxDtls/Refs/MndtId" => {
+                    e.other_ref.get_or_insert_with(|| tag.text());
+                }
+                "/NtryDtls/TxDtls/Refs/AcctSvcrRef" | "/NtryDtls/TxDtls/Refs/TxId" => {
+                    e.details_ref.get_or_insert_with(|| tag.text());
+                }
+                "/NtryDtls/TxDtls/RmtInf/Ustrd" => e.remittance.push(tag.text()),
+                "/NtryDtls/TxDtls/RmtInf/Strd/CdtrRefInf/Ref" => {
+                    e.entry.creditor_ref.get_or_insert_with(|| tag.text());
+                }
+                "/NtryDtls/TxDtls/AddtlTxInf" => match &mut e.entry.additional_info {
+                    Some(existing) => {
+                        existing.push(' ');
+                        existing.push_str(tag.text().as_str());
+                    }
+                    None => e.entry.additional_info = Some(tag.text()),
+                },
+                _ => {
+                    if let Some(path) = rest.strip_prefix("/NtryDtls/TxDtls/") {
+                        e.parties.apply(path, tag.text());
                     }
                 }
-                _ => (),
             }
         }
-        if let Some(t) = transaction {
-        transactions.push(t);
-        }
-        transactions
+        e.finish(codes)
+    }
+}
+
+impl TransactionsReader for Camt053Format {
+    fn read_transactions(&self, mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+        self.transactions_of(self.message().unwrap_or(CamtMessage::Statement), mode)
     }
 }
 
@@ -576,6 +1743,10 @@ impl TransactionsReader for Camt053Format {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bank_tx_code::DomainCode;
+    use crate::camt053_schema;
+    use crate::reconcile::reconcile;
+    use crate::transactions_holder::TransactionHolder;
     use std::io::Cursor;
 
     #[test]
@@ -610,6 +1781,233 @@ mod tests {
         assert_eq!(result, FormatError::UnknownValueFormat("Ошибка разбора формата camt053 : не удалось распарсить атрибуты тега --!".to_string()));
     }
 
+    #[test]
+    fn keeps_declaration_empty_tags_cdata_and_namespaces() {
+        let xml = concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+            "<?xml-stylesheet type=\"text/xsl\" href=\"camt.xsl\"?>\n",
+            "<!-- выгрузка банка -->\n",
+            "<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:camt.053.001.02\" ",
+            "xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+            "  <Stmt>\n",
+            "    <Id>A &amp; B</Id>\n",
+            "    <Cd/>\n",
+            "    <?bank-hint ignore?>\n",
+            "    <AddtlStmtInf><![CDATA[<не тег>]]></AddtlStmtInf>\n",
+            "  </Stmt>\n",
+            "</Document>",
+        );
+        let mut camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+
+        let decl = camt.declaration().unwrap();
+        assert_eq!(decl.encoding.as_deref(), Some("UTF-8"));
+        assert_eq!(decl.standalone.as_deref(), Some("yes"));
+        let texts: Vec<(String, String)> = camt.get_iter().map(|t| (t.path().to_string(), t.text())).collect();
+        assert!(texts.contains(&("/Document/Stmt/Id".to_string(), "A & B".to_string())));
+        assert!(texts.contains(&("/Document/Stmt/AddtlStmtInf".to_string(), "<не тег>".to_string())));
+
+        let mut out = Vec::new();
+        camt.write_to(&mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+        assert_eq!(
+            written,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+                "<?xml-stylesheet type=\"text/xsl\" href=\"camt.xsl\"?>\n",
+                "<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:camt.053.001.02\" ",
+                "xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">",
+                "<Stmt><Id>A &amp; B</Id><Cd/><AddtlStmtInf><![CDATA[<не тег>]]></AddtlStmtInf></Stmt>",
+                "</Document>",
+            )
+        );
+
+        let mut again = Vec::new();
+        Camt053Format::from_read(&mut Cursor::new(written.clone()))
+            .unwrap()
+            .write_to(&mut again)
+            .unwrap();
+        assert_eq!(String::from_utf8(again).unwrap(), written);
+    }
+
+    #[test]
+    fn prefixed_elements_resolve_like_default_namespace() {
+        let plain = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08"><BkToCstmrStmt>
+            <GrpHdr><OrgnlBizQry><MsgId>Q1</MsgId></OrgnlBizQry></GrpHdr>
+            <Stmt><Id>S1</Id><Ntry><Amt Ccy="EUR">5.00</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry></Stmt>
+        </BkToCstmrStmt></Document>"#;
+        let prefixed = r#"<ns2:Document xmlns:ns2="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08"><ns2:BkToCstmrStmt>
+            <ns2:GrpHdr><ns2:OrgnlBizQry><ns2:MsgId>Q1</ns2:MsgId></ns2:OrgnlBizQry></ns2:GrpHdr>
+            <ns2:Stmt><ns2:Id>S1</ns2:Id><ns2:Ntry><ns2:Amt Ccy="EUR">5.00</ns2:Amt><ns2:CdtDbtInd>CRDT</ns2:CdtDbtInd></ns2:Ntry></ns2:Stmt>
+        </ns2:BkToCstmrStmt></ns2:Document>"#;
+
+        let plain = Camt053Format::from_read(&mut Cursor::new(plain)).unwrap();
+        let prefixed = Camt053Format::from_read(&mut Cursor::new(prefixed)).unwrap();
+        assert_eq!(plain.version(), Some(Camt053Version::V08));
+        assert_eq!(prefixed.version(), Some(Camt053Version::V08));
+
+        let plain = Statements::from(plain);
+        assert_eq!(plain.statements[0].related_ref.as_deref(), Some("Q1"));
+        assert_eq!(plain.statements[0].entries.len(), 1);
+        assert_eq!(Statements::from(prefixed), plain);
+    }
+
+    #[test]
+    fn version_parses_and_maps_to_namespace() {
+        assert_eq!("camt.053.001.11".parse::<Camt053Version>().unwrap(), Camt053Version::V11);
+        assert_eq!("001.04".parse::<Camt053Version>().unwrap(), Camt053Version::V04);
+        assert_eq!("2".parse::<Camt053Version>().unwrap(), Camt053Version::V02);
+        assert!("12".parse::<Camt053Version>().is_err());
+        assert_eq!(
+            Camt053Version::V09.namespace(),
+            "urn:iso:std:iso:20022:tech:xsd:camt.053.001.09"
+        );
+        assert_eq!(Camt053Version::from_namespace("urn:iso:std:iso:20022:tech:xsd:camt.052.001.02"), None);
+    }
+
+    #[test]
+    fn set_version_rewrites_or_adds_document_namespace() {
+        let xml = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02 camt.053.001.02.xsd"><BkToCstmrStmt/></Document>"#;
+        let mut camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        assert_eq!(camt.version(), Some(Camt053Version::V02));
+        camt.set_version(Camt053Version::V08);
+        assert_eq!(camt.version(), Some(Camt053Version::V08));
+        let mut out = Vec::new();
+        camt.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08 camt.053.001.08.xsd"><BkToCstmrStmt/></Document>"#
+        );
+
+        let mut bare = Camt053Format::from_read(&mut Cursor::new("<BkToCstmrStmt><Stmt/></BkToCstmrStmt>")).unwrap();
+        assert_eq!(bare.version(), None);
+        bare.set_version(Camt053Version::V04);
+        assert_eq!(bare.version(), Some(Camt053Version::V04));
+        let paths: Vec<String> = bare.get_iter().map(|t| t.path().to_string()).collect();
+        assert_eq!(paths, vec!["/Document", "/Document/BkToCstmrStmt", "/Document/BkToCstmrStmt/Stmt"]);
+    }
+
+    #[test]
+    fn edits_keep_tree_consistent_and_are_written_back() {
+        let xml = r#"<ns2:Document xmlns:ns2="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02"><ns2:BkToCstmrStmt><ns2:Stmt><ns2:Id>1</ns2:Id><ns2:Acct><ns2:Id><ns2:IBAN>DE00</ns2:IBAN></ns2:Id></ns2:Acct><ns2:Ntry><ns2:Amt Ccy="EUR">1.00</ns2:Amt></ns2:Ntry><ns2:Ntry><ns2:Amt Ccy="EUR">2.00</ns2:Amt></ns2:Ntry></ns2:Stmt></ns2:BkToCstmrStmt></ns2:Document>"#;
+        let mut camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+
+        camt.set_text(camt.find("Stmt/Acct/Id/IBAN").unwrap(), "DE89370400440532013000").unwrap();
+        let amt = camt.find("Ntry[1]/Amt").unwrap();
+        camt.set_attr(amt, "Ccy", "USD").unwrap();
+        let stmt = camt.find("Stmt").unwrap();
+        let mut info = Tag::new("AddtlStmtInf");
+        info.text = Some("исправлено".to_string());
+        let info = camt.insert_child(stmt, None, info).unwrap();
+        assert_eq!(camt.tree().parent(info), Some(stmt));
+        camt.remove(camt.find("Ntry[0]").unwrap()).unwrap();
+        camt.move_node(camt.find("Stmt/Id").unwrap(), stmt, Some(1)).unwrap();
+
+        let mut out = Vec::new();
+        camt.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"<ns2:Document xmlns:ns2="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02"><ns2:BkToCstmrStmt><ns2:Stmt><ns2:Acct><ns2:Id><ns2:IBAN>DE89370400440532013000</ns2:IBAN></ns2:Id></ns2:Acct><ns2:Id>1</ns2:Id><ns2:Ntry><ns2:Amt Ccy="USD">2.00</ns2:Amt></ns2:Ntry><ns2:AddtlStmtInf>исправлено</ns2:AddtlStmtInf></ns2:Stmt></ns2:BkToCstmrStmt></ns2:Document>"#
+        );
+        assert_eq!(camt.version(), Some(Camt053Version::V02));
+    }
+
+    #[test]
+    fn edits_report_bad_targets() {
+        let xml = r#"<Document><Stmt><Ntry/><Ntry/></Stmt></Document>"#;
+        let mut camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+
+        assert!(matches!(camt.find("Ntry"), Err(FormatError::DataFormatError(_))));
+        assert!(matches!(camt.find("Bal"), Err(FormatError::DataFormatError(_))));
+        assert!(camt.remove(camt.root()).is_err());
+        let stmt = camt.find("Stmt").unwrap();
+        let ntry = camt.find("Ntry[0]").unwrap();
+        assert!(camt.move_node(stmt, ntry, None).is_err());
+        assert!(camt.insert_child(stmt, Some(3), Tag::new("Id")).is_err());
+        assert!(camt.move_node(ntry, stmt, Some(2)).is_err());
+        camt.move_node(ntry, stmt, Some(1)).unwrap();
+
+        // удалённый тег остаётся в арене, но правки в нём больше не принимаются
+        camt.remove(ntry).unwrap();
+        assert!(camt.set_text(ntry, "x").is_err());
+        assert!(camt.set_attr(ntry, "Ccy", "EUR").is_err());
+        assert!(camt.insert_child(ntry, None, Tag::new("Amt")).is_err());
+        assert!(camt.move_node(ntry, stmt, None).is_err());
+        assert!(camt.remove(ntry).is_err());
+        assert_eq!(camt.tree()[stmt].children().len(), 1);
+    }
+
+    #[test]
+    fn write_with_indents_and_controls_declaration() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?><Document><Stmt><Id>1</Id><Acct/><Ntry><Amt Ccy="EUR">1.00</Amt></Ntry></Stmt></Document>"#;
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+
+        let mut out = Vec::new();
+        camt.write_with(&mut out, &XmlWriteOptions::pretty(2)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Document>\n  <Stmt>\n    <Id>1</Id>\n    <Acct/>\n    <Ntry>\n      <Amt Ccy=\"EUR\">1.00</Amt>\n    </Ntry>\n  </Stmt>\n</Document>\n"
+        );
+
+        let mut out = Vec::new();
+        let options = XmlWriteOptions {
+            declaration: Some(false),
+            ..Default::default()
+        };
+        camt.write_with(&mut out, &options).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("<Document><Stmt>"));
+
+        let bare = Camt053Format::from_read(&mut Cursor::new("<Document/>")).unwrap();
+        let mut out = Vec::new();
+        let options = XmlWriteOptions {
+            declaration: Some(true),
+            encoding: Some("ISO-8859-1".to_string()),
+            ..Default::default()
+        };
+        bare.write_with(&mut out, &options).unwrap();
+        // в документе только ASCII, поэтому байты совпадают с ISO-8859-1
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<Document/>"
+        );
+
+        let cyrillic = Camt053Format::from_read(&mut Cursor::new("<Document><Nm>Ромашка</Nm></Document>")).unwrap();
+        assert!(matches!(
+            cyrillic.write_with(&mut Vec::new(), &options),
+            Err(FormatError::UnknownValueFormat(_))
+        ));
+        let utf8 = XmlWriteOptions {
+            encoding: Some("utf-8".to_string()),
+            ..options
+        };
+        cyrillic.write_with(&mut Vec::new(), &utf8).unwrap();
+    }
+
+    #[test]
+    fn canonical_output_is_identical_for_equal_documents() {
+        let first = r#"<?xml version="1.0"?><Document xmlns="urn:x" b="2" a="1"><Stmt><AddtlStmtInf>two   words</AddtlStmtInf><Acct/></Stmt></Document>"#;
+        let second = r#"<Document a="1" xmlns="urn:x"  b="2">
+            <Stmt>
+                <AddtlStmtInf><![CDATA[two
+                words]]></AddtlStmtInf>
+                <Acct></Acct>
+            </Stmt>
+        </Document>"#;
+
+        let canonical = |xml: &str| {
+            let mut out = Vec::new();
+            Camt053Format::from_read(&mut Cursor::new(xml))
+                .unwrap()
+                .write_with(&mut out, &XmlWriteOptions::canonical())
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(canonical(first), canonical(second));
+        assert_eq!(
+            canonical(first),
+            r#"<Document xmlns="urn:x" a="1" b="2"><Stmt><AddtlStmtInf>two words</AddtlStmtInf><Acct></Acct></Stmt></Document>"#
+        );
+    }
+
     #[test]
     fn write_to_does_not_serialize_virtual_root() {
         let xml = r#"<Document><Stmt><Id>1</Id></Stmt></Document>"#;
@@ -625,6 +2023,329 @@ mod tests {
         assert!(s.contains("</Document>"));
     }
 
+    #[test]
+    fn collect_transactions_fills_references_counterparty_and_description() {
+        let xml = r#"<BkToCstmrStmt><Stmt><Ntry>
+            <Amt Ccy="RUB">150.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+            <BookgDt><Dt>2024-03-01</Dt></BookgDt><ValDt><Dt>2024-03-02</Dt></ValDt>
+            <AcctSvcrRef>BANK-1</AcctSvcrRef>
+            <BkTxCd><Domn><Cd>PMNT</Cd><Fmly><Cd>RCDT</Cd><SubFmlyCd>DMCT</SubFmlyCd></Fmly></Domn></BkTxCd>
+            <NtryDtls><TxDtls>
+                <Refs><InstrId>42</InstrId><EndToEndId>E2E-1</EndToEndId></Refs>
+                <RltdPties>
+                    <Dbtr><Nm>ООО Ромашка</Nm><Id><OrgId><Othr><Id>7735602068</Id><SchmeNm><Cd>TXID</Cd></SchmeNm></Othr></OrgId></Id></Dbtr>
+                    <DbtrAcct><Id><Othr><Id>40702810440000030888</Id></Othr></Id></DbtrAcct>
+                    <Cdtr><Nm>Мы</Nm></Cdtr>
+                </RltdPties>
+                <RmtInf><Ustrd>Оплата по счёту 7</Ustrd></RmtInf>
+            </TxDtls></NtryDtls>
+        </Ntry></Stmt></BkToCstmrStmt>"#;
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        let txs = camt.collect_transactions().unwrap();
+        let t = &txs[0];
+
+        assert_eq!(t.date, NaiveDate::from_ymd_opt(2024, 3, 2).unwrap());
+        assert_eq!(t.booking_date, NaiveDate::from_ymd_opt(2024, 3, 1));
+        assert_eq!(t.bank_ref.as_deref(), Some("BANK-1"));
+        assert_eq!(t.customer_ref.as_deref(), Some("E2E-1"));
+        assert_eq!(t.document_number.as_deref(), Some("42"));
+        assert_eq!(t.bank_tx_code.as_deref(), Some("PMNT/RCDT/DMCT"));
+        assert_eq!(t.description.as_deref(), Some("Оплата по счёту 7"));
+
+        let party = t.counterparty.as_ref().unwrap();
+        assert_eq!(party.name.as_deref(), Some("ООО Ромашка"));
+        assert_eq!(party.inn.as_deref(), Some("7735602068"));
+        assert_eq!(party.account.as_deref(), Some("40702810440000030888"));
+    }
+
+    #[test]
+    fn bank_transaction_codes_map_to_swift_and_back() {
+        let xml = r#"<BkToCstmrStmt><Stmt>
+            <Ntry><Amt Ccy="EUR">1.00</Amt><CdtDbtInd>DBIT</CdtDbtInd><ValDt><Dt>2024-03-01</Dt></ValDt>
+                <BkTxCd><Domn><Cd>ACMT</Cd><Fmly><Cd>MDOP</Cd><SubFmlyCd>CHRG</SubFmlyCd></Fmly></Domn></BkTxCd></Ntry>
+            <Ntry><Amt Ccy="EUR">2.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2024-03-01</Dt></ValDt>
+                <BkTxCd><Prtry><Cd>NDDT</Cd><Issr>SWIFT</Issr></Prtry></BkTxCd></Ntry>
+            <Ntry><Amt Ccy="EUR">3.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2024-03-01</Dt></ValDt>
+                <BkTxCd><Prtry><Cd>NONREF</Cd><Issr>INT</Issr></Prtry></BkTxCd></Ntry>
+            <Ntry><Amt Ccy="EUR">4.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2024-03-01</Dt></ValDt>
+                <BkTxCd><Domn><Cd>PMNT</Cd><Fmly><Cd>RCDT</Cd><SubFmlyCd>ESCT</SubFmlyCd></Fmly></Domn></BkTxCd></Ntry>
+        </Stmt></BkToCstmrStmt>"#;
+        let mut statements = Statements::from(Camt053Format::from_read(&mut Cursor::new(xml)).unwrap());
+        let entries = &statements.statements[0].entries;
+        assert_eq!(entries[0].domain_code, Some(DomainCode::new("ACMT", "MDOP", "CHRG")));
+        assert_eq!(entries[0].transaction_type_code, "");
+        assert_eq!(entries[1].transaction_type_code, "DDT");
+        assert_eq!(entries[2].transaction_type_code, "INT");
+
+        let mut codes = BankTxCodeMap::default();
+        codes.insert("PMNT/RCDT/ESCT", "SEP").unwrap();
+        statements.map_bank_tx_codes(&codes);
+        let entries = &statements.statements[0].entries;
+        let swift: Vec<&str> = entries.iter().map(|e| e.transaction_type_code.as_str()).collect();
+        assert_eq!(swift, ["CHG", "DDT", "INT", "SEP"]);
+        assert_eq!(entries[1].domain_code, Some(DomainCode::new("PMNT", "RDDT", "ESDD")));
+        assert_eq!(entries[2].domain_code, Some(DomainCode::new("ACMT", "MCOP", "INTR")));
+
+        let mt: MT940Format = statements.clone().into();
+        let lines = &mt.transactions[0].statement_lines;
+        assert_eq!(lines[0].transaction_type_ident_code, "CHG");
+        assert_eq!(lines[3].transaction_type_ident_code, "SEP");
+
+        let written = Camt053Format::from(statements);
+        let codes: Vec<String> = written
+            .select("//Ntry/BkTxCd/Domn/Fmly/SubFmlyCd")
+            .unwrap()
+            .iter()
+            .map(|t| t.text())
+            .collect();
+        assert_eq!(codes, ["CHRG", "ESDD", "INTR", "ESCT"]);
+    }
+
+    #[test]
+    fn user_bank_transaction_codes_reach_readers_and_writers() {
+        let xml = r#"<BkToCstmrStmt><Stmt><Id>S1</Id>
+            <Ntry><Amt Ccy="EUR">1.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2024-03-01</Dt></ValDt>
+                <BkTxCd><Prtry><Cd>XYZ</Cd><Issr>SEP</Issr></Prtry></BkTxCd></Ntry>
+        </Stmt></BkToCstmrStmt>"#;
+        let mut codes = BankTxCodeMap::default();
+        codes.insert("PMNT/RCDT/ESCT", "SEP").unwrap();
+
+        // код SWIFT в Issr, как писали прежние версии, распознаётся только по таблице пользователя
+        let mut camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        assert_eq!(Statements::from(camt.clone()).statements[0].entries[0].transaction_type_code, "");
+        camt.set_bank_tx_codes(codes.clone());
+        assert_eq!(Statements::from(camt).statements[0].entries[0].transaction_type_code, "SEP");
+
+        let mut stream = crate::camt053_stream::Camt053Reader::new(Cursor::new(xml));
+        stream.set_bank_tx_codes(codes.clone());
+        let statement = stream.next_statement().unwrap().unwrap();
+        assert_eq!(statement.entries[0].transaction_type_code, "SEP");
+
+        // при записи недостающий код берётся из переданной таблицы, а не из встроенной
+        let mut entry = statement.entries[0].clone();
+        entry.domain_code = Some(DomainCode::new("PMNT", "RCDT", "ESCT"));
+        entry.transaction_type_code.clear();
+        let statements = Statements {
+            statements: vec![Statement {
+                entries: vec![entry.clone()],
+                ..statement.clone()
+            }],
+        };
+        let code_of = |mt: MT940Format| mt.transactions[0].statement_lines[0].transaction_type_ident_code.clone();
+        assert_eq!(code_of(MT940Format::from(statements.clone())), "TRF");
+        assert_eq!(code_of(MT940Format::from_statements_with(statements, &codes)), "SEP");
+
+        entry.domain_code = None;
+        entry.transaction_type_code = "SEP".to_string();
+        let statements = Statements {
+            statements: vec![Statement {
+                entries: vec![entry],
+                ..statement
+            }],
+        };
+        let domain = |camt: Camt053Format| camt.select("//Ntry/BkTxCd/Domn/Fmly/SubFmlyCd").unwrap().len();
+        assert_eq!(domain(Camt053Format::from(statements.clone())), 0);
+        assert_eq!(domain(Camt053Format::from_statements_with(statements, &codes)), 1);
+    }
+
+    #[test]
+    fn reversals_and_statuses_are_kept_and_non_booked_entries_skip_mt940() {
+        let xml = r#"<BkToCstmrStmt><Stmt>
+            <Ntry><Amt Ccy="EUR">10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><RvslInd>true</RvslInd><Sts>BOOK</Sts>
+                <ValDt><Dt>2024-03-01</Dt></ValDt></Ntry>
+            <Ntry><Amt Ccy="EUR">20.00</Amt><CdtDbtInd>DBIT</CdtDbtInd><RvslInd>true</RvslInd><Sts><Cd>BOOK</Cd></Sts>
+                <ValDt><Dt>2024-03-01</Dt></ValDt></Ntry>
+            <Ntry><Amt Ccy="EUR">30.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><RvslInd>false</RvslInd><Sts>PDNG</Sts>
+                <ValDt><Dt>2024-03-02</Dt></ValDt></Ntry>
+            <Ntry><Amt Ccy="EUR">40.00</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>INFO</Sts>
+                <ValDt><Dt>2024-03-02</Dt></ValDt></Ntry>
+        </Stmt></BkToCstmrStmt>"#;
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        let txs = camt.collect_transactions().unwrap();
+        let kinds: Vec<(DebitOrCredit, EntryStatus)> = txs.iter().map(|t| (t.operation_type, t.status)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (DebitOrCredit::ReverseCredit, EntryStatus::Booked),
+                (DebitOrCredit::ReverseDebit, EntryStatus::Booked),
+                (DebitOrCredit::Credit, EntryStatus::Pending),
+                (DebitOrCredit::Debit, EntryStatus::Info),
+            ]
+        );
+        let mut booked = TransactionHolder::new(camt.clone()).unwrap();
+        booked.retain_booked();
+        assert_eq!(booked.len(), 2);
+
+        let statements = Statements::from(camt);
+        let entries = &statements.statements[0].entries;
+        assert_eq!(entries[0].debit_credit_indicator, DebitOrCredit::ReverseCredit);
+        assert_eq!(entries[2].status, EntryStatus::Pending);
+
+        let mut mt: MT940Format = statements.clone().into();
+        let mut written = Vec::new();
+        mt.write_to(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.contains(":61:240301RD10.00"));
+        assert!(written.contains(":61:240301RC20.00"));
+        assert!(!written.contains("30.00") && !written.contains("40.00"));
+
+        let back = Camt053Format::from(statements.clone());
+        let text = |path: &str| back.select(path).unwrap().iter().map(|t| t.text()).collect::<Vec<_>>();
+        assert_eq!(text("//Ntry/CdtDbtInd"), ["CRDT", "DBIT", "CRDT", "DBIT"]);
+        assert_eq!(text("//Ntry/RvslInd"), ["true", "true", "false", "false"]);
+        assert_eq!(text("//Ntry/Sts"), ["BOOK", "BOOK", "PDNG", "INFO"]);
+        let kinds = |s: &Statements| {
+            s.statements[0].entries.iter().map(|e| (e.debit_credit_indicator, e.status)).collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(&Statements::from(back)), kinds(&statements));
+    }
+
+    #[test]
+    fn version_switch_restructures_status_parties_and_bic() {
+        let xml = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02"><BkToCstmrStmt><Stmt>
+            <Ntry><Amt Ccy="EUR">10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>PDNG</Sts>
+                <ValDt><Dt>2024-03-01</Dt></ValDt>
+                <NtryDtls><TxDtls><RltdPties><Dbtr><Nm>ООО Ромашка</Nm></Dbtr><DbtrAcct><Id><IBAN>DE1</IBAN></Id></DbtrAcct></RltdPties>
+                <RltdAgts><DbtrAgt><FinInstnId><BIC>DEUTDEFF</BIC></FinInstnId></DbtrAgt></RltdAgts></TxDtls></NtryDtls></Ntry>
+        </Stmt></BkToCstmrStmt></Document>"#;
+        let original = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        let text = |camt: &Camt053Format, path: &str| {
+            camt.select(path).unwrap().iter().map(|t| t.text()).collect::<Vec<_>>()
+        };
+
+        let mut v08 = original.clone();
+        v08.set_version(Camt053Version::V08);
+        assert_eq!(text(&v08, "//Ntry/Sts/Cd"), ["PDNG"]);
+        assert_eq!(text(&v08, "//Ntry/Sts"), [""]);
+        assert_eq!(text(&v08, "//RltdPties/Dbtr/Pty/Nm"), ["ООО Ромашка"]);
+        assert_eq!(text(&v08, "//DbtrAgt/FinInstnId/BICFI"), ["DEUTDEFF"]);
+        assert!(camt053_schema::validate(&v08).iter().all(|i| !i.to_string().contains("Sts")));
+        assert_eq!(Statements::from(v08.clone()), Statements::from(original.clone()));
+
+        let mut v02 = v08;
+        v02.set_version(Camt053Version::V02);
+        assert_eq!(text(&v02, "//Ntry/Sts"), ["PDNG"]);
+        assert_eq!(text(&v02, "//RltdPties/Dbtr/Nm"), ["ООО Ромашка"]);
+        assert_eq!(text(&v02, "//DbtrAgt/FinInstnId/BIC"), ["DEUTDEFF"]);
+        let xml = |mut camt: Camt053Format| {
+            let mut out = Vec::new();
+            camt.write_to(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(xml(v02), xml(original.clone()));
+
+        let mut written = Camt053Format::from(Statements::from(original));
+        written.set_version(Camt053Version::V08);
+        assert_eq!(text(&written, "//Ntry/Sts/Cd"), ["PDNG"]);
+    }
+
+    #[test]
+    fn bad_entries_fail_strict_and_are_reported_in_lenient_mode() {
+        let xml = r#"<BkToCstmrStmt><Stmt>
+            <Ntry><Amt Ccy="EUR">1.00</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry>
+            <Ntry><Amt Ccy="EUR">abc</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry>
+            <Ntry><Amt Ccy="EUR">2.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2024-13-01</Dt></ValDt></Ntry>
+            <Ntry><Amt Ccy="EUR">3.00</Amt></Ntry>
+        </Stmt></BkToCstmrStmt>"#;
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+
+        let err = camt.read_transactions(ReadMode::Strict).unwrap_err();
+        assert_eq!(err, FormatError::DataFormatError(
+            "Ошибка разбора формата camt053 : строка 2, Ntry/Amt = \"abc\": не удалось разобрать сумму".to_string()
+        ));
+
+        let collected = camt.read_transactions(ReadMode::Lenient).unwrap();
+        assert_eq!(collected.transactions.len(), 1);
+        assert_eq!(collected.diagnostics, vec![
+            RowDiagnostic::new(2, "Ntry/Amt", "abc", "не удалось разобрать сумму"),
+            RowDiagnostic::new(3, "Ntry/ValDt/Dt", "2024-13-01", "не удалось разобрать дату"),
+            RowDiagnostic::new(4, "Ntry/CdtDbtInd", "", "в проводке нет признака дебета/кредита"),
+        ]);
+    }
+
+    fn batch_example() -> Camt053Format {
+        Camt053Format::from_read(&mut std::fs::File::open("./examples/data/camt053_batch.exmpl").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn batch_entries_expand_into_transactions_per_tx_details() {
+        let mut camt = batch_example();
+        let collapsed = camt.collect_transactions().unwrap();
+        assert_eq!(collapsed.len(), 2);
+        assert_eq!(collapsed[0].amount, Decimal::from(3000));
+        assert_eq!(collapsed[0].bank_ref.as_deref(), Some("BATCH-7781"));
+
+        camt.set_expand_batches(true);
+        let txs = camt.collect_transactions().unwrap();
+        assert_eq!(txs.len(), 4);
+        let amounts: Vec<Decimal> = txs.iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, [1200, 1000, 800, 100].map(Decimal::from));
+
+        let t = &txs[1];
+        assert_eq!(t.operation_type, DebitOrCredit::Debit);
+        assert_eq!(t.currency, "EUR");
+        assert_eq!(t.date, NaiveDate::from_ymd_opt(2020, 1, 31).unwrap());
+        assert_eq!(t.customer_ref.as_deref(), Some("SAL-01-002"));
+        assert_eq!(t.bank_ref.as_deref(), Some("TX-002"));
+        // код SWIFT в Prtry/Issr, как писали прежние версии конвертера
+        assert_eq!(t.bank_tx_code.as_deref(), Some("TRF"));
+        assert_eq!(t.description.as_deref(), Some("Salary January"));
+        let party = t.counterparty.as_ref().unwrap();
+        assert_eq!(party.name.as_deref(), Some("Petrova Anna"));
+        assert_eq!(party.account.as_deref(), Some("NL39RABO0300065264"));
+
+        // обычная проводка не меняется
+        assert_eq!(txs[3], collapsed[1]);
+
+        // без сумм в TxDtls пакет не разворачивается
+        let xml = r#"<BkToCstmrStmt><Stmt><Ntry><Amt Ccy="EUR">3</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+            <NtryDtls><TxDtls><Refs><EndToEndId>A</EndToEndId></Refs></TxDtls>
+            <TxDtls><Refs><EndToEndId>B</EndToEndId></Refs></TxDtls></NtryDtls></Ntry></Stmt></BkToCstmrStmt>"#;
+        let mut camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        camt.set_expand_batches(true);
+        assert_eq!(camt.collect_transactions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn expanded_batch_reconciles_line_by_line_and_is_regrouped_on_write() {
+        let mut camt = batch_example();
+        camt.set_expand_batches(true);
+        let statements = Statements::from(camt.clone());
+        let entries = &statements.statements[0].entries;
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].customer_ref, "SAL-01-001");
+        assert_eq!(entries[2].amount, Decimal::from(800));
+        assert_eq!(entries[2].additional_info, None);
+        assert!(entries[..3].iter().all(|e| e.batch.as_deref() == Some("SAL-2020-01")));
+        assert_eq!(entries[3].batch, None);
+
+        // платежи по одному, как в реестре бухгалтерии, сверяются с развёрнутым пакетом
+        let mut payments = statements.clone();
+        payments.statements[0].entries.truncate(3);
+        for entry in &mut payments.statements[0].entries {
+            entry.batch = None;
+        }
+        let payments = TransactionHolder::new(payments).unwrap();
+        let camt_side = TransactionHolder::new(camt.clone()).unwrap();
+        let result = reconcile(&payments, &camt_side);
+        assert_eq!(result.matched.len(), 3);
+        assert_eq!(result.unmatched_right.len(), 1);
+
+        let mut written = Camt053Format::from(statements.clone());
+        assert!(camt053_schema::validate(&written).is_empty());
+        assert_eq!(written.select("//Ntry").unwrap().len(), 2);
+        let text = |path: &str| written.select_first(path).unwrap().map(|t| t.text());
+        assert_eq!(text("//Ntry/Amt").as_deref(), Some("3000.00"));
+        assert_eq!(text("//Btch/PmtInfId").as_deref(), Some("SAL-2020-01"));
+        assert_eq!(text("//Btch/NbOfTxs").as_deref(), Some("3"));
+        assert_eq!(text("//TtlNtries/NbOfNtries").as_deref(), Some("2"));
+
+        written.set_expand_batches(true);
+        let mut expected = statements;
+        expected.map_bank_tx_codes(&BankTxCodeMap::default());
+        assert_eq!(Statements::from(written), expected);
+    }
+
     #[cfg(test)]
     mod camt_to_mt_tests {
         use crate::camt053_format::Camt053Format;
@@ -1,20 +1,21 @@
-use crate::camt053_format::Tag;
 use crate::camt053_iterator::Camt053IterStatus::{Empty, Exists, NoOtherChildrens};
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::camt053_query::Camt053Path;
+use crate::camt053_tree::{NodeId, Tag, TagTree};
+use crate::error::FormatError;
 
-pub struct TagView {
-    node: Rc<RefCell<Tag>>,
+pub struct TagView<'a> {
+    tree: &'a TagTree,
+    node: NodeId,
     path: String,
 }
 
-impl TagView {
-    pub fn new(node: Rc<RefCell<Tag>>, path: String) -> TagView {
-        Self { node, path }
+impl<'a> TagView<'a> {
+    pub fn new(tree: &'a TagTree, node: NodeId, path: String) -> TagView<'a> {
+        Self { tree, node, path }
     }
 
     pub fn text(&self) -> String {
-        if let Some(value) = &self.node.borrow().text {
+        if let Some(value) = &self.tag().text {
             return value.clone();
         }
         String::new()
@@ -25,58 +26,187 @@ impl TagView {
     }
 
     pub fn get_attr(&self, name: &str) -> Option<String> {
+        self.tag().attr(name).map(str::to_string)
+    }
+
+    /// Идентификатор тега в дереве документа.
+    pub fn node(&self) -> NodeId {
         self.node
-            .borrow()
-            .attrs
-            .iter()
-            .find(|&x| x.0 == name)
-            .map(|x| x.1.clone())
     }
 
+    pub fn tag(&self) -> &'a Tag {
+        &self.tree[self.node]
+    }
+
+    /// Родительский тег.
+    pub fn parent(&self) -> Option<TagView<'a>> {
+        let parent = self.tree.parent(self.node)?;
+        let path = match self.path.rfind('/') {
+            Some(end) if end > 0 => self.path[..end].to_string(),
+            // путь начинался с этого тега, родителя в нём нет
+            _ => path_of(self.tree, parent),
+        };
+        Some(TagView::new(self.tree, parent, path))
+    }
+
+    /// Дети тега в порядке документа.
+    pub fn children(&self) -> impl Iterator<Item = TagView<'a>> + '_ {
+        self.tree.children(self.node).iter().map(|&c| self.child_view(c))
+    }
+
+    /// Первый ребёнок с локальным именем `name`.
+    pub fn child(&self, name: &str) -> Option<TagView<'a>> {
+        self.tree.child(self.node, name).map(|c| self.child_view(c))
+    }
+
+    /// Следующий тег того же родителя.
+    pub fn next_sibling(&self) -> Option<TagView<'a>> {
+        self.sibling(|pos| pos.checked_add(1))
+    }
+
+    /// Предыдущий тег того же родителя.
+    pub fn prev_sibling(&self) -> Option<TagView<'a>> {
+        self.sibling(|pos| pos.checked_sub(1))
+    }
+
+    /// Теги по пути из [`crate::camt053_query`]; относительный путь отсчитывается от этого тега,
+    /// абсолютный — от верхнего тега дерева.
+    pub fn select(&self, path: &str) -> Result<Vec<TagView<'a>>, FormatError> {
+        let path: Camt053Path = path.parse()?;
+        let top = self.tree.top(self.node);
+        Ok(path
+            .select(self.tree, top, Some(self.node))
+            .into_iter()
+            .map(|id| {
+                let path = if path.is_absolute() {
+                    path_of(self.tree, id)
+                } else {
+                    self.path_below(id)
+                };
+                TagView::new(self.tree, id, path)
+            })
+            .collect())
+    }
+
+    /// Первый тег по пути, см. [`TagView::select`].
+    pub fn select_first(&self, path: &str) -> Result<Option<TagView<'a>>, FormatError> {
+        Ok(self.select(path)?.into_iter().next())
+    }
+
+    fn child_view(&self, id: NodeId) -> TagView<'a> {
+        let path = format!("{}/{}", self.path, self.tree[id].local_name());
+        TagView::new(self.tree, id, path)
+    }
+
+    fn sibling(&self, step: impl Fn(usize) -> Option<usize>) -> Option<TagView<'a>> {
+        let parent = self.tree.parent(self.node)?;
+        let siblings = self.tree.children(parent);
+        let pos = siblings.iter().position(|&s| s == self.node)?;
+        let id = *siblings.get(step(pos)?)?;
+        let prefix = &self.path[..self.path.rfind('/').unwrap_or(0)];
+        let path = format!("{prefix}/{}", self.tree[id].local_name());
+        Some(TagView::new(self.tree, id, path))
+    }
+
+    /// Путь к потомку `id` этого тега.
+    fn path_below(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut current = id;
+        while current != self.node {
+            names.push(self.tree[current].local_name());
+            match self.tree.parent(current) {
+                Some(parent) => current = parent,
+                None => return path_of(self.tree, id),
+            }
+        }
+        let mut path = self.path.clone();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        path
+    }
+}
+
+/// Путь вида `/A/B/C` от верхнего тега дерева до `id`.
+pub(crate) fn path_of(tree: &TagTree, mut id: NodeId) -> String {
+    let mut names = vec![tree[id].local_name()];
+    while let Some(parent) = tree.parent(id) {
+        names.push(tree[parent].local_name());
+        id = parent;
+    }
+    names.iter().rev().fold(String::new(), |mut path, name| {
+        path.push('/');
+        path.push_str(name);
+        path
+    })
 }
 
-pub struct Camt053Iter {
-    tag: Rc<RefCell<Tag>>,
-    path: Vec<String>,
+pub struct Camt053Iter<'a> {
+    tree: &'a TagTree,
+    start: NodeId,
+    tag: NodeId,
+    /// Путь к текущему тегу вида `/A/B/C`.
+    path: String,
+    /// Для каждого уровня: длина `path` до его добавления и индекс тега среди детей родителя,
+    /// чтобы подниматься и переходить к соседу без пересборки строки и поиска по детям.
+    levels: Vec<(usize, usize)>,
     first: bool,
 }
 
-impl Camt053Iter {
-    pub fn new(tag: Rc<RefCell<Tag>>) -> Self {
-        let p: Vec<String> = vec![tag.borrow().name.clone()];
-        Self { tag, path: p, first: true}
+impl<'a> Camt053Iter<'a> {
+    /// Обход поддерева с корнем `tag`; соседи и родители `tag` не посещаются.
+    pub fn new(tree: &'a TagTree, tag: NodeId) -> Self {
+        let mut iter = Self {
+            tree,
+            start: tag,
+            tag,
+            path: String::new(),
+            levels: Vec::new(),
+            first: true,
+        };
+        iter.push(tag, 0);
+        iter
     }
 
-    fn get_next_in_parent(&mut self) -> Camt053IterStatus {
-        let parent = match self.tag.borrow().parent.upgrade() {
-            Some(p) => {
-                self.path.pop();
-                p
-            }
-            None => return Empty,
-        };
+    fn push(&mut self, tag: NodeId, index: usize) {
+        self.levels.push((self.path.len(), index));
+        self.path.push('/');
+        self.path.push_str(self.tree[tag].local_name());
+    }
 
-        let status: Camt053IterStatus;
-        let next = {
-            let pb = parent.borrow();
-
-            let idx = match pb.childrens.iter().position(|x| Rc::ptr_eq(x, &self.tag)) {
-                Some(i) => i,
-                None => return Empty,
-            };
-
-            if idx + 1 >= pb.childrens.len() {
-                status = NoOtherChildrens;
-                Rc::clone(&parent)
-            } else {
-                status = Exists;
-                self.path.push(pb.childrens[idx + 1].borrow().name.clone());
-                Rc::clone(&pb.childrens[idx + 1])
-            }
+    fn pop(&mut self) -> usize {
+        let (len, index) = self.levels.pop().unwrap_or_default();
+        self.path.truncate(len);
+        index
+    }
+
+    fn view(&self) -> TagView<'a> {
+        TagView {
+            tree: self.tree,
+            node: self.tag,
+            path: self.path.clone(),
+        }
+    }
+
+    fn get_next_in_parent(&mut self) -> Camt053IterStatus {
+        if self.tag == self.start {
+            return Empty;
+        }
+        let Some(parent) = self.tree.parent(self.tag) else {
+            return Empty;
         };
+        let idx = self.pop();
 
-        self.tag = next;
-        status
+        let siblings = self.tree.children(parent);
+        if idx + 1 >= siblings.len() {
+            self.tag = parent;
+            NoOtherChildrens
+        } else {
+            self.tag = siblings[idx + 1];
+            self.push(self.tag, idx + 1);
+            Exists
+        }
     }
 }
 
@@ -86,45 +216,25 @@ enum Camt053IterStatus {
     Empty,
 }
 
-impl Iterator for Camt053Iter {
-    type Item = TagView;
+impl<'a> Iterator for Camt053Iter<'a> {
+    type Item = TagView<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.first {
             self.first = false;
-            return Some(TagView {
-                node: self.tag.clone(),
-                path: '/'.to_string() + self.path.join("/").as_str(),
-            })
+            return Some(self.view());
         }
-        
-        let children = {
-            let g = self.tag.borrow();
-            let mut result = None;
-            if !g.childrens.is_empty() {
-                self.path.push(g.childrens[0].borrow().name.clone());
-                result = Some(g.childrens[0].clone());
-            }
-            result
-        };
 
-        if let Some(children) = children {
-            self.tag = children;
-            return Some(TagView {
-                node: self.tag.clone(),
-                path: '/'.to_string() + self.path.join("/").as_str(),
-            });
+        if let Some(&child) = self.tree.children(self.tag).first() {
+            self.tag = child;
+            self.push(child, 0);
+            return Some(self.view());
         }
 
         loop {
             match self.get_next_in_parent() {
                 NoOtherChildrens => continue,
-                Exists => {
-                    return Some(TagView {
-                        node: self.tag.clone(),
-                        path: '/'.to_string() + self.path.join("/").as_str(),
-                    });
-                }
+                Exists => return Some(self.view()),
                 Empty => break,
             }
         }
@@ -137,34 +247,23 @@ impl Iterator for Camt053Iter {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::rc::Weak;
 
-    fn tag(name: &str, text: Option<&str>) -> Rc<RefCell<Tag>> {
-        Rc::new(RefCell::new(Tag {
-            name: name.to_string(),
-            text: text.map(|s| s.to_string()),
-            attrs: Vec::new(),
-            childrens: Vec::new(),
-            parent: Weak::new(),
-        }))
+    fn tag(tree: &mut TagTree, parent: Option<NodeId>, name: &str, text: Option<&str>) -> NodeId {
+        let mut tag = Tag::new(name);
+        tag.text = text.map(|s| s.to_string());
+        tree.push(parent, tag)
     }
 
     #[test]
     fn iter_builds_paths_and_text() {
+        let mut tree = TagTree::new();
         // virtual root
-        let root = tag("root", None);
-        let a = tag("A", None);
-        let b = tag("B", Some("bbb"));
-        let c = tag("C", None);
-
-        b.borrow_mut().parent = Rc::downgrade(&a);
-        c.borrow_mut().parent = Rc::downgrade(&a);
-        a.borrow_mut().childrens.push(Rc::clone(&b));
-        a.borrow_mut().childrens.push(Rc::clone(&c));
-        a.borrow_mut().parent = Rc::downgrade(&root);
-        root.borrow_mut().childrens.push(Rc::clone(&a));
-
-        let got: Vec<(String, String)> = Camt053Iter::new(root)
+        let root = tag(&mut tree, None, "root", None);
+        let a = tag(&mut tree, Some(root), "A", None);
+        tag(&mut tree, Some(a), "B", Some("bbb"));
+        tag(&mut tree, Some(a), "C", None);
+
+        let got: Vec<(String, String)> = Camt053Iter::new(&tree, root)
             .map(|v| (v.path().to_string(), v.text()))
             .collect();
 
@@ -179,15 +278,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn iter_stays_inside_subtree() {
+        let mut tree = TagTree::new();
+        let root = tag(&mut tree, None, "root", None);
+        for name in ["A", "B"] {
+            let child = tag(&mut tree, Some(root), name, None);
+            tag(&mut tree, Some(child), "X", Some(name));
+        }
+
+        let a = tree.children(root)[0];
+        let got: Vec<String> = Camt053Iter::new(&tree, a).map(|v| v.path().to_string()).collect();
+        assert_eq!(got, vec!["/A".to_string(), "/A/X".to_string()]);
+    }
+
     #[test]
     fn tagview_attr_works() {
-        let n = tag("Amt", Some("10.00"));
-        n.borrow_mut()
-            .attrs
-            .push(("Ccy".to_string(), "EUR".to_string()));
+        let mut tree = TagTree::new();
+        let n = tag(&mut tree, None, "Amt", Some("10.00"));
+        tree[n].attrs.push(("Ccy".to_string(), "EUR".to_string()));
 
-        let v = TagView::new(n, "/Stmt/Ntry/Amt".to_string());
+        let v = TagView::new(&tree, n, "/Stmt/Ntry/Amt".to_string());
         assert_eq!(v.get_attr("Ccy").as_deref(), Some("EUR"));
         assert_eq!(v.get_attr("Missing"), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn tagview_navigates_tree() {
+        let mut tree = TagTree::new();
+        let root = tag(&mut tree, None, "Stmt", None);
+        let ntry = tag(&mut tree, Some(root), "Ntry", None);
+        tag(&mut tree, Some(ntry), "Amt", Some("1.00"));
+        tag(&mut tree, Some(ntry), "CdtDbtInd", Some("CRDT"));
+        tag(&mut tree, Some(root), "AddtlStmtInf", Some("info"));
+
+        let view = Camt053Iter::new(&tree, ntry).next().unwrap();
+        let amt = view.child("Amt").unwrap();
+        assert_eq!(amt.path(), "/Ntry/Amt");
+        assert_eq!(amt.next_sibling().unwrap().text(), "CRDT");
+        assert!(amt.prev_sibling().is_none());
+
+        // родитель за пределами пути обхода получает путь от верхнего тега
+        let stmt = view.parent().unwrap();
+        assert_eq!(stmt.path(), "/Stmt");
+        let names: Vec<String> = stmt.children().map(|c| c.path().to_string()).collect();
+        assert_eq!(names, vec!["/Stmt/Ntry", "/Stmt/AddtlStmtInf"]);
+        assert_eq!(view.next_sibling().unwrap().text(), "info");
+
+        let crdt = stmt.select("Ntry[CdtDbtInd='CRDT']/Amt").unwrap();
+        assert_eq!(crdt.len(), 1);
+        assert_eq!(crdt[0].path(), "/Stmt/Ntry/Amt");
+        assert_eq!(amt.select_first("/Stmt/AddtlStmtInf").unwrap().unwrap().text(), "info");
+    }
+}
@@ -0,0 +1,974 @@
+//! Типизированная модель сообщения CAMT.053 (`BkToCstmrStmt`).
+//!
+//! Модель строится из дерева тегов [`Camt053Format`] и записывается обратно в него, поэтому
+//! вместо строковых путей вида `/Stmt/Ntry/Amt` можно обращаться к полям:
+//! `document.statements[0].entries[3].amount.currency`.
+//!
+//! Поддерживаются элементы, которые встречаются в выписках банков: заголовок группы, счёт,
+//! остатки, сводка оборотов, проводки с деталями, участниками и назначением платежа.
+//! Остальные элементы при чтении пропускаются.
+
+use crate::camt053_format::{Camt053Format, Camt053Version};
+use crate::camt053_tree::{Element, NodeId, Tag, TagTree};
+use crate::common::debit_credit::DebitOrCredit;
+use crate::error::{FormatError, GeneratorFormatError};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+/// Тег дерева, из которого читается модель.
+#[derive(Clone, Copy)]
+pub(crate) struct Node<'a> {
+    pub(crate) tree: &'a TagTree,
+    pub(crate) id: NodeId,
+}
+
+impl<'a> Node<'a> {
+    fn tag(&self) -> &'a Tag {
+        &self.tree[self.id]
+    }
+
+    fn with_id(&self, id: NodeId) -> Node<'a> {
+        Node { tree: self.tree, id }
+    }
+}
+
+/// Сообщение `BkToCstmrStmt`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Camt053Document {
+    /// Версия сообщения; при записи определяет пространство имён `Document`
+    /// (по умолчанию `camt.053.001.02`).
+    pub version: Option<Camt053Version>,
+    pub group_header: GroupHeader,
+    pub statements: Vec<AccountStatement>,
+}
+
+/// Заголовок группы (`GrpHdr`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GroupHeader {
+    /// `MsgId`
+    pub message_id: String,
+    /// `CreDtTm`
+    pub creation_date_time: Option<String>,
+    /// `OrgnlBizQry/MsgId`
+    pub original_business_query: Option<String>,
+    /// `AddtlInf`
+    pub additional_info: Option<String>,
+}
+
+/// Выписка (`Stmt`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AccountStatement {
+    /// `Id`
+    pub id: String,
+    /// `ElctrncSeqNb`
+    pub electronic_sequence_number: Option<String>,
+    /// `LglSeqNb`
+    pub legal_sequence_number: Option<String>,
+    /// `CreDtTm`
+    pub creation_date_time: Option<String>,
+    /// `FrToDt`
+    pub period: Option<DateTimePeriod>,
+    /// `Acct`
+    pub account: CashAccount,
+    /// `Bal`
+    pub balances: Vec<CashBalance>,
+    /// `TxsSummry`
+    pub transactions_summary: Option<TransactionsSummary>,
+    /// `Ntry`
+    pub entries: Vec<ReportEntry>,
+    /// `AddtlStmtInf`
+    pub additional_info: Option<String>,
+}
+
+impl AccountStatement {
+    /// Остаток с кодом `code` (`OPBD`, `CLBD`, ...).
+    pub fn balance(&self, code: &str) -> Option<&CashBalance> {
+        self.balances.iter().find(|b| b.code == code)
+    }
+}
+
+/// Период выписки (`FrToDt`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DateTimePeriod {
+    /// `FrDtTm`
+    pub from: String,
+    /// `ToDtTm`
+    pub to: String,
+}
+
+/// Идентификатор счёта: `IBAN` или `Othr/Id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountIdentification {
+    Iban(String),
+    Other(String),
+}
+
+impl Default for AccountIdentification {
+    fn default() -> Self {
+        AccountIdentification::Other(String::new())
+    }
+}
+
+impl AccountIdentification {
+    pub fn value(&self) -> &str {
+        match self {
+            AccountIdentification::Iban(v) | AccountIdentification::Other(v) => v,
+        }
+    }
+}
+
+/// Счёт (`Acct`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CashAccount {
+    /// `Id`
+    pub id: AccountIdentification,
+    /// `Ccy`
+    pub currency: Option<String>,
+    /// `Nm`
+    pub name: Option<String>,
+    /// `Ownr/Nm`
+    pub owner_name: Option<String>,
+    /// `Svcr/FinInstnId/BIC` (`BICFI` начиная с версии 04).
+    pub servicer_bic: Option<String>,
+}
+
+/// Сумма с валютой (`Amt Ccy="..."`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Amount {
+    pub value: Decimal,
+    pub currency: String,
+}
+
+/// Дата (`Dt`) или дата и время (`DtTm`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateAndDateTime {
+    Date(NaiveDate),
+    /// Значение `DtTm` как в документе, со смещением часового пояса, если оно указано.
+    DateTime(String),
+}
+
+impl DateAndDateTime {
+    /// Календарная дата.
+    pub fn date(&self) -> Option<NaiveDate> {
+        match self {
+            DateAndDateTime::Date(d) => Some(*d),
+            DateAndDateTime::DateTime(s) => NaiveDate::parse_from_str(s.get(..10)?, "%Y-%m-%d").ok(),
+        }
+    }
+}
+
+/// Остаток (`Bal`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CashBalance {
+    /// `Tp/CdOrPrtry/Cd` или `Tp/CdOrPrtry/Prtry`: `OPBD`, `CLBD`, `CLAV`, `FWAV`, ...
+    pub code: String,
+    /// `Amt`
+    pub amount: Amount,
+    /// `CdtDbtInd`
+    pub credit_debit: DebitOrCredit,
+    /// `Dt`
+    pub date: Option<DateAndDateTime>,
+}
+
+/// Количество и сумма проводок (`NbOfNtries`, `Sum`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NumberAndSum {
+    pub number_of_entries: Option<u64>,
+    pub sum: Option<Decimal>,
+}
+
+/// Сводка оборотов (`TxsSummry`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TransactionsSummary {
+    /// `TtlNtries`
+    pub total_entries: Option<NumberAndSum>,
+    /// `TtlCdtNtries`
+    pub total_credit_entries: Option<NumberAndSum>,
+    /// `TtlDbtNtries`
+    pub total_debit_entries: Option<NumberAndSum>,
+}
+
+/// Код банковской операции (`BkTxCd`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BankTransactionCode {
+    /// `Domn/Cd`
+    pub domain: Option<String>,
+    /// `Domn/Fmly/Cd`
+    pub family: Option<String>,
+    /// `Domn/Fmly/SubFmlyCd`
+    pub sub_family: Option<String>,
+    /// `Prtry/Cd`
+    pub proprietary_code: Option<String>,
+    /// `Prtry/Issr`
+    pub proprietary_issuer: Option<String>,
+}
+
+/// Проводка (`Ntry`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReportEntry {
+    /// `NtryRef`
+    pub reference: Option<String>,
+    /// `Amt`
+    pub amount: Amount,
+    /// `CdtDbtInd`
+    pub credit_debit: DebitOrCredit,
+    /// `RvslInd`
+    pub reversal: Option<bool>,
+    /// `Sts` (`Sts/Cd` начиная с версии 08): `BOOK`, `PDNG`, `INFO`.
+    pub status: Option<String>,
+    /// `BookgDt`
+    pub booking_date: Option<DateAndDateTime>,
+    /// `ValDt`
+    pub value_date: Option<DateAndDateTime>,
+    /// `AcctSvcrRef`
+    pub account_servicer_ref: Option<String>,
+    /// `BkTxCd`
+    pub bank_transaction_code: Option<BankTransactionCode>,
+    /// `NtryDtls`
+    pub details: Vec<EntryDetails>,
+    /// `AddtlNtryInf`
+    pub additional_info: Option<String>,
+}
+
+/// Детали проводки (`NtryDtls`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EntryDetails {
+    /// `Btch/NbOfTxs`
+    pub batch_number_of_transactions: Option<u64>,
+    /// `TxDtls`
+    pub transactions: Vec<TransactionDetails>,
+}
+
+/// Детали операции (`TxDtls`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TransactionDetails {
+    /// `Refs`
+    pub references: Option<TransactionReferences>,
+    /// `AmtDtls/TxAmt/Amt` (`Amt` начиная с версии 03).
+    pub amount: Option<Amount>,
+    /// `CdtDbtInd`
+    pub credit_debit: Option<DebitOrCredit>,
+    /// `RltdPties`
+    pub related_parties: Option<RelatedParties>,
+    /// `RmtInf`
+    pub remittance_info: Option<RemittanceInformation>,
+    /// `AddtlTxInf`
+    pub additional_info: Option<String>,
+}
+
+/// Референсы операции (`Refs`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TransactionReferences {
+    pub message_id: Option<String>,
+    pub account_servicer_ref: Option<String>,
+    pub payment_information_id: Option<String>,
+    pub instruction_id: Option<String>,
+    pub end_to_end_id: Option<String>,
+    pub transaction_id: Option<String>,
+    pub mandate_id: Option<String>,
+}
+
+/// Участник операции (`Dbtr`, `Cdtr`, ...).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Party {
+    /// `Nm`
+    pub name: Option<String>,
+    /// `Id/OrgId/Othr/Id` или `Id/PrvtId/Othr/Id`.
+    pub id: Option<String>,
+    /// `Id/.../Othr/SchmeNm/Cd` или `Prtry`.
+    pub id_scheme: Option<String>,
+    /// Идентификатор относится к частному лицу (`PrvtId`), а не к организации (`OrgId`).
+    pub private: bool,
+}
+
+/// Участники операции (`RltdPties`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RelatedParties {
+    /// `Dbtr` (`Dbtr/Pty` начиная с версии 08).
+    pub debtor: Option<Party>,
+    /// `DbtrAcct/Id`
+    pub debtor_account: Option<AccountIdentification>,
+    /// `Cdtr` (`Cdtr/Pty` начиная с версии 08).
+    pub creditor: Option<Party>,
+    /// `CdtrAcct/Id`
+    pub creditor_account: Option<AccountIdentification>,
+}
+
+/// Структурированное назначение платежа (`Strd`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StructuredRemittance {
+    /// `CdtrRefInf/Ref`
+    pub creditor_reference: Option<String>,
+    /// `AddtlRmtInf`
+    pub additional_info: Vec<String>,
+}
+
+/// Назначение платежа (`RmtInf`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemittanceInformation {
+    /// `Ustrd`
+    pub unstructured: Vec<String>,
+    /// `Strd`
+    pub structured: Vec<StructuredRemittance>,
+}
+
+fn error(details: String) -> FormatError {
+    Camt053Format::data_format_error(&details)
+}
+
+fn child<'a>(node: &Node<'a>, name: &str) -> Option<Node<'a>> {
+    node.tree.child(node.id, name).map(|id| node.with_id(id))
+}
+
+fn children<'a>(node: &Node<'a>, name: &str) -> Vec<Node<'a>> {
+    node.tree
+        .children(node.id)
+        .iter()
+        .filter(|&&c| node.tree[c].local_name() == name)
+        .map(|&c| node.with_id(c))
+        .collect()
+}
+
+/// Узел по относительному пути `A/B/C` (первый подходящий на каждом шаге).
+fn at<'a>(node: &Node<'a>, path: &str) -> Option<Node<'a>> {
+    path.split('/').try_fold(*node, |n, name| child(&n, name))
+}
+
+fn text(node: &Node, path: &str) -> Option<String> {
+    at(node, path).and_then(|n| n.tag().text.clone())
+}
+
+fn texts(node: &Node, name: &str) -> Vec<String> {
+    children(node, name)
+        .iter()
+        .filter_map(|n| n.tag().text.clone())
+        .collect()
+}
+
+fn parse_decimal(value: &str, path: &str) -> Result<Decimal, FormatError> {
+    value
+        .trim()
+        .replace(',', ".")
+        .parse()
+        .map_err(|_| error(format!("не удалось разобрать сумму {value} в {path}")))
+}
+
+fn parse_amount(node: &Node, path: &str) -> Result<Amount, FormatError> {
+    let value = node.tag().text.clone().unwrap_or_default();
+    let currency = node.tag().attr("Ccy").unwrap_or_default().to_string();
+    Ok(Amount {
+        value: parse_decimal(&value, path)?,
+        currency,
+    })
+}
+
+fn parse_indicator(value: &str, path: &str) -> Result<DebitOrCredit, FormatError> {
+    match value {
+        "CRDT" => Ok(DebitOrCredit::Credit),
+        "DBIT" => Ok(DebitOrCredit::Debit),
+        _ => Err(error(format!("неизвестный признак {value} в {path}, ожидалось CRDT или DBIT"))),
+    }
+}
+
+fn parse_date(node: &Node, path: &str) -> Result<Option<DateAndDateTime>, FormatError> {
+    if let Some(dt) = text(node, "Dt") {
+        let date = NaiveDate::parse_from_str(dt.trim(), "%Y-%m-%d")
+            .map_err(|_| error(format!("не удалось разобрать дату {dt} в {path}/Dt")))?;
+        return Ok(Some(DateAndDateTime::Date(date)));
+    }
+    Ok(text(node, "DtTm").map(DateAndDateTime::DateTime))
+}
+
+fn parse_account_id(node: &Node) -> Option<AccountIdentification> {
+    text(node, "IBAN")
+        .map(AccountIdentification::Iban)
+        .or_else(|| text(node, "Othr/Id").map(AccountIdentification::Other))
+}
+
+fn parse_number_and_sum(node: &Node, path: &str) -> Result<NumberAndSum, FormatError> {
+    Ok(NumberAndSum {
+        number_of_entries: text(node, "NbOfNtries").and_then(|n| n.trim().parse().ok()),
+        sum: text(node, "Sum").map(|s| parse_decimal(&s, path)).transpose()?,
+    })
+}
+
+fn parse_party(node: &Node) -> Party {
+    // Начиная с версии 08 реквизиты участника вложены в `Pty`.
+    let node = child(node, "Pty").unwrap_or(*node);
+    let (id_node, private) = match at(&node, "Id/OrgId/Othr") {
+        Some(n) => (Some(n), false),
+        None => (at(&node, "Id/PrvtId/Othr"), true),
+    };
+    Party {
+        name: text(&node, "Nm"),
+        id: id_node.as_ref().and_then(|n| text(n, "Id")),
+        id_scheme: id_node
+            .as_ref()
+            .and_then(|n| text(n, "SchmeNm/Cd").or_else(|| text(n, "SchmeNm/Prtry"))),
+        private: private && id_node.is_some(),
+    }
+}
+
+fn parse_transaction(node: &Node, path: &str) -> Result<TransactionDetails, FormatError> {
+    let references = child(node, "Refs").map(|r| TransactionReferences {
+        message_id: text(&r, "MsgId"),
+        account_servicer_ref: text(&r, "AcctSvcrRef"),
+        payment_information_id: text(&r, "PmtInfId"),
+        instruction_id: text(&r, "InstrId"),
+        end_to_end_id: text(&r, "EndToEndId"),
+        transaction_id: text(&r, "TxId"),
+        mandate_id: text(&r, "MndtId"),
+    });
+    let amount = match child(node, "Amt").or_else(|| at(node, "AmtDtls/TxAmt/Amt")) {
+        Some(a) => Some(parse_amount(&a, &format!("{path}/Amt"))?),
+        None => None,
+    };
+    let credit_debit = text(node, "CdtDbtInd")
+        .map(|v| parse_indicator(&v, &format!("{path}/CdtDbtInd")))
+        .transpose()?;
+    let related_parties = child(node, "RltdPties").map(|p| RelatedParties {
+        debtor: child(&p, "Dbtr").map(|n| parse_party(&n)),
+        debtor_account: at(&p, "DbtrAcct/Id").and_then(|n| parse_account_id(&n)),
+        creditor: child(&p, "Cdtr").map(|n| parse_party(&n)),
+        creditor_account: at(&p, "CdtrAcct/Id").and_then(|n| parse_account_id(&n)),
+    });
+    let remittance_info = child(node, "RmtInf").map(|r| RemittanceInformation {
+        unstructured: texts(&r, "Ustrd"),
+        structured: children(&r, "Strd")
+            .iter()
+            .map(|s| StructuredRemittance {
+                creditor_reference: text(s, "CdtrRefInf/Ref"),
+                additional_info: texts(s, "AddtlRmtInf"),
+            })
+            .collect(),
+    });
+    Ok(TransactionDetails {
+        references,
+        amount,
+        credit_debit,
+        related_parties,
+        remittance_info,
+        additional_info: text(node, "AddtlTxInf"),
+    })
+}
+
+pub(crate) fn parse_entry(node: &Node, path: &str) -> Result<ReportEntry, FormatError> {
+    let Some(amount) = child(node, "Amt") else {
+        return Err(error(format!("в {path} нет суммы Amt")));
+    };
+    let Some(indicator) = text(node, "CdtDbtInd") else {
+        return Err(error(format!("в {path} нет признака CdtDbtInd")));
+    };
+    let bank_transaction_code = child(node, "BkTxCd").map(|c| BankTransactionCode {
+        domain: text(&c, "Domn/Cd"),
+        family: text(&c, "Domn/Fmly/Cd"),
+        sub_family: text(&c, "Domn/Fmly/SubFmlyCd"),
+        proprietary_code: text(&c, "Prtry/Cd"),
+        proprietary_issuer: text(&c, "Prtry/Issr"),
+    });
+
+    let mut details = Vec::new();
+    for (i, d) in children(node, "NtryDtls").iter().enumerate() {
+        let mut transactions = Vec::new();
+        for (j, tx) in children(d, "TxDtls").iter().enumerate() {
+            transactions.push(parse_transaction(tx, &format!("{path}/NtryDtls[{i}]/TxDtls[{j}]"))?);
+        }
+        details.push(EntryDetails {
+            batch_number_of_transactions: text(d, "Btch/NbOfTxs").and_then(|n| n.trim().parse().ok()),
+            transactions,
+        });
+    }
+
+    Ok(ReportEntry {
+        reference: text(node, "NtryRef"),
+        amount: parse_amount(&amount, &format!("{path}/Amt"))?,
+        credit_debit: parse_indicator(&indicator, &format!("{path}/CdtDbtInd"))?,
+        reversal: text(node, "RvslInd").map(|v| v.trim() == "true"),
+        status: text(node, "Sts/Cd").or_else(|| text(node, "Sts")),
+        booking_date: match child(node, "BookgDt") {
+            Some(d) => parse_date(&d, &format!("{path}/BookgDt"))?,
+            None => None,
+        },
+        value_date: match child(node, "ValDt") {
+            Some(d) => parse_date(&d, &format!("{path}/ValDt"))?,
+            None => None,
+        },
+        account_servicer_ref: text(node, "AcctSvcrRef"),
+        bank_transaction_code,
+        details,
+        additional_info: text(node, "AddtlNtryInf"),
+    })
+}
+
+pub(crate) fn parse_group_header(node: &Node) -> GroupHeader {
+    GroupHeader {
+        message_id: text(node, "MsgId").unwrap_or_default(),
+        creation_date_time: text(node, "CreDtTm"),
+        original_business_query: text(node, "OrgnlBizQry/MsgId"),
+        additional_info: text(node, "AddtlInf"),
+    }
+}
+
+pub(crate) fn parse_statement(node: &Node, path: &str) -> Result<AccountStatement, FormatError> {
+    let account = match child(node, "Acct") {
+        Some(a) => CashAccount {
+            id: child(&a, "Id").and_then(|n| parse_account_id(&n)).unwrap_or_default(),
+            currency: text(&a, "Ccy"),
+            name: text(&a, "Nm"),
+            owner_name: text(&a, "Ownr/Nm"),
+            servicer_bic: text(&a, "Svcr/FinInstnId/BIC").or_else(|| text(&a, "Svcr/FinInstnId/BICFI")),
+        },
+        None => CashAccount::default(),
+    };
+
+    let mut balances = Vec::new();
+    for (i, b) in children(node, "Bal").iter().enumerate() {
+        let bal_path = format!("{path}/Bal[{i}]");
+        let Some(amount) = child(b, "Amt") else {
+            return Err(error(format!("в {bal_path} нет суммы Amt")));
+        };
+        balances.push(CashBalance {
+            code: text(b, "Tp/CdOrPrtry/Cd")
+                .or_else(|| text(b, "Tp/CdOrPrtry/Prtry"))
+                .unwrap_or_default(),
+            amount: parse_amount(&amount, &format!("{bal_path}/Amt"))?,
+            credit_debit: parse_indicator(&text(b, "CdtDbtInd").unwrap_or_default(), &format!("{bal_path}/CdtDbtInd"))?,
+            date: match child(b, "Dt") {
+                Some(d) => parse_date(&d, &format!("{bal_path}/Dt"))?,
+                None => None,
+            },
+        });
+    }
+
+    let transactions_summary = match child(node, "TxsSummry") {
+        Some(s) => {
+            let sum_path = format!("{path}/TxsSummry");
+            let part = |name: &str| {
+                child(&s, name)
+                    .map(|n| parse_number_and_sum(&n, &format!("{sum_path}/{name}")))
+                    .transpose()
+            };
+            Some(TransactionsSummary {
+                total_entries: part("TtlNtries")?,
+                total_credit_entries: part("TtlCdtNtries")?,
+                total_debit_entries: part("TtlDbtNtries")?,
+            })
+        }
+        None => None,
+    };
+
+    let mut entries = Vec::new();
+    for (i, e) in children(node, "Ntry").iter().enumerate() {
+        entries.push(parse_entry(e, &format!("{path}/Ntry[{i}]"))?);
+    }
+
+    Ok(AccountStatement {
+        id: text(node, "Id").unwrap_or_default(),
+        electronic_sequence_number: text(node, "ElctrncSeqNb"),
+        legal_sequence_number: text(node, "LglSeqNb"),
+        creation_date_time: text(node, "CreDtTm"),
+        period: child(node, "FrToDt").map(|p| DateTimePeriod {
+            from: text(&p, "FrDtTm").unwrap_or_default(),
+            to: text(&p, "ToDtTm").unwrap_or_default(),
+        }),
+        account,
+        balances,
+        transactions_summary,
+        entries,
+        additional_info: text(node, "AddtlStmtInf"),
+    })
+}
+
+impl TryFrom<&Camt053Format> for Camt053Document {
+    type Error = FormatError;
+
+    /// Построить модель из дерева тегов.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если в документе нет `BkToCstmrStmt`, в проводке или остатке
+    /// нет суммы, либо сумма, дата или признак дебета/кредита не разбираются. Текст ошибки
+    /// содержит путь к элементу, например `Stmt[0]/Ntry[3]/Amt`.
+    fn try_from(value: &Camt053Format) -> Result<Self, Self::Error> {
+        let root = Node {
+            tree: value.tree(),
+            id: value.root(),
+        };
+        let message = if root.tag().local_name() == "BkToCstmrStmt" {
+            Some(root)
+        } else {
+            child(&root, "BkToCstmrStmt")
+        };
+        let Some(message) = message else {
+            return Err(error("в документе нет тега BkToCstmrStmt".to_string()));
+        };
+
+        let group_header = child(&message, "GrpHdr")
+            .map(|h| parse_group_header(&h))
+            .unwrap_or_default();
+
+        let mut statements = Vec::new();
+        for (i, s) in children(&message, "Stmt").iter().enumerate() {
+            statements.push(parse_statement(s, &format!("Stmt[{i}]"))?);
+        }
+
+        Ok(Self {
+            version: value.version(),
+            group_header,
+            statements,
+        })
+    }
+}
+
+impl TryFrom<Camt053Format> for Camt053Document {
+    type Error = FormatError;
+
+    fn try_from(value: Camt053Format) -> Result<Self, Self::Error> {
+        Camt053Document::try_from(&value)
+    }
+}
+
+fn amount_element(name: &str, amount: &Amount) -> Element {
+    Element::text(name, amount.value.to_string()).attr("Ccy", &amount.currency)
+}
+
+fn indicator(value: DebitOrCredit) -> &'static str {
+    match value {
+        DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => "CRDT",
+        DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => "DBIT",
+    }
+}
+
+fn date_element(name: &str, date: &DateAndDateTime) -> Element {
+    let inner = match date {
+        DateAndDateTime::Date(d) => Element::text("Dt", d.format("%Y-%m-%d").to_string()),
+        DateAndDateTime::DateTime(s) => Element::text("DtTm", s.as_str()),
+    };
+    Element::new(name).child(inner)
+}
+
+fn account_id_element(id: &AccountIdentification) -> Element {
+    let inner = match id {
+        AccountIdentification::Iban(v) => Element::text("IBAN", v.as_str()),
+        AccountIdentification::Other(v) => Element::new("Othr").child(Element::text("Id", v.as_str())),
+    };
+    Element::new("Id").child(inner)
+}
+
+fn number_and_sum_element(name: &str, value: &NumberAndSum) -> Element {
+    Element::new(name)
+        .child_opt(value.number_of_entries.map(|n| Element::text("NbOfNtries", n.to_string())))
+        .child_opt(value.sum.map(|s| Element::text("Sum", s.to_string())))
+}
+
+fn party_element(name: &str, party: &Party, version: Camt053Version) -> Element {
+    let id = party.id.as_ref().map(|id| {
+        let othr = Element::new("Othr")
+            .child(Element::text("Id", id.as_str()))
+            .child_opt(
+                party
+                    .id_scheme
+                    .as_ref()
+                    .map(|s| Element::new("SchmeNm").child(Element::text("Cd", s.as_str()))),
+            );
+        Element::new("Id").child(Element::new(if party.private { "PrvtId" } else { "OrgId" }).child(othr))
+    });
+    let content = Element::new("Pty").text_opt("Nm", party.name.as_ref()).child_opt(id);
+    if version >= Camt053Version::V08 {
+        Element::new(name).child(content)
+    } else {
+        let mut element = Element::new(name);
+        element.children = content.children;
+        element
+    }
+}
+
+fn transaction_element(tx: &TransactionDetails, version: Camt053Version) -> Element {
+    let refs = tx.references.as_ref().map(|r| {
+        Element::new("Refs")
+            .text_opt("MsgId", r.message_id.as_ref())
+            .text_opt("AcctSvcrRef", r.account_servicer_ref.as_ref())
+            .text_opt("PmtInfId", r.payment_information_id.as_ref())
+            .text_opt("InstrId", r.instruction_id.as_ref())
+            .text_opt("EndToEndId", r.end_to_end_id.as_ref())
+            .text_opt("TxId", r.transaction_id.as_ref())
+            .text_opt("MndtId", r.mandate_id.as_ref())
+    });
+    let amount = tx.amount.as_ref().map(|a| {
+        if version >= Camt053Version::V03 {
+            amount_element("Amt", a)
+        } else {
+            Element::new("AmtDtls").child(Element::new("TxAmt").child(amount_element("Amt", a)))
+        }
+    });
+    let parties = tx.related_parties.as_ref().map(|p| {
+        Element::new("RltdPties")
+            .child_opt(p.debtor.as_ref().map(|d| party_element("Dbtr", d, version)))
+            .child_opt(p.debtor_account.as_ref().map(|a| Element::new("DbtrAcct").child(account_id_element(a))))
+            .child_opt(p.creditor.as_ref().map(|c| party_element("Cdtr", c, version)))
+            .child_opt(p.creditor_account.as_ref().map(|a| Element::new("CdtrAcct").child(account_id_element(a))))
+    });
+    let remittance = tx.remittance_info.as_ref().map(|r| {
+        Element::new("RmtInf")
+            .children(r.unstructured.iter().map(|u| Element::text("Ustrd", u.as_str())))
+            .children(r.structured.iter().map(|s| {
+                Element::new("Strd")
+                    .child_opt(
+                        s.creditor_reference
+                            .as_ref()
+                            .map(|c| Element::new("CdtrRefInf").child(Element::text("Ref", c.as_str()))),
+                    )
+                    .children(s.additional_info.iter().map(|a| Element::text("AddtlRmtInf", a.as_str())))
+            }))
+    });
+
+    let mut element = Element::new("TxDtls").child_opt(refs);
+    if version >= Camt053Version::V03 {
+        element = element
+            .child_opt(amount)
+            .child_opt(tx.credit_debit.map(|c| Element::text("CdtDbtInd", indicator(c))));
+    } else {
+        element = element.child_opt(amount);
+    }
+    element
+        .child_opt(parties)
+        .child_opt(remittance)
+        .text_opt("AddtlTxInf", tx.additional_info.as_ref())
+}
+
+fn entry_element(entry: &ReportEntry, version: Camt053Version) -> Element {
+    let status = entry.status.as_ref().map(|s| {
+        if version >= Camt053Version::V08 {
+            Element::new("Sts").child(Element::text("Cd", s.as_str()))
+        } else {
+            Element::text("Sts", s.as_str())
+        }
+    });
+    let code = entry.bank_transaction_code.as_ref().map(|c| {
+        let domain = c.domain.as_ref().map(|d| {
+            let family = c.family.as_ref().map(|f| {
+                Element::new("Fmly")
+                    .child(Element::text("Cd", f.as_str()))
+                    .text_opt("SubFmlyCd", c.sub_family.as_ref())
+            });
+            Element::new("Domn").child(Element::text("Cd", d.as_str())).child_opt(family)
+        });
+        let proprietary = c.proprietary_code.as_ref().map(|p| {
+            Element::new("Prtry")
+                .child(Element::text("Cd", p.as_str()))
+                .text_opt("Issr", c.proprietary_issuer.as_ref())
+        });
+        Element::new("BkTxCd").child_opt(domain).child_opt(proprietary)
+    });
+
+    Element::new("Ntry")
+        .text_opt("NtryRef", entry.reference.as_ref())
+        .child(amount_element("Amt", &entry.amount))
+        .child(Element::text("CdtDbtInd", indicator(entry.credit_debit)))
+        .child_opt(entry.reversal.map(|r| Element::text("RvslInd", r.to_string())))
+        .child_opt(status)
+        .child_opt(entry.booking_date.as_ref().map(|d| date_element("BookgDt", d)))
+        .child_opt(entry.value_date.as_ref().map(|d| date_element("ValDt", d)))
+        .text_opt("AcctSvcrRef", entry.account_servicer_ref.as_ref())
+        .child_opt(code)
+        .children(entry.details.iter().map(|d| {
+            Element::new("NtryDtls")
+                .child_opt(
+                    d.batch_number_of_transactions
+                        .map(|n| Element::new("Btch").child(Element::text("NbOfTxs", n.to_string()))),
+                )
+                .children(d.transactions.iter().map(|tx| transaction_element(tx, version)))
+        }))
+        .text_opt("AddtlNtryInf", entry.additional_info.as_ref())
+}
+
+fn statement_element(statement: &AccountStatement, version: Camt053Version) -> Element {
+    let account = &statement.account;
+    let bic_tag = if version >= Camt053Version::V04 { "BICFI" } else { "BIC" };
+    let acct = Element::new("Acct")
+        .child(account_id_element(&account.id))
+        .text_opt("Ccy", account.currency.as_ref())
+        .text_opt("Nm", account.name.as_ref())
+        .child_opt(account.owner_name.as_ref().map(|n| Element::new("Ownr").child(Element::text("Nm", n.as_str()))))
+        .child_opt(account.servicer_bic.as_ref().map(|b| {
+            Element::new("Svcr").child(Element::new("FinInstnId").child(Element::text(bic_tag, b.as_str())))
+        }));
+
+    let balances = statement.balances.iter().map(|b| {
+        Element::new("Bal")
+            .child(Element::new("Tp").child(Element::new("CdOrPrtry").child(Element::text("Cd", b.code.as_str()))))
+            .child(amount_element("Amt", &b.amount))
+            .child(Element::text("CdtDbtInd", indicator(b.credit_debit)))
+            .child_opt(b.date.as_ref().map(|d| date_element("Dt", d)))
+    });
+
+    let summary = statement.transactions_summary.as_ref().map(|s| {
+        Element::new("TxsSummry")
+            .child_opt(s.total_entries.as_ref().map(|n| number_and_sum_element("TtlNtries", n)))
+            .child_opt(s.total_credit_entries.as_ref().map(|n| number_and_sum_element("TtlCdtNtries", n)))
+            .child_opt(s.total_debit_entries.as_ref().map(|n| number_and_sum_element("TtlDbtNtries", n)))
+    });
+
+    Element::new("Stmt")
+        .child(Element::text("Id", statement.id.as_str()))
+        .text_opt("ElctrncSeqNb", statement.electronic_sequence_number.as_ref())
+        .text_opt("LglSeqNb", statement.legal_sequence_number.as_ref())
+        .text_opt("CreDtTm", statement.creation_date_time.as_ref())
+        .child_opt(statement.period.as_ref().map(|p| {
+            Element::new("FrToDt")
+                .child(Element::text("FrDtTm", p.from.as_str()))
+                .child(Element::text("ToDtTm", p.to.as_str()))
+        }))
+        .child(acct)
+        .children(balances)
+        .child_opt(summary)
+        .children(statement.entries.iter().map(|e| entry_element(e, version)))
+        .text_opt("AddtlStmtInf", statement.additional_info.as_ref())
+}
+
+impl From<&Camt053Document> for Camt053Format {
+    /// Записать модель в дерево тегов `<Document xmlns="..."><BkToCstmrStmt>...`
+    /// с порядком элементов по схеме выбранной версии.
+    fn from(value: &Camt053Document) -> Self {
+        let version = value.version.unwrap_or(Camt053Version::V02);
+        let header = &value.group_header;
+        let message = Element::new("BkToCstmrStmt")
+            .child(
+                Element::new("GrpHdr")
+                    .child(Element::text("MsgId", header.message_id.as_str()))
+                    .text_opt("CreDtTm", header.creation_date_time.as_ref())
+                    .child_opt(
+                        header
+                            .original_business_query
+                            .as_ref()
+                            .map(|q| Element::new("OrgnlBizQry").child(Element::text("MsgId", q.as_str()))),
+                    )
+                    .text_opt("AddtlInf", header.additional_info.as_ref()),
+            )
+            .children(value.statements.iter().map(|s| statement_element(s, version)));
+
+        let document = Element::new("Document")
+            .attr("xmlns", &version.namespace())
+            .child(message);
+        let (tree, root) = document.into_tree();
+        let mut camt = Camt053Format::from_tree(tree, root);
+        camt.set_version(version);
+        camt
+    }
+}
+
+impl From<Camt053Document> for Camt053Format {
+    fn from(value: Camt053Document) -> Self {
+        Camt053Format::from(&value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn read(xml: &str) -> Camt053Document {
+        Camt053Document::try_from(Camt053Format::from_read(&mut Cursor::new(xml)).unwrap()).unwrap()
+    }
+
+    const XML: &str = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02"><BkToCstmrStmt>
+        <GrpHdr><MsgId>MSG1</MsgId><CreDtTm>2024-07-04T06:00:00</CreDtTm></GrpHdr>
+        <Stmt>
+            <Id>ST1</Id><ElctrncSeqNb>12</ElctrncSeqNb>
+            <Acct><Id><IBAN>RO49AAAA1B31007593840000</IBAN></Id><Ccy>RON</Ccy></Acct>
+            <Bal><Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp><Amt Ccy="RON">100.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Dt><Dt>2024-07-03</Dt></Dt></Bal>
+            <Bal><Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp><Amt Ccy="RON">50.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Dt><Dt>2024-07-03</Dt></Dt></Bal>
+            <TxsSummry><TtlDbtNtries><NbOfNtries>1</NbOfNtries><Sum>50.00</Sum></TtlDbtNtries></TxsSummry>
+            <Ntry>
+                <Amt Ccy="RON">50.00</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>BOOK</Sts>
+                <BookgDt><Dt>2024-07-03</Dt></BookgDt><ValDt><DtTm>2024-07-03T10:00:00+03:00</DtTm></ValDt>
+                <AcctSvcrRef>BANK1</AcctSvcrRef>
+                <BkTxCd><Domn><Cd>PMNT</Cd><Fmly><Cd>ICDT</Cd><SubFmlyCd>DMCT</SubFmlyCd></Fmly></Domn></BkTxCd>
+                <NtryDtls><TxDtls>
+                    <Refs><InstrId>DOC7</InstrId><EndToEndId>E2E</EndToEndId></Refs>
+                    <AmtDtls><TxAmt><Amt Ccy="RON">50.00</Amt></TxAmt></AmtDtls>
+                    <RltdPties>
+                        <Cdtr><Nm>ООО Ромашка</Nm><Id><OrgId><Othr><Id>7735602068</Id><SchmeNm><Cd>TXID</Cd></SchmeNm></Othr></OrgId></Id></Cdtr>
+                        <CdtrAcct><Id><Othr><Id>40702810440000030888</Id></Othr></Id></CdtrAcct>
+                    </RltdPties>
+                    <RmtInf><Ustrd>Оплата</Ustrd><Strd><CdtrRefInf><Ref>RF18</Ref></CdtrRefInf></Strd></RmtInf>
+                </TxDtls></NtryDtls>
+            </Ntry>
+        </Stmt>
+    </BkToCstmrStmt></Document>"#;
+
+    #[test]
+    fn reads_typed_fields() {
+        let doc = read(XML);
+        assert_eq!(doc.version, Some(Camt053Version::V02));
+        assert_eq!(doc.group_header.message_id, "MSG1");
+
+        let stmt = &doc.statements[0];
+        assert_eq!(stmt.electronic_sequence_number.as_deref(), Some("12"));
+        assert_eq!(stmt.account.id, AccountIdentification::Iban("RO49AAAA1B31007593840000".to_string()));
+        assert_eq!(stmt.balance("CLBD").unwrap().amount.value, dec("50.00"));
+        assert_eq!(
+            stmt.transactions_summary.as_ref().unwrap().total_debit_entries,
+            Some(NumberAndSum { number_of_entries: Some(1), sum: Some(dec("50.00")) })
+        );
+
+        let entry = &stmt.entries[0];
+        assert_eq!(entry.amount.currency, "RON");
+        assert_eq!(entry.credit_debit, DebitOrCredit::Debit);
+        assert_eq!(entry.status.as_deref(), Some("BOOK"));
+        assert_eq!(entry.value_date.as_ref().unwrap().date(), NaiveDate::from_ymd_opt(2024, 7, 3));
+        assert_eq!(entry.bank_transaction_code.as_ref().unwrap().sub_family.as_deref(), Some("DMCT"));
+
+        let tx = &entry.details[0].transactions[0];
+        assert_eq!(tx.references.as_ref().unwrap().instruction_id.as_deref(), Some("DOC7"));
+        assert_eq!(tx.amount.as_ref().unwrap().value, dec("50.00"));
+        let creditor = tx.related_parties.as_ref().unwrap().creditor.as_ref().unwrap();
+        assert_eq!(creditor.name.as_deref(), Some("ООО Ромашка"));
+        assert_eq!(creditor.id_scheme.as_deref(), Some("TXID"));
+        let rmt = tx.remittance_info.as_ref().unwrap();
+        assert_eq!(rmt.unstructured, vec!["Оплата".to_string()]);
+        assert_eq!(rmt.structured[0].creditor_reference.as_deref(), Some("RF18"));
+    }
+
+    #[test]
+    fn model_round_trips_through_xml() {
+        let doc = read(XML);
+        let mut out = Vec::new();
+        Camt053Format::from(&doc).write_to(&mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+        assert!(written.starts_with(r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02"><BkToCstmrStmt><GrpHdr><MsgId>MSG1</MsgId>"#));
+        assert_eq!(read(&written), doc);
+    }
+
+    #[test]
+    fn writer_follows_version_specific_layout() {
+        let mut doc = read(XML);
+        doc.version = Some(Camt053Version::V08);
+        let mut out = Vec::new();
+        Camt053Format::from(&doc).write_to(&mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+
+        assert!(written.contains("camt.053.001.08"));
+        assert!(written.contains("<Sts><Cd>BOOK</Cd></Sts>"));
+        assert!(written.contains("<Cdtr><Pty><Nm>ООО Ромашка</Nm>"));
+        assert!(written.contains("<TxDtls><Refs><InstrId>DOC7</InstrId><EndToEndId>E2E</EndToEndId></Refs><Amt Ccy=\"RON\">50.00</Amt>"));
+        assert_eq!(read(&written), doc);
+    }
+
+    #[test]
+    fn reports_path_of_bad_value() {
+        let xml = XML.replace("<Amt Ccy=\"RON\">50.00</Amt><CdtDbtInd>DBIT</CdtDbtInd>", "<Amt Ccy=\"RON\">5O.00</Amt><CdtDbtInd>DBIT</CdtDbtInd>");
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        assert_eq!(
+            Camt053Document::try_from(&camt).unwrap_err(),
+            FormatError::DataFormatError(
+                "Ошибка разбора формата camt053 : не удалось разобрать сумму 5O.00 в Stmt[0]/Ntry[0]/Amt".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Пути для поиска тегов в дереве CAMT.053.
+//!
+//! Синтаксис — небольшое подмножество XPath:
+//! - `/Document/BkToCstmrStmt/Stmt` — путь от корневого тега документа;
+//! - `Stmt/Ntry` — путь, который может начинаться в любом месте документа
+//!   (обёртка `Document/BkToCstmrStmt` у разных банков отличается);
+//! - `//Refs/EndToEndId` — `//` пропускает любое число уровней (в начале пути — от корня);
+//! - `*` — тег с любым именем;
+//! - `Ntry[2]` — третий `Ntry` у каждого родителя (индексы с 0, как в путях ошибок
+//!   [`crate::camt053_model`]: `Stmt[0]/Ntry[3]/Amt`);
+//! - `Ntry[CdtDbtInd='CRDT']` — у тега есть ребёнок `CdtDbtInd` с текстом `CRDT`;
+//!   слева может стоять относительный путь (`[NtryDtls/TxDtls/Refs/EndToEndId='E2E']`),
+//!   атрибут (`[@Ccy='EUR']`, `[Amt/@Ccy='EUR']`) или `.` — текст самого тега;
+//!   без `='...'` проверяется только наличие.
+//!
+//! Имена сравниваются без префикса пространства имён.
+
+use crate::camt053_format::Camt053Format;
+use crate::camt053_tree::{NodeId, TagTree};
+use crate::error::{FormatError, GeneratorFormatError};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Разобранный путь.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Camt053Path {
+    source: String,
+    absolute: bool,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    /// Шагу предшествует `//`.
+    descendant: bool,
+    /// `None` для `*`.
+    name: Option<String>,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Predicate {
+    Index(usize),
+    Test {
+        /// `None` для `.` и условий на атрибут самого тега.
+        path: Option<Camt053Path>,
+        attr: Option<String>,
+        value: Option<String>,
+    },
+}
+
+impl FromStr for Camt053Path {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            source: s,
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let path = parser.path()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("лишние символы"));
+        }
+        Ok(path)
+    }
+}
+
+impl fmt::Display for Camt053Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, details: &str) -> FormatError {
+        Camt053Format::unknown_value_error(&format!(
+            "неверный путь {}: {} в позиции {}",
+            self.source, details, self.pos
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn text_from(&self, start: usize) -> String {
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Путь до конца строки или до `=`/`]` внутри условия.
+    fn path(&mut self) -> Result<Camt053Path, FormatError> {
+        let start = self.start();
+        let mut absolute = false;
+        let mut steps = Vec::new();
+        loop {
+            let descendant = if self.eat('/') {
+                absolute |= steps.is_empty();
+                self.eat('/')
+            } else if steps.is_empty() {
+                false
+            } else {
+                break;
+            };
+            if self.peek() == Some('@') {
+                if steps.is_empty() {
+                    return Err(self.error("ожидалось имя тега"));
+                }
+                // атрибут в конце пути разбирает условие
+                self.pos -= if descendant { 2 } else { 1 };
+                break;
+            }
+            steps.push(self.step(descendant)?);
+        }
+        Ok(Camt053Path {
+            source: self.text_from(start),
+            absolute,
+            steps,
+        })
+    }
+
+    fn step(&mut self, descendant: bool) -> Result<Step, FormatError> {
+        let name = if self.eat('*') {
+            None
+        } else {
+            Some(self.name()?)
+        };
+        let mut predicates = Vec::new();
+        while self.eat('[') {
+            predicates.push(self.predicate()?);
+            if !self.eat(']') {
+                return Err(self.error("ожидалась ]"));
+            }
+        }
+        Ok(Step {
+            descendant,
+            name,
+            predicates,
+        })
+    }
+
+    fn name(&mut self) -> Result<String, FormatError> {
+        let start = self.start();
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+        {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("ожидалось имя тега"));
+        }
+        let name = self.text_from(start);
+        Ok(name.rsplit(':').next().unwrap_or_default().to_string())
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, FormatError> {
+        if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            let start = self.start();
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+            let index = self.text_from(start).parse().map_err(|_| self.error("неверный индекс"))?;
+            return Ok(Predicate::Index(index));
+        }
+
+        // `.` — сам тег; `@attr` без пути — атрибут самого тега
+        let path = if self.eat('.') || self.peek() == Some('@') {
+            None
+        } else {
+            Some(self.path()?)
+        };
+        if path.as_ref().is_some_and(|p| p.absolute) {
+            return Err(self.error("путь в условии должен быть относительным"));
+        }
+        let attr = if self.eat('/') || path.is_none() {
+            if self.eat('@') {
+                Some(self.name()?)
+            } else if path.is_some() {
+                return Err(self.error("ожидался атрибут"));
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        let value = if self.eat('=') {
+            Some(self.literal()?)
+        } else {
+            None
+        };
+        Ok(Predicate::Test { path, attr, value })
+    }
+
+    fn literal(&mut self) -> Result<String, FormatError> {
+        let quote = match self.peek() {
+            Some(q @ ('\'' | '"')) => q,
+            _ => return Err(self.error("ожидалось значение в кавычках")),
+        };
+        self.pos += 1;
+        let start = self.start();
+        while self.peek().is_some_and(|c| c != quote) {
+            self.pos += 1;
+        }
+        let value = self.text_from(start);
+        if !self.eat(quote) {
+            return Err(self.error("не закрыта кавычка"));
+        }
+        Ok(value)
+    }
+}
+
+/// Место, от которого вычисляется шаг: тег дерева или узел документа над корневым тегом.
+type Context = Option<NodeId>;
+
+impl Camt053Path {
+    /// Путь начинается с `/`.
+    pub fn is_absolute(&self) -> bool {
+        self.absolute
+    }
+
+    /// Теги, подходящие под путь, в порядке документа.
+    ///
+    /// Абсолютный путь отсчитывается от `top` — верхнего тега дерева. Относительный путь
+    /// отсчитывается от детей `context`, а если `context` — `None`, может начинаться
+    /// с любого тега под `top` включительно.
+    pub(crate) fn select(&self, tree: &TagTree, top: NodeId, context: Context) -> Vec<NodeId> {
+        let start = if self.absolute { None } else { context };
+        let mut current = vec![start];
+        for (i, step) in self.steps.iter().enumerate() {
+            let anywhere = i == 0 && !self.absolute && context.is_none();
+            let contexts = if step.descendant || anywhere {
+                Self::descendants(tree, top, &current)
+            } else {
+                current
+            };
+
+            let mut seen = HashSet::new();
+            let mut next = Vec::new();
+            for ctx in contexts {
+                let children: &[NodeId] = match ctx {
+                    Some(id) => tree.children(id),
+                    None => std::slice::from_ref(&top),
+                };
+                let mut matched: Vec<NodeId> = children
+                    .iter()
+                    .copied()
+                    .filter(|&c| step.name.as_ref().is_none_or(|n| tree[c].local_name() == n))
+                    .collect();
+                for predicate in &step.predicates {
+                    matched = predicate.apply(tree, top, matched);
+                }
+                next.extend(matched.into_iter().filter(|&m| seen.insert(m)).map(Some));
+            }
+            current = next;
+        }
+        current.into_iter().flatten().collect()
+    }
+
+    /// Все контексты вместе с их потомками, без повторов.
+    fn descendants(tree: &TagTree, top: NodeId, contexts: &[Context]) -> Vec<Context> {
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+        for &ctx in contexts {
+            if !seen.insert(ctx) {
+                continue;
+            }
+            result.push(ctx);
+            let mut stack: Vec<NodeId> = match ctx {
+                Some(id) => tree.children(id).iter().rev().copied().collect(),
+                None => vec![top],
+            };
+            while let Some(id) = stack.pop() {
+                if seen.insert(Some(id)) {
+                    result.push(Some(id));
+                    stack.extend(tree.children(id).iter().rev());
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Predicate {
+    fn apply(&self, tree: &TagTree, top: NodeId, nodes: Vec<NodeId>) -> Vec<NodeId> {
+        match self {
+            Predicate::Index(index) => nodes.get(*index).copied().into_iter().collect(),
+            Predicate::Test { path, attr, value } => nodes
+                .into_iter()
+                .filter(|&id| {
+                    let targets = match path {
+                        Some(path) => path.select(tree, top, Some(id)),
+                        None => vec![id],
+                    };
+                    targets.into_iter().any(|t| {
+                        let tag = &tree[t];
+                        let actual = match attr {
+                            Some(attr) => tag.attr(attr),
+                            None => Some(tag.text.as_deref().unwrap_or_default()),
+                        };
+                        match (actual, value) {
+                            (Some(actual), Some(value)) => actual == value,
+                            (actual, None) => actual.is_some(),
+                            (None, Some(_)) => false,
+                        }
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const XML: &str = r#"<Document><BkToCstmrStmt><Stmt><Id>S1</Id>
+        <Ntry><Amt Ccy="EUR">1.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+            <NtryDtls><TxDtls><Refs><EndToEndId>E1</EndToEndId></Refs></TxDtls></NtryDtls></Ntry>
+        <Ntry><Amt Ccy="USD">2.00</Amt><CdtDbtInd>DBIT</CdtDbtInd>
+            <NtryDtls><TxDtls><Refs><EndToEndId>E2</EndToEndId></Refs></TxDtls></NtryDtls></Ntry>
+        <Ntry><Amt Ccy="EUR">3.00</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry>
+        </Stmt></BkToCstmrStmt></Document>"#;
+
+    fn texts(camt: &Camt053Format, path: &str) -> Vec<String> {
+        camt.select(path).unwrap().iter().map(|v| v.text()).collect()
+    }
+
+    #[test]
+    fn select_supports_wildcards_indices_and_predicates() {
+        let camt = Camt053Format::from_read(&mut Cursor::new(XML)).unwrap();
+
+        assert_eq!(texts(&camt, "/Document/BkToCstmrStmt/Stmt/Id"), vec!["S1"]);
+        assert_eq!(texts(&camt, "/Stmt/Id"), Vec::<String>::new());
+        assert_eq!(texts(&camt, "Stmt/Ntry[CdtDbtInd='CRDT']/Amt"), vec!["1.00", "3.00"]);
+        assert_eq!(texts(&camt, "//Refs/EndToEndId"), vec!["E1", "E2"]);
+        assert_eq!(texts(&camt, "Stmt[0]/Ntry[1]/Amt"), vec!["2.00"]);
+        assert_eq!(texts(&camt, "Ntry/*[@Ccy='USD']"), vec!["2.00"]);
+        assert_eq!(texts(&camt, "Ntry[Amt/@Ccy='EUR'][NtryDtls]/Amt"), vec!["1.00"]);
+        assert_eq!(texts(&camt, "Ntry/Amt[.='3.00']"), vec!["3.00"]);
+        assert_eq!(
+            texts(&camt, r#"Ntry[NtryDtls/TxDtls/Refs/EndToEndId="E2"]/CdtDbtInd"#),
+            vec!["DBIT"]
+        );
+
+        let amt = camt.select_first("Ntry[2]/Amt").unwrap().unwrap();
+        assert_eq!(amt.path(), "/Document/BkToCstmrStmt/Stmt/Ntry/Amt");
+    }
+
+    #[test]
+    fn invalid_path_is_reported() {
+        for path in ["", "@x", "/@x", "Stmt/", "Ntry[CdtDbtInd='CRDT'", "Ntry[Amt=CRDT]", "Ntry[/Amt]"] {
+            let error = path.parse::<Camt053Path>().unwrap_err();
+            assert!(matches!(error, FormatError::UnknownValueFormat(_)), "{path}");
+        }
+    }
+}
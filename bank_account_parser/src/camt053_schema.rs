@@ -0,0 +1,482 @@
+//! Структурная проверка CAMT.053 (и camt.052, camt.054) по схеме ISO 20022.
+//!
+//! Для основных элементов сообщения (`GrpHdr`, `Stmt`, `Acct`, `Bal`, `TxsSummry`, `Ntry`,
+//! `TxDtls`, `BkTxCd`, ...) проверяется, что обязательные дети есть, идут в порядке схемы
+//! и неповторяющиеся дети встречаются один раз. Элементы, которых нет в описании ниже,
+//! не проверяются и не считаются ошибкой: у версий схемы они различаются.
+//!
+//! ```no_run
+//! use bank_account_parser::camt053_format::Camt053Format;
+//! use bank_account_parser::camt053_schema;
+//! # let camt = Camt053Format::default();
+//! for issue in camt053_schema::validate(&camt) {
+//!     eprintln!("{}", issue);
+//! }
+//! ```
+
+use crate::camt053_format::{Camt053Format, Camt053Version, CamtMessage};
+use crate::camt053_tree::{NodeId, TagTree};
+use std::fmt;
+
+/// Вид нарушения структуры.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructureIssueKind {
+    /// Нет обязательного элемента.
+    Missing(String),
+    /// Элемент стоит после `after`, хотя по схеме должен идти раньше.
+    OutOfOrder { element: String, after: String },
+    /// Элемент, допустимый один раз, повторяется.
+    Repeated(String),
+    /// Пустой код валюты: элемент `Ccy` или атрибут `Ccy` (записывается как `@Ccy`).
+    EmptyCurrency(String),
+}
+
+/// Нарушение структуры документа.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructureIssue {
+    /// Путь к элементу, в котором найдено нарушение, например `/Document/BkToCstmrStmt/Stmt[0]/Bal[1]`
+    /// (индексы с 0 среди одноимённых соседей, путь подходит для [`Camt053Format::select`]).
+    pub path: String,
+    pub kind: StructureIssueKind,
+}
+
+impl fmt::Display for StructureIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            StructureIssueKind::Missing(element) => {
+                write!(f, "{}: нет обязательного элемента {}", self.path, element)
+            }
+            StructureIssueKind::OutOfOrder { element, after } => {
+                write!(f, "{}: элемент {} должен стоять перед {}", self.path, element, after)
+            }
+            StructureIssueKind::Repeated(element) => {
+                write!(f, "{}: элемент {} встречается больше одного раза", self.path, element)
+            }
+            StructureIssueKind::EmptyCurrency(element) => {
+                write!(f, "{}: пустой код валюты {}", self.path, element)
+            }
+        }
+    }
+}
+
+/// Обязательность элемента.
+#[derive(Clone, Copy)]
+enum Required {
+    No,
+    Yes,
+    /// Обязателен в версиях до указанной.
+    Before(Camt053Version),
+}
+
+/// Ребёнок в последовательности схемы.
+struct Child {
+    name: &'static str,
+    required: Required,
+    repeated: bool,
+}
+
+const fn one(name: &'static str) -> Child {
+    Child { name, required: Required::Yes, repeated: false }
+}
+
+const fn opt(name: &'static str) -> Child {
+    Child { name, required: Required::No, repeated: false }
+}
+
+const fn many(name: &'static str) -> Child {
+    Child { name, required: Required::No, repeated: true }
+}
+
+const fn some(name: &'static str) -> Child {
+    Child { name, required: Required::Yes, repeated: true }
+}
+
+const ACCOUNT: &[Child] = &[one("Id"), opt("Tp"), opt("Ccy"), opt("Nm"), opt("Prxy"), opt("Ownr"), opt("Svcr")];
+
+/// Последовательности детей. Ключ — имя элемента или `родитель/элемент` для имён,
+/// которые в разных местах схемы означают разное (`Tp`, `Prtry`).
+const SEQUENCES: &[(&str, &[Child])] = &[
+    // какое из сообщений должно быть в Document, проверяет validate
+    ("Document", &[opt("BkToCstmrStmt"), opt("BkToCstmrAcctRpt"), opt("BkToCstmrDbtCdtNtfctn")]),
+    ("BkToCstmrStmt", &[one("GrpHdr"), some("Stmt"), many("SplmtryData")]),
+    ("BkToCstmrAcctRpt", &[one("GrpHdr"), some("Rpt"), many("SplmtryData")]),
+    ("BkToCstmrDbtCdtNtfctn", &[one("GrpHdr"), some("Ntfctn"), many("SplmtryData")]),
+    (
+        "GrpHdr",
+        &[one("MsgId"), one("CreDtTm"), opt("MsgRcpt"), opt("MsgPgntn"), opt("OrgnlBizQry"), opt("AddtlInf")],
+    ),
+    (
+        "Stmt",
+        &[
+            one("Id"),
+            opt("StmtPgntn"),
+            opt("ElctrncSeqNb"),
+            opt("RptgSeq"),
+            opt("LglSeqNb"),
+            Child { name: "CreDtTm", required: Required::Before(Camt053Version::V08), repeated: false },
+            opt("FrToDt"),
+            opt("CpyDplctInd"),
+            opt("RptgSrc"),
+            one("Acct"),
+            opt("RltdAcct"),
+            many("Intrst"),
+            some("Bal"),
+            opt("TxsSummry"),
+            many("Ntry"),
+            opt("AddtlStmtInf"),
+        ],
+    ),
+    // отчёт camt.052 отличается от выписки необязательными остатками
+    (
+        "Rpt",
+        &[
+            one("Id"),
+            opt("RptPgntn"),
+            opt("ElctrncSeqNb"),
+            opt("RptgSeq"),
+            opt("LglSeqNb"),
+            Child { name: "CreDtTm", required: Required::Before(Camt053Version::V08), repeated: false },
+            opt("FrToDt"),
+            opt("CpyDplctInd"),
+            opt("RptgSrc"),
+            one("Acct"),
+            opt("RltdAcct"),
+            many("Intrst"),
+            many("Bal"),
+            opt("TxsSummry"),
+            many("Ntry"),
+            opt("AddtlRptInf"),
+        ],
+    ),
+    // в уведомлении camt.054 нет остатков
+    (
+        "Ntfctn",
+        &[
+            one("Id"),
+            opt("NtfctnPgntn"),
+            opt("ElctrncSeqNb"),
+            opt("RptgSeq"),
+            opt("LglSeqNb"),
+            Child { name: "CreDtTm", required: Required::Before(Camt053Version::V08), repeated: false },
+            opt("FrToDt"),
+            opt("CpyDplctInd"),
+            opt("RptgSrc"),
+            one("Acct"),
+            opt("RltdAcct"),
+            many("Intrst"),
+            opt("TxsSummry"),
+            many("Ntry"),
+            opt("AddtlNtfctnInf"),
+        ],
+    ),
+    ("FrToDt", &[one("FrDtTm"), one("ToDtTm")]),
+    // Ccy по схеме необязателен; выписки с известной валютой пишут его всегда
+    ("Stmt/Acct", ACCOUNT),
+    ("Rpt/Acct", ACCOUNT),
+    ("Ntfctn/Acct", ACCOUNT),
+    ("Bal", &[one("Tp"), many("CdtLine"), one("Amt"), one("CdtDbtInd"), one("Dt"), many("Avlbty")]),
+    ("Bal/Tp", &[one("CdOrPrtry"), opt("SubTp")]),
+    ("TxsSummry", &[opt("TtlNtries"), opt("TtlCdtNtries"), opt("TtlDbtNtries"), many("TtlNtriesPerBkTxCd")]),
+    ("TtlNtries", &[opt("NbOfNtries"), opt("Sum"), opt("TtlNetNtryAmt"), opt("CdtDbtInd")]),
+    ("TtlCdtNtries", &[opt("NbOfNtries"), opt("Sum")]),
+    ("TtlDbtNtries", &[opt("NbOfNtries"), opt("Sum")]),
+    (
+        "Ntry",
+        &[
+            opt("NtryRef"),
+            one("Amt"),
+            one("CdtDbtInd"),
+            opt("RvslInd"),
+            one("Sts"),
+            opt("BookgDt"),
+            opt("ValDt"),
+            opt("AcctSvcrRef"),
+            many("Avlbty"),
+            one("BkTxCd"),
+            opt("ComssnWvrInd"),
+            opt("AddtlInfInd"),
+            opt("AmtDtls"),
+            opt("Chrgs"),
+            opt("TechInptChanl"),
+            opt("Intrst"),
+            opt("CardTx"),
+            many("NtryDtls"),
+            opt("AddtlNtryInf"),
+        ],
+    ),
+    ("BkTxCd", &[opt("Domn"), opt("Prtry")]),
+    ("BkTxCd/Domn", &[one("Cd"), one("Fmly")]),
+    ("Domn/Fmly", &[one("Cd"), one("SubFmlyCd")]),
+    ("BkTxCd/Prtry", &[one("Cd"), opt("Issr")]),
+    ("NtryDtls", &[opt("Btch"), many("TxDtls")]),
+    ("Btch", &[opt("MsgId"), opt("PmtInfId"), opt("NbOfTxs"), opt("TtlAmt"), opt("CdtDbtInd")]),
+    (
+        "TxDtls",
+        &[
+            opt("Refs"),
+            opt("Amt"),
+            opt("CdtDbtInd"),
+            opt("AmtDtls"),
+            many("Avlbty"),
+            opt("BkTxCd"),
+            opt("Chrgs"),
+            opt("Intrst"),
+            opt("RltdPties"),
+            opt("RltdAgts"),
+            opt("LclInstrm"),
+            opt("Purp"),
+            many("RltdRmtInf"),
+            opt("RmtInf"),
+            opt("RltdDts"),
+            opt("RltdPric"),
+            many("RltdQties"),
+            opt("FinInstrmId"),
+            opt("Tax"),
+            opt("RtrInf"),
+            opt("CorpActn"),
+            opt("SfkpgAcct"),
+            opt("CshDpst"),
+            opt("CardTx"),
+            opt("AddtlTxInf"),
+            many("SplmtryData"),
+        ],
+    ),
+    (
+        "Refs",
+        &[
+            opt("MsgId"),
+            opt("AcctSvcrRef"),
+            opt("PmtInfId"),
+            opt("InstrId"),
+            opt("EndToEndId"),
+            opt("UETR"),
+            opt("TxId"),
+            opt("MndtId"),
+            opt("ChqNb"),
+            opt("ClrSysRef"),
+            opt("AcctOwnrTxId"),
+            opt("AcctSvcrTxId"),
+            opt("MktInfrstrctrTxId"),
+            opt("PrcgId"),
+            many("Prtry"),
+        ],
+    ),
+];
+
+fn sequence(parent: &str, name: &str) -> Option<&'static [Child]> {
+    let qualified = format!("{parent}/{name}");
+    SEQUENCES
+        .iter()
+        .find(|(key, _)| *key == qualified)
+        .or_else(|| SEQUENCES.iter().find(|(key, _)| *key == name))
+        .map(|(_, children)| *children)
+}
+
+/// Проверить структуру документа. Сообщение определяется по корневому тегу, версия схемы —
+/// по пространству имён документа, по умолчанию — `camt.053.001.02`.
+pub fn validate(camt: &Camt053Format) -> Vec<StructureIssue> {
+    let message = camt.message().unwrap_or(CamtMessage::Statement);
+    let version = camt.message_version(message).unwrap_or(Camt053Version::V02);
+    let tree = camt.tree();
+    let root = camt.root();
+    let mut issues = Vec::new();
+    if tree[root].local_name() != "Document" {
+        issues.push(StructureIssue {
+            path: "/".to_string(),
+            kind: StructureIssueKind::Missing("Document".to_string()),
+        });
+    } else if tree.child(root, message.root_tag()).is_none() {
+        issues.push(StructureIssue {
+            path: "/Document".to_string(),
+            kind: StructureIssueKind::Missing(message.root_tag().to_string()),
+        });
+    }
+    let path = format!("/{}", tree[root].local_name());
+    validate_node(tree, root, "", &path, version, &mut issues);
+    issues
+}
+
+fn validate_node(
+    tree: &TagTree,
+    id: NodeId,
+    parent: &str,
+    path: &str,
+    version: Camt053Version,
+    issues: &mut Vec<StructureIssue>,
+) {
+    let name = tree[id].local_name();
+    let spec = sequence(parent, name);
+    // валюта по схеме — трёхбуквенный код ISO 4217, пустая недопустима
+    let text = tree[id].text.as_deref().unwrap_or_default();
+    if name == "Ccy" && tree.children(id).is_empty() && text.trim().is_empty() {
+        issues.push(StructureIssue {
+            path: path.to_string(),
+            kind: StructureIssueKind::EmptyCurrency("Ccy".to_string()),
+        });
+    }
+    if tree[id].attr("Ccy").is_some_and(|c| c.trim().is_empty()) {
+        issues.push(StructureIssue {
+            path: path.to_string(),
+            kind: StructureIssueKind::EmptyCurrency("@Ccy".to_string()),
+        });
+    }
+    if let Some(spec) = spec {
+        check_sequence(tree, id, spec, path, version, issues);
+    }
+
+    let mut seen: Vec<(&str, usize)> = Vec::new();
+    for &child in tree.children(id) {
+        let child_name = tree[child].local_name();
+        let index = match seen.iter_mut().find(|(n, _)| *n == child_name) {
+            Some((_, count)) => {
+                *count += 1;
+                *count - 1
+            }
+            None => {
+                seen.push((child_name, 1));
+                0
+            }
+        };
+        let repeated = spec
+            .and_then(|s| s.iter().find(|c| c.name == child_name))
+            .is_none_or(|c| c.repeated);
+        let child_path = if repeated {
+            format!("{path}/{child_name}[{index}]")
+        } else {
+            format!("{path}/{child_name}")
+        };
+        validate_node(tree, child, name, &child_path, version, issues);
+    }
+}
+
+fn check_sequence(
+    tree: &TagTree,
+    id: NodeId,
+    spec: &[Child],
+    path: &str,
+    version: Camt053Version,
+    issues: &mut Vec<StructureIssue>,
+) {
+    let issue = |kind| StructureIssue {
+        path: path.to_string(),
+        kind,
+    };
+    let mut counts = vec![0usize; spec.len()];
+    // позиция в схеме последнего известного ребёнка
+    let mut last: Option<usize> = None;
+    for &child in tree.children(id) {
+        let name = tree[child].local_name();
+        let Some(position) = spec.iter().position(|c| c.name == name) else {
+            continue;
+        };
+        counts[position] += 1;
+        if counts[position] == 2 && !spec[position].repeated {
+            issues.push(issue(StructureIssueKind::Repeated(name.to_string())));
+        }
+        match last {
+            Some(last) if position < last => issues.push(issue(StructureIssueKind::OutOfOrder {
+                element: name.to_string(),
+                after: spec[last].name.to_string(),
+            })),
+            _ => last = Some(position),
+        }
+    }
+
+    for (child, count) in spec.iter().zip(counts) {
+        let required = match child.required {
+            Required::No => false,
+            Required::Yes => true,
+            Required::Before(until) => version < until,
+        };
+        if required && count == 0 {
+            issues.push(issue(StructureIssueKind::Missing(child.name.to_string())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mt940_format::MT940Format;
+    use crate::statement::{Account, Statement, Statements};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn converted_mt940_conforms_to_schema() {
+        let mt940 = MT940Format::from_read(&mut File::open("./examples/data/mt940.exmpl").unwrap()).unwrap();
+        let camt = Camt053Format::from(mt940);
+        assert_eq!(validate(&camt), Vec::new());
+
+        let mut out = Vec::new();
+        camt.clone().write_to(&mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.starts_with(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:camt.053.001.02\"><BkToCstmrStmt><GrpHdr><MsgId>"
+        ));
+    }
+
+    #[test]
+    fn reports_missing_repeated_and_misplaced_elements() {
+        let xml = r#"<Document><BkToCstmrStmt><GrpHdr><MsgId>1</MsgId></GrpHdr><Stmt>
+            <Id>S1</Id><CreDtTm>2024-01-01T00:00:00</CreDtTm>
+            <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id></Acct><Acct/>
+            <Bal><Amt Ccy="EUR">1.00</Amt><Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp><CdtDbtInd>CRDT</CdtDbtInd><Dt><Dt>2024-01-01</Dt></Dt></Bal>
+            <Ntry><Amt Ccy="EUR">1.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts></Ntry>
+        </Stmt></BkToCstmrStmt></Document>"#;
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+
+        let issues: Vec<String> = validate(&camt).iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "/Document/BkToCstmrStmt/GrpHdr: нет обязательного элемента CreDtTm",
+                "/Document/BkToCstmrStmt/Stmt[0]: элемент Acct встречается больше одного раза",
+                "/Document/BkToCstmrStmt/Stmt[0]/Acct: нет обязательного элемента Id",
+                "/Document/BkToCstmrStmt/Stmt[0]/Bal[0]: элемент Tp должен стоять перед Amt",
+                "/Document/BkToCstmrStmt/Stmt[0]/Ntry[0]: нет обязательного элемента BkTxCd",
+            ]
+        );
+        assert!(camt.select_first("/Document/BkToCstmrStmt/Stmt[0]/Bal[0]").unwrap().is_some());
+
+        let bare = Camt053Format::from_read(&mut Cursor::new("<BkToCstmrStmt/>")).unwrap();
+        let issues = validate(&bare);
+        assert_eq!(issues[0].kind, StructureIssueKind::Missing("Document".to_string()));
+        assert_eq!(issues[1].kind, StructureIssueKind::Missing("GrpHdr".to_string()));
+    }
+
+    #[test]
+    fn reports_empty_currency_and_unknown_creation_time() {
+        let xml = r#"<Document><BkToCstmrStmt><GrpHdr><MsgId>1</MsgId><CreDtTm>2024-01-01T00:00:00</CreDtTm></GrpHdr><Stmt>
+            <Id>S1</Id><CreDtTm>2024-01-01T00:00:00</CreDtTm>
+            <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy> </Ccy></Acct>
+            <Bal><Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp><Amt Ccy="">1.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Dt><Dt>2024-01-01</Dt></Dt></Bal>
+        </Stmt></BkToCstmrStmt></Document>"#;
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+        let issues: Vec<String> = validate(&camt).iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "/Document/BkToCstmrStmt/Stmt[0]/Acct/Ccy: пустой код валюты Ccy",
+                "/Document/BkToCstmrStmt/Stmt[0]/Bal[0]/Amt: пустой код валюты @Ccy",
+            ]
+        );
+
+        // выписка без валюты, остатков и проводок: ни пустой Ccy, ни даты 1970 года
+        let statements = Statements {
+            statements: vec![Statement {
+                id: "S1".to_string(),
+                account: Account {
+                    id: "DE89370400440532013000".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+        };
+        let camt = Camt053Format::from(statements);
+        assert!(camt.select("//Acct/Ccy").unwrap().is_empty());
+        assert!(camt.select("//CreDtTm").unwrap().is_empty());
+        let kinds: Vec<StructureIssueKind> = validate(&camt).into_iter().map(|i| i.kind).collect();
+        assert!(kinds.contains(&StructureIssueKind::Missing("CreDtTm".to_string())));
+    }
+}
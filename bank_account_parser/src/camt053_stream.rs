@@ -0,0 +1,662 @@
+//! Потоковое чтение CAMT.053 (и camt.052 — отчёты `Rpt` читаются так же, как выписки `Stmt`).
+//!
+//! [`Camt053Format::from_read`] строит дерево всего документа, поэтому выписка в сотни мегабайт
+//! с миллионами проводок целиком оказывается в памяти. [`Camt053Reader`] читает XML по событиям
+//! и отдаёт заголовок группы, реквизиты выписки, а затем её проводки по одной: в памяти
+//! держатся только реквизиты текущей выписки и текущая проводка.
+//!
+//! ```no_run
+//! use bank_account_parser::camt053_stream::{Camt053Event, Camt053Reader};
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! let file = File::open("statement.xml").unwrap();
+//! for event in Camt053Reader::new(BufReader::new(file)) {
+//!     if let Camt053Event::Entry(entry) = event.unwrap() {
+//!         println!("{}", entry.to_model().unwrap().amount.value);
+//!     }
+//! }
+//! ```
+
+use crate::bank_tx_code::BankTxCodeMap;
+use crate::camt053_format::{Camt053Format, Camt053Version, CamtMessage};
+use crate::camt053_iterator::Camt053Iter;
+use crate::camt053_model::{self, AccountStatement, GroupHeader, Node, ReportEntry};
+use crate::camt053_tree::{NodeId, TagTree};
+use crate::error::{FormatError, GeneratorFormatError};
+use crate::statement::{Entry, Statement};
+use crate::transactions_holder::{
+    CollectedTransactions, ReadMode, RowDiagnostic, Transaction,
+};
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::NsReader;
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+/// Очередная часть документа.
+pub enum Camt053Event {
+    /// Заголовок группы `GrpHdr`.
+    GroupHeader(GroupHeader),
+    /// Реквизиты выписки `Stmt` без проводок. Отдаются перед первой проводкой выписки,
+    /// поэтому элементы, стоящие в `Stmt` после проводок (`AddtlStmtInf`), в них не попадают.
+    Statement(StatementHeader),
+    /// Проводка `Ntry`.
+    Entry(StatementEntry),
+}
+
+/// Реквизиты выписки: счёт, номера, остатки и сводка оборотов.
+pub struct StatementHeader {
+    tree: TagTree,
+    index: usize,
+}
+
+impl StatementHeader {
+    /// Порядковый номер выписки в документе (с 0).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Обход тегов `Stmt` с путями вида `/Stmt/Bal/Amt`.
+    pub fn get_iter(&self) -> Camt053Iter<'_> {
+        Camt053Iter::new(&self.tree, FRAGMENT_ROOT)
+    }
+
+    /// Типизированная модель выписки (без проводок).
+    pub fn to_model(&self) -> Result<AccountStatement, FormatError> {
+        camt053_model::parse_statement(&fragment_root(&self.tree), &format!("Stmt[{}]", self.index))
+    }
+
+    /// Формато-независимая выписка (без проводок).
+    pub fn to_statement(&self) -> Statement {
+        Camt053Format::statement_header(&self.tree, FRAGMENT_ROOT)
+    }
+}
+
+/// Проводка выписки.
+pub struct StatementEntry {
+    tree: TagTree,
+    statement: usize,
+    index: usize,
+    row: usize,
+    /// Валюта выписки для проводки без своей валюты.
+    currency: String,
+}
+
+impl StatementEntry {
+    /// Порядковый номер выписки, к которой относится проводка (с 0).
+    pub fn statement(&self) -> usize {
+        self.statement
+    }
+
+    /// Порядковый номер проводки в документе (с 1), как в [`RowDiagnostic::row`].
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Обход тегов проводки с путями вида `/Ntry/Amt`.
+    pub fn get_iter(&self) -> Camt053Iter<'_> {
+        Camt053Iter::new(&self.tree, FRAGMENT_ROOT)
+    }
+
+    /// Типизированная модель проводки.
+    pub fn to_model(&self) -> Result<ReportEntry, FormatError> {
+        camt053_model::parse_entry(
+            &fragment_root(&self.tree),
+            &format!("Stmt[{}]/Ntry[{}]", self.statement, self.index),
+        )
+    }
+
+    /// Формато-независимая проводка; код операции распознаётся по встроенной таблице.
+    /// Проводка, которую не удалось разобрать, заполнена, насколько удалось, см. [`Self::to_transaction`].
+    pub fn to_entry(&self) -> Entry {
+        self.to_entry_with(&BankTxCodeMap::default())
+    }
+
+    /// Формато-независимая проводка с таблицей кодов операций `codes`,
+    /// см. [`Camt053Format::set_bank_tx_codes`].
+    pub fn to_entry_with(&self, codes: &BankTxCodeMap) -> Entry {
+        Camt053Format::statement_entry(&self.tree, FRAGMENT_ROOT, self.row, codes).0
+    }
+
+    /// Транзакция для сверки, как у [`Entry::to_transaction`].
+    pub fn to_transaction(&self) -> Result<Transaction, RowDiagnostic> {
+        self.transactions(false, &BankTxCodeMap::default()).remove(0)
+    }
+
+    /// Формато-независимые проводки, пакетная проводка развёрнута по `TxDtls`,
+    /// см. [`Camt053Format::set_expand_batches`].
+    pub fn to_entries(&self) -> Vec<Entry> {
+        self.to_entries_with(&BankTxCodeMap::default())
+    }
+
+    /// Как [`Self::to_entries`], но с таблицей кодов операций `codes`.
+    pub fn to_entries_with(&self, codes: &BankTxCodeMap) -> Vec<Entry> {
+        Camt053Format::statement_entries(&self.tree, FRAGMENT_ROOT, self.index + 1, self.row, true, codes)
+            .into_iter()
+            .map(|(entry, _)| entry)
+            .collect()
+    }
+
+    /// Транзакции для сверки, пакетная проводка развёрнута по `TxDtls`.
+    pub fn to_transactions(&self) -> Vec<Result<Transaction, RowDiagnostic>> {
+        self.transactions(true, &BankTxCodeMap::default())
+    }
+
+    fn transactions(&self, expand: bool, codes: &BankTxCodeMap) -> Vec<Result<Transaction, RowDiagnostic>> {
+        Camt053Format::statement_entries(&self.tree, FRAGMENT_ROOT, self.index + 1, self.row, expand, codes)
+            .into_iter()
+            .map(|(entry, error)| match error {
+                None => Ok(entry.to_transaction(&self.currency)),
+                Some(d) => Err(d),
+            })
+            .collect()
+    }
+}
+
+/// Корень фрагмента: первый тег, добавленный в его дерево.
+const FRAGMENT_ROOT: NodeId = NodeId::FIRST;
+
+fn fragment_root(tree: &TagTree) -> Node<'_> {
+    Node {
+        tree,
+        id: FRAGMENT_ROOT,
+    }
+}
+
+enum CaptureKind {
+    GroupHeader,
+    /// Дочерний тег `Stmt`, кроме `Ntry`; добавляется к реквизитам выписки.
+    StatementPart,
+    Entry,
+}
+
+/// Поддерево, которое собирается целиком в отдельное дерево.
+struct Capture {
+    kind: CaptureKind,
+    tree: TagTree,
+    current: NodeId,
+}
+
+struct ReaderState<R> {
+    reader: NsReader<R>,
+    buf: Vec<u8>,
+    /// Локальные имена открытых тегов вне собираемых поддеревьев.
+    path: Vec<String>,
+    capture: Option<Capture>,
+    pending: VecDeque<Camt053Event>,
+    version: Option<Camt053Version>,
+    /// Реквизиты текущей выписки (корень — `Stmt`); `None`, если они уже отданы.
+    statement: Option<TagTree>,
+    /// Валюта текущей выписки, см. [`Statement::currency`].
+    currency: String,
+    /// Сообщение, выписка (`Stmt`, `Rpt`) которого сейчас читается.
+    in_statement: Option<CamtMessage>,
+    statements: usize,
+    entries_in_statement: usize,
+    rows: usize,
+    related_ref: Option<String>,
+    finished: bool,
+    expand_batches: bool,
+    bank_tx_codes: BankTxCodeMap,
+    mode: ReadMode,
+    /// Проводки, пропущенные [`Camt053Reader::next_statement`] в режиме [`ReadMode::Lenient`].
+    diagnostics: Vec<RowDiagnostic>,
+}
+
+impl<R: BufRead> ReaderState<R> {
+    /// Начать отдельное дерево с тегом `e` в корне.
+    fn fragment(&self, e: &BytesStart) -> Result<TagTree, FormatError> {
+        let mut tree = TagTree::new();
+        Camt053Format::read_tag(&self.reader, e, &mut tree, None)?;
+        Ok(tree)
+    }
+
+    /// Обработать открывающий тег; возвращает тег, добавленный в собираемое поддерево.
+    fn start(&mut self, e: &BytesStart) -> Result<Option<NodeId>, FormatError> {
+        if let Some(capture) = &mut self.capture {
+            let id = Camt053Format::read_tag(&self.reader, e, &mut capture.tree, Some(capture.current))?;
+            capture.current = id;
+            return Ok(Some(id));
+        }
+
+        let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+        if self.version.is_none()
+            && let ResolveResult::Bound(ns) = self.reader.resolver().resolve_element(e.name()).0
+        {
+            self.version = CamtMessage::from_namespace(&String::from_utf8_lossy(ns.as_ref())).map(|(_, v)| v);
+        }
+
+        let parent = self.path.last().map(String::as_str);
+        let statement_tag = self.in_statement.map(CamtMessage::statement_tag);
+        let kind = match (parent, name.as_str()) {
+            (Some(parent), "GrpHdr") if CamtMessage::ALL.iter().any(|m| m.root_tag() == parent) => {
+                CaptureKind::GroupHeader
+            }
+            (_, tag) if self.in_statement.is_none() && CamtMessage::ALL.iter().any(|m| m.statement_tag() == tag) => {
+                self.statement = Some(self.fragment(e)?);
+                self.in_statement = CamtMessage::ALL.into_iter().find(|m| m.statement_tag() == tag);
+                self.statements += 1;
+                self.entries_in_statement = 0;
+                self.path.push(name);
+                return Ok(None);
+            }
+            (parent, "Ntry") if parent.is_some() && parent == statement_tag => {
+                self.send_statement();
+                CaptureKind::Entry
+            }
+            (parent, _) if parent.is_some() && parent == statement_tag => CaptureKind::StatementPart,
+            _ => {
+                self.path.push(name);
+                return Ok(None);
+            }
+        };
+
+        self.capture = Some(Capture {
+            kind,
+            tree: self.fragment(e)?,
+            current: FRAGMENT_ROOT,
+        });
+        Ok(Some(FRAGMENT_ROOT))
+    }
+
+    fn send_statement(&mut self) {
+        if let Some(tree) = self.statement.take() {
+            let header = Camt053Format::statement_header(&tree, FRAGMENT_ROOT);
+            self.currency = header.currency().unwrap_or_default().to_string();
+            self.pending.push_back(Camt053Event::Statement(StatementHeader {
+                tree,
+                index: self.statements - 1,
+            }));
+        }
+    }
+
+    fn end(&mut self) -> Result<(), FormatError> {
+        let Some(capture) = &mut self.capture else {
+            let closed = self.path.pop();
+            if let Some(message) = self.in_statement
+                && closed.as_deref() == Some(message.statement_tag())
+            {
+                self.send_statement();
+                self.in_statement = None;
+            }
+            return Ok(());
+        };
+
+        capture.tree[capture.current].trim_text();
+        if let Some(parent) = capture.tree.parent(capture.current) {
+            capture.current = parent;
+            return Ok(());
+        }
+
+        let Some(Capture { kind, tree, .. }) = self.capture.take() else {
+            return Ok(());
+        };
+        match kind {
+            CaptureKind::GroupHeader => {
+                let header = camt053_model::parse_group_header(&fragment_root(&tree));
+                self.related_ref = header.original_business_query.clone();
+                self.pending.push_back(Camt053Event::GroupHeader(header));
+            }
+            CaptureKind::StatementPart => {
+                // Части после проводок приходят, когда реквизиты уже отданы, и отбрасываются.
+                if let Some(statement) = &mut self.statement {
+                    statement.copy_from(Some(FRAGMENT_ROOT), &tree, FRAGMENT_ROOT);
+                }
+            }
+            CaptureKind::Entry => {
+                self.rows += 1;
+                self.pending.push_back(Camt053Event::Entry(StatementEntry {
+                    tree,
+                    statement: self.statements - 1,
+                    index: self.entries_in_statement,
+                    row: self.rows,
+                    currency: self.currency.clone(),
+                }));
+                self.entries_in_statement += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), FormatError> {
+        match &mut self.capture {
+            Some(capture) => Camt053Format::append_text(&mut capture.tree, Some(capture.current), text),
+            None => Ok(()),
+        }
+    }
+
+    fn read_event(&mut self, buf: &mut Vec<u8>) -> Result<bool, FormatError> {
+        match self.reader.read_event_into(buf) {
+            Ok(Event::Start(e)) => {
+                self.start(&e)?;
+            }
+            Ok(Event::Empty(e)) => {
+                if let Some(id) = self.start(&e)?
+                    && let Some(capture) = &mut self.capture
+                {
+                    capture.tree[id].self_closing = true;
+                }
+                self.end()?;
+            }
+            Ok(Event::Text(e)) => self.text(&String::from_utf8_lossy(e.as_ref()))?,
+            Ok(Event::GeneralRef(e)) => {
+                let reference = format!("&{};", String::from_utf8_lossy(e.as_ref()));
+                let Ok(text) = unescape(&reference) else {
+                    return Err(Camt053Format::unknown_value_error(
+                        format!("неизвестная ссылка на сущность {reference}").as_str(),
+                    ));
+                };
+                self.text(&text)?;
+            }
+            Ok(Event::CData(e)) => {
+                self.text(&String::from_utf8_lossy(e.as_ref()))?;
+                if let Some(capture) = &mut self.capture {
+                    capture.tree[capture.current].cdata = true;
+                }
+            }
+            Ok(Event::End(_)) => self.end()?,
+            Ok(Event::Eof) => {
+                if self.capture.is_some() || !self.path.is_empty() {
+                    return Err(Camt053Format::data_format_error("документ оборван до закрытия всех тегов"));
+                }
+                return Ok(false);
+            }
+            Ok(_) => (),
+            Err(e) => {
+                return Err(Camt053Format::read_write_error(
+                    format!("не удалось разложить xml файл на теги {}", e).as_str(),
+                ));
+            }
+        }
+        Ok(true)
+    }
+
+    fn next_event(&mut self) -> Result<Option<Camt053Event>, FormatError> {
+        let mut buf = std::mem::take(&mut self.buf);
+        while self.pending.is_empty() && !self.finished {
+            buf.clear();
+            match self.read_event(&mut buf) {
+                Ok(true) => (),
+                Ok(false) => self.finished = true,
+                Err(e) => {
+                    self.finished = true;
+                    return Err(e);
+                }
+            }
+        }
+        self.buf = buf;
+        Ok(self.pending.pop_front())
+    }
+}
+
+/// Потоковый читатель CAMT.053.
+///
+/// События читаются через [`Iterator`] или [`Self::next_statement`]; [`Self::into_transactions`]
+/// читает проводки, оставшиеся в потоке, не строя дерево документа.
+pub struct Camt053Reader<R> {
+    state: ReaderState<R>,
+}
+
+impl<R: BufRead> Camt053Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            state: ReaderState {
+                reader: NsReader::from_reader(reader),
+                buf: Vec::new(),
+                path: Vec::new(),
+                capture: None,
+                pending: VecDeque::new(),
+                version: None,
+                statement: None,
+                currency: String::new(),
+                in_statement: None,
+                statements: 0,
+                entries_in_statement: 0,
+                rows: 0,
+                related_ref: None,
+                finished: false,
+                expand_batches: false,
+                bank_tx_codes: BankTxCodeMap::default(),
+                mode: ReadMode::Strict,
+                diagnostics: Vec::new(),
+            },
+        }
+    }
+
+    /// Версия сообщения по пространству имён; известна после первого события.
+    pub fn version(&self) -> Option<Camt053Version> {
+        self.state.version
+    }
+
+    /// Разворачивать ли пакетные проводки в [`Self::next_statement`] и при чтении транзакций,
+    /// см. [`Camt053Format::set_expand_batches`].
+    pub fn set_expand_batches(&mut self, expand: bool) {
+        self.state.expand_batches = expand;
+    }
+
+    /// Таблица кодов операций для [`Self::next_statement`],
+    /// см. [`Camt053Format::set_bank_tx_codes`].
+    pub fn set_bank_tx_codes(&mut self, codes: BankTxCodeMap) {
+        self.state.bank_tx_codes = codes;
+    }
+
+    /// Режим чтения проводок в [`Self::next_statement`], по умолчанию [`ReadMode::Strict`].
+    pub fn set_read_mode(&mut self, mode: ReadMode) {
+        self.state.mode = mode;
+    }
+
+    /// Забрать проводки, пропущенные [`Self::next_statement`] в режиме [`ReadMode::Lenient`].
+    pub fn take_diagnostics(&mut self) -> Vec<RowDiagnostic> {
+        std::mem::take(&mut self.state.diagnostics)
+    }
+
+    /// Прочитать следующую выписку целиком в формато-независимую модель.
+    ///
+    /// В памяти оказываются проводки только одной выписки, поэтому документ с несколькими
+    /// выписками можно конвертировать по одной выписке.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если XML некорректен или произошла ошибка чтения, а в режиме
+    /// [`ReadMode::Strict`] — также при первой проводке, которую не удалось разобрать. В режиме
+    /// [`ReadMode::Lenient`] такие проводки пропускаются, см. [`Self::take_diagnostics`].
+    pub fn next_statement(&mut self) -> Result<Option<Statement>, FormatError> {
+        let state = &mut self.state;
+        let mut current: Option<Statement> = None;
+        while let Some(event) = state.next_event()? {
+            match event {
+                Camt053Event::GroupHeader(_) => (),
+                Camt053Event::Statement(header) => {
+                    if current.is_some() {
+                        state.pending.push_front(Camt053Event::Statement(header));
+                        break;
+                    }
+                    let mut statement = header.to_statement();
+                    statement.related_ref = state.related_ref.clone();
+                    current = Some(statement);
+                }
+                Camt053Event::Entry(entry) => {
+                    let Some(statement) = current.as_mut() else { continue };
+                    let entries = Camt053Format::statement_entries(
+                        &entry.tree,
+                        FRAGMENT_ROOT,
+                        entry.index + 1,
+                        entry.row,
+                        state.expand_batches,
+                        &state.bank_tx_codes,
+                    );
+                    for (e, error) in entries {
+                        match error {
+                            None => statement.entries.push(e),
+                            Some(d) if state.mode == ReadMode::Strict => {
+                                return Err(Camt053Format::data_format_error(&d.to_string()));
+                            }
+                            Some(d) => state.diagnostics.push(d),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(current)
+    }
+}
+
+impl<R: BufRead> Iterator for Camt053Reader<R> {
+    type Item = Result<Camt053Event, FormatError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.state.next_event().transpose()
+    }
+}
+
+impl<R: BufRead> Camt053Reader<R> {
+    /// Собрать транзакции из проводок, оставшихся в потоке.
+    ///
+    /// Читатель потребляется: поток можно прочитать только один раз.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если XML некорректен, а в режиме [`ReadMode::Strict`] — также
+    /// при первой проводке, которую не удалось разобрать.
+    pub fn into_transactions(mut self, mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+        let state = &mut self.state;
+        let mut collected = CollectedTransactions::default();
+        while let Some(event) = state.next_event()? {
+            if let Camt053Event::Entry(entry) = event {
+                for transaction in entry.transactions(state.expand_batches, &state.bank_tx_codes) {
+                    collected
+                        .push(transaction, mode)
+                        .map_err(|d| Camt053Format::data_format_error(&d.to_string()))?;
+                }
+            }
+        }
+        Ok(collected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camt052_format::Camt052Format;
+    use crate::camt053_model::Camt053Document;
+    use crate::statement::Statements;
+    use crate::transactions_holder::TransactionsReader;
+    use std::fs::File;
+    use std::io::{BufReader, Cursor};
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08"><BkToCstmrStmt>
+  <GrpHdr><MsgId>MSG</MsgId><OrgnlBizQry><MsgId>Q1</MsgId></OrgnlBizQry></GrpHdr>
+  <Stmt><Id>S1</Id><Acct><Id><Othr><Id>ACC</Id></Othr></Id></Acct>
+    <Bal><Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp><Amt Ccy="EUR">10</Amt><CdtDbtInd>CRDT</CdtDbtInd><Dt><Dt>2024-01-01</Dt></Dt></Bal>
+    <Ntry><Amt Ccy="EUR">1.50</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2024-01-02</Dt></ValDt><AddtlNtryInf>A &amp; B</AddtlNtryInf></Ntry>
+    <Ntry><Amt Ccy="EUR">2</Amt><CdtDbtInd>DBIT</CdtDbtInd><ValDt><Dt>2024-01-03</Dt></ValDt></Ntry>
+  </Stmt>
+  <Stmt><Id>S2</Id><Acct><Id><Othr><Id>ACC2</Id></Othr></Id></Acct></Stmt>
+</BkToCstmrStmt></Document>"#;
+
+    fn reader(xml: &str) -> Camt053Reader<Cursor<&str>> {
+        Camt053Reader::new(Cursor::new(xml))
+    }
+
+    #[test]
+    fn yields_header_statement_and_entries_in_order() {
+        let mut camt = reader(XML);
+        let mut got = Vec::new();
+        for event in camt.by_ref() {
+            got.push(match event.unwrap() {
+                Camt053Event::GroupHeader(h) => format!("header {}", h.message_id),
+                Camt053Event::Statement(s) => format!("statement {} {}", s.index(), s.to_model().unwrap().id),
+                Camt053Event::Entry(e) => {
+                    let model = e.to_model().unwrap();
+                    format!("entry {}/{} {} {:?}", e.statement(), e.row(), model.amount.value, model.additional_info)
+                }
+            });
+        }
+        assert_eq!(
+            got,
+            vec![
+                "header MSG",
+                "statement 0 S1",
+                "entry 0/1 1.50 Some(\"A & B\")",
+                "entry 0/2 2 None",
+                "statement 1 S2",
+            ]
+        );
+        assert_eq!(camt.version(), Some(Camt053Version::V08));
+    }
+
+    #[test]
+    fn matches_tree_based_reading() {
+        let tree = Camt053Format::from_read(&mut Cursor::new(XML)).unwrap();
+        assert_eq!(
+            reader(XML).into_transactions(ReadMode::Strict).unwrap().transactions,
+            tree.collect_transactions().unwrap()
+        );
+
+        let mut stream = reader(XML);
+        let mut statements = Vec::new();
+        while let Some(st) = stream.next_statement().unwrap() {
+            statements.push(st);
+        }
+        assert_eq!(Statements { statements }, Statements::from(tree));
+
+        let example = || BufReader::new(File::open("examples/data/camt053.exmpl").unwrap());
+        let tree = Camt053Format::from_read(&mut example()).unwrap();
+        let document = Camt053Document::try_from(&tree).unwrap();
+        let entries: Vec<ReportEntry> = Camt053Reader::new(example())
+            .filter_map(|e| match e.unwrap() {
+                Camt053Event::Entry(e) => Some(e.to_model().unwrap()),
+                _ => None,
+            })
+            .collect();
+        let expected: Vec<ReportEntry> = document.statements.into_iter().flat_map(|s| s.entries).collect();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn reports_bad_entry_by_document_row() {
+        let xml = XML.replace("<Amt Ccy=\"EUR\">2</Amt>", "<Amt Ccy=\"EUR\">x</Amt>");
+        let collected = reader(&xml).into_transactions(ReadMode::Lenient).unwrap();
+        assert_eq!(collected.transactions.len(), 1);
+        assert_eq!(collected.diagnostics[0].row, 2);
+        assert!(reader(&xml).into_transactions(ReadMode::Strict).is_err());
+
+        let broken = &XML[..XML.find("</Ntry>").unwrap()];
+        let events: Vec<_> = reader(broken).collect();
+        assert!(matches!(events.last(), Some(Err(_))));
+    }
+
+    #[test]
+    fn reads_camt052_reports() {
+        let example = || BufReader::new(File::open("examples/data/camt052.exmpl").unwrap());
+        let report = Camt052Format::from_read(&mut example()).unwrap();
+        let stream = Camt053Reader::new(example());
+        assert_eq!(stream.into_transactions(ReadMode::Strict).unwrap().transactions, report.collect_transactions().unwrap());
+
+        let mut stream = Camt053Reader::new(example());
+        let statement = stream.next_statement().unwrap().unwrap();
+        assert_eq!(stream.version(), Some(Camt053Version::V08));
+        assert_eq!(statement.id, "RPT-0001");
+        assert_eq!(statement.entries.len(), 2);
+        assert!(stream.next_statement().unwrap().is_none());
+    }
+
+    #[test]
+    fn expands_batches_like_document() {
+        let example = || BufReader::new(File::open("examples/data/camt053_batch.exmpl").unwrap());
+        let mut document = Camt053Format::from_read(&mut example()).unwrap();
+        document.set_expand_batches(true);
+
+        let mut stream = Camt053Reader::new(example());
+        stream.set_expand_batches(true);
+        let transactions = stream.into_transactions(ReadMode::Strict).unwrap().transactions;
+        assert_eq!(transactions.len(), 4);
+        assert_eq!(transactions, document.collect_transactions().unwrap());
+
+        let mut stream = Camt053Reader::new(example());
+        stream.set_expand_batches(true);
+        let statement = stream.next_statement().unwrap().unwrap();
+        assert_eq!(Statements { statements: vec![statement] }, Statements::from(document));
+    }
+}
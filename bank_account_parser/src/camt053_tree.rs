@@ -0,0 +1,333 @@
+//! Дерево XML-тегов CAMT.053 на арене.
+//!
+//! Теги хранятся в одном векторе [`TagTree`], а связи между ними — индексами [`NodeId`].
+//! Дерево не содержит `Rc`/`RefCell`, поэтому [`crate::camt053_format::Camt053Format`] можно
+//! передавать между потоками и разделять между ними (`Send + Sync`).
+
+use std::ops::{Index, IndexMut};
+
+/// Идентификатор тега в [`TagTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Первый тег, добавленный в дерево.
+    pub const FIRST: NodeId = NodeId(0);
+
+    /// Позиция тега в арене.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub text: Option<String>,
+    pub attrs: Vec<(String, String)>,
+    /// Дети тега; связи меняются только через [`TagTree::attach`] и [`TagTree::detach`].
+    pub(crate) childrens: Vec<NodeId>,
+    pub(crate) parent: Option<NodeId>,
+    /// Текст был записан секцией `<![CDATA[...]]>`.
+    pub cdata: bool,
+    /// Тег был записан в сокращённой форме `<Tag/>`.
+    pub self_closing: bool,
+    /// Пространство имён тега, если оно объявлено в документе.
+    pub namespace: Option<String>,
+}
+
+impl Tag {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Обрезать собранный текст по краям; пустой текст удаляется.
+    pub(crate) fn trim_text(&mut self) {
+        self.text = self
+            .text
+            .take()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
+    }
+
+    /// Имя тега без префикса пространства имён: `Stmt` для `ns2:Stmt`.
+    pub fn local_name(&self) -> &str {
+        self.name.rsplit(':').next().unwrap_or_default()
+    }
+
+    /// Префикс пространства имён: `ns2` для `ns2:Stmt`.
+    pub(crate) fn prefix(&self) -> Option<&str> {
+        self.name.split_once(':').map(|(p, _)| p)
+    }
+
+    /// Значение атрибута `name`.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+    /// Дети тега в порядке документа.
+    pub fn children(&self) -> &[NodeId] {
+        &self.childrens
+    }
+
+    /// Родитель тега; `None` у корня и у отсоединённого поддерева.
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+}
+
+/// Арена тегов.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TagTree {
+    nodes: Vec<Tag>,
+}
+
+impl TagTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавить тег последним ребёнком `parent` (или без родителя, если `parent` — `None`).
+    pub fn push(&mut self, parent: Option<NodeId>, mut tag: Tag) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        tag.parent = parent;
+        tag.childrens.clear();
+        self.nodes.push(tag);
+        if let Some(parent) = parent {
+            self.nodes[parent.0].childrens.push(id);
+        }
+        id
+    }
+
+    /// Вставить тег ребёнком `parent` на позицию `index`.
+    ///
+    /// # Паника
+    /// Если `index` больше числа детей `parent`.
+    pub fn insert(&mut self, parent: NodeId, index: usize, tag: Tag) -> NodeId {
+        let id = self.push(None, tag);
+        self.attach(id, parent, index);
+        id
+    }
+
+    /// Отсоединить поддерево `id` от родителя. Теги остаются в арене, но недостижимы от корня;
+    /// поддерево можно снова присоединить через [`TagTree::attach`].
+    pub fn detach(&mut self, id: NodeId) {
+        if let Some(parent) = self[id].parent.take() {
+            self[parent].childrens.retain(|&c| c != id);
+        }
+    }
+
+    /// Перенести поддерево `id` ребёнком `parent` на позицию `index` (считается после
+    /// отсоединения `id` от прежнего родителя).
+    ///
+    /// # Паника
+    /// Если `index` больше числа детей `parent` или `parent` лежит внутри поддерева `id`.
+    pub fn attach(&mut self, id: NodeId, parent: NodeId, index: usize) {
+        assert!(!self.contains(id, parent), "тег нельзя перенести внутрь него самого");
+        self.detach(id);
+        self[parent].childrens.insert(index, id);
+        self[id].parent = Some(parent);
+    }
+
+    /// `id` лежит в поддереве `ancestor` (или совпадает с ним).
+    pub fn contains(&self, ancestor: NodeId, mut id: NodeId) -> bool {
+        loop {
+            if id == ancestor {
+                return true;
+            }
+            match self.parent(id) {
+                Some(parent) => id = parent,
+                None => return false,
+            }
+        }
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Tag> {
+        self.nodes.get(id.0)
+    }
+
+    /// Изменяемый тег. Связи тега с родителем и детьми так не изменить, для них есть
+    /// [`TagTree::attach`] и [`TagTree::detach`].
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Tag> {
+        self.nodes.get_mut(id.0)
+    }
+
+    /// Количество тегов в арене.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self[id].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self[id].childrens
+    }
+
+    /// Верхний тег дерева, в которое входит `id`.
+    pub fn top(&self, mut id: NodeId) -> NodeId {
+        while let Some(parent) = self.parent(id) {
+            id = parent;
+        }
+        id
+    }
+
+    /// Первый ребёнок с локальным именем `name`.
+    pub fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id).iter().copied().find(|&c| self[c].local_name() == name)
+    }
+
+    /// Скопировать поддерево `id` из `other` последним ребёнком `parent`.
+    pub fn copy_from(&mut self, parent: Option<NodeId>, other: &TagTree, id: NodeId) -> NodeId {
+        let new = self.push(parent, other[id].clone());
+        for &child in other.children(id) {
+            self.copy_from(Some(new), other, child);
+        }
+        new
+    }
+}
+
+impl Index<NodeId> for TagTree {
+    type Output = Tag;
+
+    fn index(&self, id: NodeId) -> &Tag {
+        &self.nodes[id.0]
+    }
+}
+
+impl IndexMut<NodeId> for TagTree {
+    fn index_mut(&mut self, id: NodeId) -> &mut Tag {
+        &mut self.nodes[id.0]
+    }
+}
+
+/// Тег с детьми, собираемый до добавления в дерево.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Element {
+    pub(crate) tag: Tag,
+    pub(crate) children: Vec<Element>,
+}
+
+impl Element {
+    pub(crate) fn new(name: &str) -> Self {
+        Element {
+            tag: Tag::new(name),
+            children: Vec::new(),
+        }
+    }
+
+    pub(crate) fn text(name: &str, text: impl Into<String>) -> Self {
+        let mut e = Self::new(name);
+        e.tag.text = Some(text.into());
+        e
+    }
+
+    pub(crate) fn attr(mut self, key: &str, value: &str) -> Self {
+        self.tag.attrs.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub(crate) fn child(mut self, child: Element) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub(crate) fn child_opt(self, child: Option<Element>) -> Self {
+        match child {
+            Some(c) => self.child(c),
+            None => self,
+        }
+    }
+
+    pub(crate) fn text_opt(self, name: &str, text: Option<&String>) -> Self {
+        self.child_opt(text.map(|t| Self::text(name, t.as_str())))
+    }
+
+    pub(crate) fn children(mut self, children: impl IntoIterator<Item = Element>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    /// Добавить в `tree` последним ребёнком `parent`.
+    pub(crate) fn build(self, tree: &mut TagTree, parent: Option<NodeId>) -> NodeId {
+        let id = tree.push(parent, self.tag);
+        for child in self.children {
+            child.build(tree, Some(id));
+        }
+        id
+    }
+
+    /// Отдельное дерево с этим тегом в корне.
+    pub(crate) fn into_tree(self) -> (TagTree, NodeId) {
+        let mut tree = TagTree::new();
+        let root = self.build(&mut tree, None);
+        (tree, root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_links_parent_and_children() {
+        let mut tree = TagTree::new();
+        let root = tree.push(None, Tag::new("Stmt"));
+        let ntry = tree.push(Some(root), Tag::new("ns:Ntry"));
+        let amt = tree.push(Some(ntry), Tag::new("Amt"));
+
+        assert_eq!(tree.children(root), &[ntry]);
+        assert_eq!(tree.parent(amt), Some(ntry));
+        assert_eq!(tree.child(root, "Ntry"), Some(ntry));
+        assert_eq!(tree.len(), 3);
+
+        let mut copy = TagTree::new();
+        let copied = copy.copy_from(None, &tree, ntry);
+        assert_eq!(copy[copied].name, "ns:Ntry");
+        assert_eq!(copy[copied].parent, None);
+        assert_eq!(copy[copy.children(copied)[0]].name, "Amt");
+    }
+
+    #[test]
+    fn insert_detach_and_attach_keep_links_consistent() {
+        let mut tree = TagTree::new();
+        let root = tree.push(None, Tag::new("Stmt"));
+        let first = tree.push(Some(root), Tag::new("Ntry"));
+        let second = tree.push(Some(root), Tag::new("Ntry"));
+        let id = tree.insert(root, 0, Tag::new("Id"));
+        assert_eq!(tree.children(root), &[id, first, second]);
+
+        tree.attach(second, first, 0);
+        assert_eq!(tree.children(root), &[id, first]);
+        assert_eq!(tree.parent(second), Some(first));
+        assert!(tree.contains(root, second));
+
+        tree.detach(first);
+        assert_eq!(tree.children(root), &[id]);
+        assert_eq!(tree.parent(first), None);
+        assert!(!tree.contains(root, second));
+    }
+
+    #[test]
+    #[should_panic]
+    fn attach_rejects_cycles() {
+        let mut tree = TagTree::new();
+        let root = tree.push(None, Tag::new("Stmt"));
+        let ntry = tree.push(Some(root), Tag::new("Ntry"));
+        tree.attach(root, ntry, 0);
+    }
+
+    #[test]
+    fn tree_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<TagTree>();
+        assert_send_sync::<crate::camt053_format::Camt053Format>();
+    }
+}
@@ -0,0 +1,175 @@
+//! Уведомление о списании или зачислении camt.054 (`BkToCstmrDbtCdtNtfctn/Ntfctn`).
+//!
+//! Уведомление сообщает об отдельных проводках по счёту. Оно устроено как выписка CAMT.053
+//! без остатков: тот же заголовок группы, реквизиты счёта и проводки `Ntry`, поэтому
+//! [`Camt054Format`] хранит документ в [`Camt053Format`] и читает проводки тем же разбором.
+//! Уведомления за день сверяются с выпиской на конец дня через
+//! [`crate::transactions_holder::TransactionHolder::merge`] и [`crate::reconcile`].
+
+use crate::bank_tx_code::BankTxCodeMap;
+use crate::camt053_format::{Camt053Format, Camt053Version, CamtMessage, XmlWriteOptions};
+use crate::error::{FormatError, GeneratorFormatError};
+use crate::statement::{CollectedStatements, Statements};
+use crate::transactions_holder::{CollectedTransactions, ReadMode, TransactionsReader};
+use std::io::Write;
+
+#[derive(Debug, Clone)]
+pub struct Camt054Format {
+    document: Camt053Format,
+}
+
+impl GeneratorFormatError for Camt054Format {
+    const ERROR_PREFIX: &'static str = "Ошибка разбора формата camt054";
+}
+
+impl Camt054Format {
+    /// Разобрать camt.054 из потока ввода, см. [`Camt053Format::from_read`].
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если XML некорректен или документ не является уведомлением
+    /// `BkToCstmrDbtCdtNtfctn` (например, это выписка camt.053).
+    ///
+    pub fn from_read<R: std::io::Read>(r: &mut R) -> Result<Camt054Format, FormatError> {
+        let document = Camt053Format::from_read(r)?;
+        match document.message() {
+            Some(CamtMessage::Notification) => Ok(Self { document }),
+            _ => Err(Self::data_format_error(
+                "в документе нет уведомления BkToCstmrDbtCdtNtfctn или Ntfctn",
+            )),
+        }
+    }
+
+    /// Записать уведомление в XML, см. [`Camt053Format::write_to`].
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> Result<(), FormatError> {
+        self.document.write_to(writer)
+    }
+
+    /// Записать уведомление в XML с параметрами `options`, см. [`Camt053Format::write_with`].
+    pub fn write_with<W: Write>(&self, writer: &mut W, options: &XmlWriteOptions) -> Result<(), FormatError> {
+        self.document.write_with(writer, options)
+    }
+
+    /// Версия сообщения по пространству имён `camt.054.001.NN`.
+    pub fn version(&self) -> Option<Camt053Version> {
+        self.document.message_version(CamtMessage::Notification)
+    }
+
+    /// Подготовить уведомление к записи в версии `version`, см. [`Camt053Format::set_version`].
+    pub fn set_version(&mut self, version: Camt053Version) {
+        self.document.set_message_version(CamtMessage::Notification, version);
+    }
+
+    /// Дерево тегов уведомления с поиском по путям и правкой.
+    pub fn document(&self) -> &Camt053Format {
+        &self.document
+    }
+
+    /// Дерево тегов уведомления для правки.
+    pub fn document_mut(&mut self) -> &mut Camt053Format {
+        &mut self.document
+    }
+
+    /// Дерево тегов уведомления.
+    pub fn into_document(self) -> Camt053Format {
+        self.document
+    }
+
+    /// Выписки уведомления, см. [`Camt053Format::into_statements`].
+    pub fn into_statements(self, mode: ReadMode) -> Result<CollectedStatements, FormatError> {
+        self.document.statements_of(CamtMessage::Notification, mode)
+    }
+}
+
+/// Каждое уведомление `Ntfctn` — отдельная выписка без остатков.
+impl From<Camt054Format> for Statements {
+    fn from(value: Camt054Format) -> Self {
+        value.document.collect_statements(CamtMessage::Notification).statements
+    }
+}
+
+/// Выписки в документ `camt.054.001.02`; остатки выписок не записываются.
+impl From<Statements> for Camt054Format {
+    fn from(v: Statements) -> Self {
+        Self::from_statements_with(v, &BankTxCodeMap::default())
+    }
+}
+
+impl Camt054Format {
+    /// Выписки в уведомление, как `From<Statements>`, с таблицей кодов операций `codes`.
+    pub fn from_statements_with(v: Statements, codes: &BankTxCodeMap) -> Self {
+        Self {
+            document: Camt053Format::from_statements(v, CamtMessage::Notification, codes),
+        }
+    }
+}
+
+impl TransactionsReader for Camt054Format {
+    fn read_transactions(&self, mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+        self.document.transactions_of(CamtMessage::Notification, mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camt053_schema;
+    use crate::reconcile::reconcile;
+    use crate::transactions_holder::TransactionHolder;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    const SECOND: &str = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.054.001.08">
+        <BkToCstmrDbtCdtNtfctn><GrpHdr><MsgId>N2</MsgId><CreDtTm>2020-01-01T15:00:00</CreDtTm></GrpHdr>
+        <Ntfctn><Id>N2</Id><Acct><Id><IBAN>NL81ASNB9999999999</IBAN></Id></Acct>
+        <Ntry><Amt Ccy="EUR">10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2020-01-01</Dt></ValDt></Ntry>
+        </Ntfctn></BkToCstmrDbtCdtNtfctn></Document>"#;
+
+    fn example() -> Camt054Format {
+        Camt054Format::from_read(&mut File::open("./examples/data/camt054.exmpl").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_notification() {
+        let camt = example();
+        assert_eq!(camt.version(), Some(Camt053Version::V02));
+
+        let statements = Statements::from(camt.clone());
+        let [notification] = statements.statements.as_slice() else {
+            panic!("ожидалось одно уведомление");
+        };
+        assert_eq!(notification.id, "NTFCTN-0017");
+        assert_eq!(notification.opening_balance, None);
+        assert_eq!(notification.entries.len(), 1);
+
+        let transactions = camt.collect_transactions().unwrap();
+        assert_eq!(transactions[0].customer_ref.as_deref(), Some("NL47INGB99999999"));
+
+        let statement = "<BkToCstmrStmt><GrpHdr/><Stmt><Id>1</Id></Stmt></BkToCstmrStmt>";
+        assert!(Camt054Format::from_read(&mut Cursor::new(statement)).is_err());
+    }
+
+    #[test]
+    fn writes_notification_without_balances() {
+        let mut statements = Statements::from(example());
+        statements.statements[0].opening_balance = Some(Default::default());
+        let camt = Camt054Format::from(statements);
+        assert!(camt053_schema::validate(camt.document()).is_empty());
+        assert!(camt.document().select("//Bal").unwrap().is_empty());
+        assert_eq!(camt.document().select("/Document/BkToCstmrDbtCdtNtfctn/Ntfctn/Ntry").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn days_notifications_reconcile_against_statement() {
+        let mut notifications = TransactionHolder::new(example()).unwrap();
+        let second = Camt054Format::from_read(&mut Cursor::new(SECOND)).unwrap();
+        notifications.merge(TransactionHolder::new(second).unwrap());
+        assert_eq!(notifications.len(), 2);
+
+        let statement = Camt053Format::from_read(&mut File::open("./examples/data/camt053.exmpl").unwrap()).unwrap();
+        let statement = TransactionHolder::new(statement).unwrap();
+        let result = reconcile(&notifications, &statement);
+        assert_eq!(result.matched.len(), 1);
+        assert_eq!(result.unmatched_left.len(), 1);
+        assert!(result.unmatched_right.is_empty());
+    }
+}
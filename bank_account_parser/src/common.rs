@@ -13,6 +13,8 @@ pub mod debit_credit {
     }
 
     impl DebitOrCredit {
+        // имя и сигнатура — часть прежнего API, ими пользуются форматы и бинарники
+        #[allow(clippy::wrong_self_convention)]
         pub fn to_string(&self) -> &str {
             match self {
                 DebitOrCredit::Debit => "D",
@@ -1,8 +1,12 @@
 use crate::error::{FormatError, GeneratorFormatError};
 use crate::common::debit_credit::DebitOrCredit;
-use crate::transactions_holder::{Transaction, TransactionsReader};
+use crate::mt940_format::MT940Format;
+use crate::statement::{Account, Balance, CollectedStatements, Entry, Statement, Statements};
+use crate::transactions_holder::{
+    CollectedTransactions, Counterparty, ReadMode, RowDiagnostic, Transaction, TransactionsReader,
+};
 use chrono::NaiveDate;
-use std::collections::HashMap;
+use rust_decimal::Decimal;
 
 enum State {
     Before,
@@ -12,11 +16,55 @@ enum State {
 }
 
 
+const DATE_COLUMN: &str = "Дата проводки";
+const DEBIT_COLUMN: &str = "Сумма по дебету";
+const CREDIT_COLUMN: &str = "Сумма по кредиту";
+const DOCUMENT_COLUMN: &str = "№ документа";
+const OPERATION_KIND_COLUMN: &str = "ВО";
+const DEBIT_ACCOUNT_COLUMNS: [&str; 2] = ["Счет Дебет", "СчетДебет"];
+const CREDIT_ACCOUNT_COLUMNS: [&str; 3] = ["Счет Кредит", "СчетКредит", "Кредит"];
+const PURPOSE_COLUMN: &str = "Назначение платежа";
+const OPENING_BALANCE_LABEL: &str = "Входящий остаток";
+const CLOSING_BALANCE_LABEL: &str = "Исходящий остаток";
+const ACCOUNT_LABEL: &str = "Выписка по счету";
+const CURRENCY_LABEL: &str = "Валюта";
+
+const MONTHS: [&str; 12] = [
+    "января", "февраля", "марта", "апреля", "мая", "июня",
+    "июля", "августа", "сентября", "октября", "ноября", "декабря",
+];
+
+/// Строка таблицы выписки, разобранная в типизированный вид.
+pub(crate) struct CsvEntry {
+    pub date: NaiveDate,
+    pub operation_type: DebitOrCredit,
+    pub amount: Decimal,
+    pub document_no: Option<String>,
+    pub operation_kind: Option<String>,
+    pub purpose: Option<String>,
+    /// Счёт по дебету (плательщик).
+    pub debit_party: Option<Counterparty>,
+    /// Счёт по кредиту (получатель).
+    pub credit_party: Option<Counterparty>,
+}
+
+impl CsvEntry {
+    /// Контрагент: получатель для списаний и плательщик для поступлений.
+    pub fn counterparty(&self) -> Option<&Counterparty> {
+        match self.operation_type {
+            DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => self.credit_party.as_ref(),
+            DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => self.debit_party.as_ref(),
+        }
+    }
+}
+
 #[derive(Default)]
 #[derive(Debug)]
 pub struct CSVFormat {
     columns: Vec<String>,
     table: Vec<Vec<String>>,
+    /// Номера строк файла, из которых прочитаны строки `table`.
+    table_lines: Vec<usize>,
     other_before: Vec<Vec<String>>,
     other_after: Vec<Vec<String>>,
 }
@@ -35,6 +83,192 @@ impl CSVFormat {
         true
     }
 
+    fn parse_amount(s: &str) -> Option<Decimal> {
+        s.replace(",", ".").replace(' ', "").parse().ok()
+    }
+
+    /// Разобрать дату вида `01 января 2024 г.` или `01.01.2024`.
+    fn parse_date(s: &str) -> Option<NaiveDate> {
+        if let Ok(d) = NaiveDate::parse_from_str(s, "%d.%m.%Y") {
+            return Some(d);
+        }
+        let mut parts = s.split_whitespace();
+        let day = parts.next()?.parse().ok()?;
+        let month = parts.next()?;
+        let month = MONTHS.iter().position(|m| *m == month)? as u32 + 1;
+        let year = parts.next()?.parse().ok()?;
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == name)
+    }
+
+    fn any_column(&self, names: &[&str]) -> Option<usize> {
+        names.iter().find_map(|n| self.column(n))
+    }
+
+    /// Разобрать ячейку счёта вида `счёт\nИНН\nнаименование`.
+    fn parse_party(cell: &str) -> Option<Counterparty> {
+        let mut lines = cell.lines().map(str::trim).filter(|l| !l.is_empty());
+        let account = lines.next()?.to_string();
+        let inn = lines.next().map(str::to_string);
+        let name: Vec<&str> = lines.collect();
+        Some(Counterparty {
+            account: Some(account),
+            inn,
+            name: (!name.is_empty()).then(|| name.join(" ")),
+            bic: None,
+        })
+    }
+
+    /// Значение, следующее в строках до таблицы за ячейкой `label`.
+    fn labeled_value(&self, label: &str) -> Option<String> {
+        let cells: Vec<&String> = self.other_before.iter().flatten().collect();
+        let pos = cells.iter().position(|c| c.as_str() == label)?;
+        cells[pos + 1..].iter().find(|c| !c.is_empty()).map(|c| c.to_string())
+    }
+
+    /// Номер счёта из строк до таблицы: значение после `Выписка по счету`
+    /// или первая ячейка из 20 цифр.
+    pub(crate) fn account_id(&self) -> Option<String> {
+        self.labeled_value(ACCOUNT_LABEL).or_else(|| {
+            self.other_before
+                .iter()
+                .flatten()
+                .find(|c| c.len() == 20 && c.chars().all(|x| x.is_ascii_digit()))
+                .cloned()
+        })
+    }
+
+    /// Валюта счёта из строк до таблицы.
+    pub(crate) fn currency(&self) -> Option<String> {
+        if let Some(currency) = self.labeled_value(CURRENCY_LABEL) {
+            return Some(currency);
+        }
+        let cells: Vec<&String> = self.other_before.iter().flatten().collect();
+        cells
+            .iter()
+            .any(|c| c.to_lowercase().contains("российский рубль"))
+            .then(|| "RUB".to_string())
+    }
+
+    fn balance(&self, label: &str) -> Option<Balance> {
+        let row = self.other_after.iter().find(|r| r.iter().any(|c| c == label))?;
+        let values: Vec<&String> = row
+            .iter()
+            .skip_while(|c| c.as_str() != label)
+            .skip(1)
+            .filter(|c| !c.is_empty())
+            .collect();
+        let debit = Self::parse_amount(values.first()?)?;
+        let credit = Self::parse_amount(values.get(1)?)?;
+        let date = values.iter().rev().find_map(|c| Self::parse_date(c)).unwrap_or_default();
+
+        let (debit_credit_indicator, amount) = if debit.is_zero() {
+            (DebitOrCredit::Credit, credit)
+        } else {
+            (DebitOrCredit::Debit, debit)
+        };
+        Some(Balance {
+            debit_credit_indicator,
+            date,
+            currency: self.currency().unwrap_or_default(),
+            amount,
+            is_intermediate: false,
+        })
+    }
+
+    pub(crate) fn opening_balance(&self) -> Option<Balance> {
+        self.balance(OPENING_BALANCE_LABEL)
+    }
+
+    pub(crate) fn closing_balance(&self) -> Option<Balance> {
+        self.balance(CLOSING_BALANCE_LABEL)
+    }
+
+    /// Номер строки файла для строки таблицы `index`.
+    fn line_of(&self, index: usize) -> usize {
+        self.table_lines
+            .get(index)
+            .copied()
+            .unwrap_or(self.other_before.len() + 2 + index)
+    }
+
+    /// Строки таблицы в типизированном виде.
+    ///
+    /// Для каждой строки возвращается либо разобранная проводка, либо описание
+    /// первой ячейки, которую не удалось разобрать.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если в таблице нет колонки даты или ни одной колонки суммы.
+    pub(crate) fn entries(&self) -> Result<Vec<Result<CsvEntry, RowDiagnostic>>, FormatError> {
+        let Some(date) = self.column(DATE_COLUMN) else {
+            return Err(Self::data_format_error(&format!("нет колонки \"{DATE_COLUMN}\"")));
+        };
+        let debit = self.column(DEBIT_COLUMN);
+        let credit = self.column(CREDIT_COLUMN);
+        if debit.is_none() && credit.is_none() {
+            return Err(Self::data_format_error(&format!(
+                "нет колонок \"{DEBIT_COLUMN}\" и \"{CREDIT_COLUMN}\""
+            )));
+        }
+        let document_no = self.column(DOCUMENT_COLUMN);
+        let operation_kind = self.column(OPERATION_KIND_COLUMN);
+        let purpose = self.column(PURPOSE_COLUMN);
+        let debit_account = self.any_column(&DEBIT_ACCOUNT_COLUMNS);
+        let credit_account = self.any_column(&CREDIT_ACCOUNT_COLUMNS);
+
+        let cell = |row: &Vec<String>, index: Option<usize>| -> Option<String> {
+            index
+                .and_then(|i| row.get(i))
+                .filter(|c| !c.is_empty())
+                .cloned()
+        };
+
+        let entries = self
+            .table
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let line = self.line_of(index);
+
+                let raw_date = cell(row, Some(date)).unwrap_or_default();
+                let date = Self::parse_date(&raw_date).ok_or_else(|| {
+                    RowDiagnostic::new(line, DATE_COLUMN, &raw_date, "не удалось разобрать дату")
+                })?;
+
+                let (operation_type, column, raw_amount) = match (cell(row, debit), cell(row, credit)) {
+                    (Some(v), _) => (DebitOrCredit::Debit, DEBIT_COLUMN, v),
+                    (None, Some(v)) => (DebitOrCredit::Credit, CREDIT_COLUMN, v),
+                    (None, None) => {
+                        return Err(RowDiagnostic::new(
+                            line,
+                            CREDIT_COLUMN,
+                            "",
+                            "не указана сумма ни по дебету, ни по кредиту",
+                        ));
+                    }
+                };
+                let amount = Self::parse_amount(&raw_amount).ok_or_else(|| {
+                    RowDiagnostic::new(line, column, &raw_amount, "не удалось разобрать сумму")
+                })?;
+
+                Ok(CsvEntry {
+                    date,
+                    operation_type,
+                    amount,
+                    document_no: cell(row, document_no),
+                    operation_kind: cell(row, operation_kind),
+                    purpose: cell(row, purpose),
+                    debit_party: cell(row, debit_account).and_then(|c| Self::parse_party(&c)),
+                    credit_party: cell(row, credit_account).and_then(|c| Self::parse_party(&c)),
+                })
+            })
+            .collect();
+        Ok(entries)
+    }
+
     fn join_columns(columns: &mut [String], cells: &[&str]) {
         for (index, column) in columns.iter_mut().enumerate() {
             if !cells[index].is_empty() {
@@ -63,6 +297,7 @@ impl CSVFormat {
         let mut state = State::Before;
 
         let mut table: Vec<Vec<String>> = Vec::new();
+        let mut table_lines: Vec<usize> = Vec::new();
         let mut other_before: Vec<Vec<String>> = Vec::new();
         let mut other_after: Vec<Vec<String>> = Vec::new();
         let mut columns: Vec<String> = Vec::new();
@@ -70,6 +305,7 @@ impl CSVFormat {
 
         for rec in rdr.records().filter_map(Result::ok) {
             let cells: Vec<&str> = rec.iter().map(|s| s.trim()).collect();
+            let line = rec.position().map(|p| p.line() as usize).unwrap_or_default();
 
             match state {
                 State::Before => {
@@ -99,6 +335,7 @@ impl CSVFormat {
                         );
                     } else {
                         state = State::Data;
+                        table_lines.push(line);
                         table.push(
                             cells[column_position.0..column_position.1]
                                 .iter()
@@ -114,6 +351,7 @@ impl CSVFormat {
                         other_after.push(cells.iter().map(|s| s.to_string()).collect::<Vec<String>>());
                         state = State::After;
                     } else {
+                        table_lines.push(line);
                         table.push(
                             cells[column_position.0..column_position.1]
                                 .iter()
@@ -138,6 +376,7 @@ impl CSVFormat {
         Ok(Self {
             columns,
             table,
+            table_lines,
             other_before,
             other_after,
         })
@@ -194,48 +433,175 @@ impl CSVFormat {
 }
 
 impl TransactionsReader for CSVFormat {
-    fn collect_transactions(&self) -> Vec<Transaction> {
-        let mut transactions = Vec::new();
-        let mut index = HashMap::new();
-        for cell in self.columns.iter().enumerate() {
-            if cell.1 == "Дата проводки" {
-                index.insert("Дата проводки".to_string(), cell.0);
-            } else if cell.1 == "Сумма по дебету" {
-                index.insert("Сумма по дебету".to_string(), cell.0);
-            } else if cell.1 == "Сумма по кредиту" {
-                index.insert("Сумма по кредиту".to_string(), cell.0);
-            }
+    fn read_transactions(&self, mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+        let mut collected = CollectedTransactions::default();
+        for entry in self.entries()? {
+            let row = entry.map(|e| Transaction {
+                counterparty: e.counterparty().cloned(),
+                booking_date: Some(e.date),
+                description: e.purpose,
+                document_number: e.document_no,
+                bank_tx_code: e.operation_kind,
+                ..Transaction::new(e.amount, e.operation_type, e.date)
+            });
+            collected
+                .push(row, mode)
+                .map_err(|d| Self::data_format_error(&d.to_string()))?;
         }
+        Ok(collected)
+    }
+}
 
-        for row in &self.table {
-            let mut transaction = Transaction::default();
-
-            if let Ok(d) =
-                NaiveDate::parse_from_str(row[index["Дата проводки"]].as_str(), "%d.%m.%Y")
-            {
-                transaction.date = d;
+impl CSVFormat {
+    /// Выписка по таблице.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если в таблице нет колонки даты или суммы, а в режиме
+    /// [`ReadMode::Strict`] — также при первой строке, которую не удалось разобрать. В режиме
+    /// [`ReadMode::Lenient`] такие строки в выписку не попадают и возвращаются в `diagnostics`.
+    pub fn into_statements(self, mode: ReadMode) -> Result<CollectedStatements, FormatError> {
+        let mut entries = Vec::new();
+        let mut diagnostics = Vec::new();
+        for entry in self.entries()? {
+            match entry {
+                Ok(e) => entries.push(e),
+                Err(d) if mode == ReadMode::Strict => return Err(Self::data_format_error(&d.to_string())),
+                Err(d) => diagnostics.push(d),
             }
+        }
+        let first_date = entries.iter().map(|e| e.date).min();
+        let last_date = entries.iter().map(|e| e.date).max();
 
-            if row[index["Сумма по дебету"]].is_empty() {
-                transaction.operation_type = DebitOrCredit::Credit;
-                if let Ok(a) = row[index["Сумма по кредиту"]].replace(",", ".").parse()
-                {
-                    transaction.amount = a
-                }
-            } else {
-                transaction.operation_type = DebitOrCredit::Debit;
-                if let Ok(a) = row[index["Сумма по дебету"]].replace(",", ".").parse()
-                {
-                    transaction.amount = a
+        let with_date = |b: Option<Balance>, date: Option<NaiveDate>| {
+            b.map(|mut b| {
+                if b.date == NaiveDate::default() && let Some(d) = date {
+                    b.date = d;
                 }
+                b
+            })
+        };
+
+        let statement = Statement {
+            id: "NONREF".to_string(),
+            account: Account {
+                id: self.account_id().unwrap_or_default(),
+                currency: self.currency(),
+            },
+            statement_no: "1".to_string(),
+            opening_balance: with_date(self.opening_balance(), first_date),
+            closing_balance: with_date(self.closing_balance(), last_date),
+            entries: entries
+                .into_iter()
+                .map(|e| Entry {
+                    value_date: e.date,
+                    debit_credit_indicator: e.operation_type,
+                    amount: e.amount,
+                    transaction_type_code: match e.operation_kind.as_deref().map(|k| k.trim_start_matches('0')) {
+                        Some("1") => "TRF".to_string(),
+                        _ => "MSC".to_string(),
+                    },
+                    document_number: e.document_no.clone(),
+                    customer_ref: e
+                        .document_no
+                        .map(|n| n.chars().take(16).collect())
+                        .unwrap_or_else(|| "NONREF".to_string()),
+                    additional_info: e.purpose,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        Ok(CollectedStatements {
+            statements: Statements {
+                statements: vec![statement],
+            },
+            diagnostics,
+        })
+    }
+}
+
+/// Выписка по таблице в строгом режиме, см. [`CSVFormat::into_statements`].
+impl TryFrom<CSVFormat> for Statements {
+    type Error = FormatError;
+
+    fn try_from(value: CSVFormat) -> Result<Self, Self::Error> {
+        value.into_statements(ReadMode::Strict).map(|c| c.statements)
+    }
+}
+
+impl From<Statements> for CSVFormat {
+    fn from(value: Statements) -> Self {
+        let columns: Vec<String> = [DATE_COLUMN, DEBIT_COLUMN, CREDIT_COLUMN, DOCUMENT_COLUMN, PURPOSE_COLUMN]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+
+        let mut other_before = Vec::new();
+        let mut other_after = vec![vec![String::new()]];
+        let mut table = Vec::new();
+
+        let statements = value.statements;
+        if let Some(first) = statements.first() {
+            other_before.push(vec![ACCOUNT_LABEL.to_string(), first.account.id.clone()]);
+            other_before.push(vec![
+                CURRENCY_LABEL.to_string(),
+                first.currency().unwrap_or_default().to_string(),
+            ]);
+        }
+
+        for statement in &statements {
+            for entry in &statement.entries {
+                let amount = entry.amount.to_string();
+                let (debit, credit) = match entry.debit_credit_indicator {
+                    DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => (amount, String::new()),
+                    DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => (String::new(), amount),
+                };
+                let document_no = if entry.customer_ref == "NONREF" {
+                    String::new()
+                } else {
+                    entry.customer_ref.clone()
+                };
+                table.push(vec![
+                    entry.value_date.format("%d.%m.%Y").to_string(),
+                    debit,
+                    credit,
+                    document_no,
+                    entry.description().unwrap_or_default(),
+                ]);
             }
-            transactions.push(transaction);
         }
 
-        transactions
+        let balance_row = |label: &str, b: &Balance| {
+            let amount = b.amount.to_string();
+            let (debit, credit) = match b.debit_credit_indicator {
+                DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => (amount, "0.00".to_string()),
+                DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => ("0.00".to_string(), amount),
+            };
+            vec![label.to_string(), debit, credit, b.date.format("%d.%m.%Y").to_string()]
+        };
+        if let Some(b) = statements.first().and_then(|s| s.opening_balance.as_ref()) {
+            other_after.push(balance_row(OPENING_BALANCE_LABEL, b));
+        }
+        if let Some(b) = statements.last().and_then(|s| s.closing_balance.as_ref()) {
+            other_after.push(balance_row(CLOSING_BALANCE_LABEL, b));
+        }
+
+        Self {
+            columns,
+            table,
+            other_before,
+            other_after,
+            ..Default::default()
+        }
     }
 }
 
+impl From<MT940Format> for CSVFormat {
+    fn from(value: MT940Format) -> Self {
+        Statements::from(value).into()
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -283,7 +649,7 @@ mod tests {
         let mut cur = Cursor::new(data.as_bytes());
         let fmt = CSVFormat::from_read(&mut cur).expect("parse");
 
-        let txs = fmt.collect_transactions();
+        let txs = fmt.collect_transactions().unwrap();
         assert_eq!(txs.len(), 2);
 
         assert_eq!(txs[0].date, NaiveDate::from_ymd_opt(2026, 1, 20).unwrap());
@@ -295,6 +661,61 @@ mod tests {
         assert_ne!(txs[1].amount.to_string(), Transaction::default().amount.to_string());
     }
 
+    #[test]
+    fn bad_rows_fail_strict_and_are_reported_in_lenient_mode() {
+        let data = [
+            "Дата проводки,Сумма по дебету,Сумма по кредиту",
+            "20.01.2026,12x,",
+            "32.01.2026,,10.00",
+            "22.01.2026,,5.00",
+        ].join("\n");
+        let fmt = CSVFormat::from_read(&mut Cursor::new(data.as_bytes())).unwrap();
+
+        let err = fmt.read_transactions(ReadMode::Strict).unwrap_err();
+        assert_eq!(err, FormatError::DataFormatError(
+            "Ошибка разбора таблицы csv : строка 2, Сумма по дебету = \"12x\": не удалось разобрать сумму".to_string()
+        ));
+
+        let collected = fmt.read_transactions(ReadMode::Lenient).unwrap();
+        assert_eq!(collected.transactions.len(), 1);
+        assert_eq!(collected.transactions[0].amount, dec("5.00"));
+        assert_eq!(collected.diagnostics, vec![
+            RowDiagnostic::new(2, DEBIT_COLUMN, "12x", "не удалось разобрать сумму"),
+            RowDiagnostic::new(3, DATE_COLUMN, "32.01.2026", "не удалось разобрать дату"),
+        ]);
+    }
+
+    #[test]
+    fn missing_amount_columns_fail_in_any_mode() {
+        let data = "Дата проводки,Комментарий\n20.01.2026,1";
+        let fmt = CSVFormat::from_read(&mut Cursor::new(data.as_bytes())).unwrap();
+
+        assert!(fmt.read_transactions(ReadMode::Strict).is_err());
+        assert!(fmt.read_transactions(ReadMode::Lenient).is_err());
+    }
+
+    #[test]
+    fn statements_conversion_honours_read_mode() {
+        let data = [
+            "Дата проводки,Сумма по дебету,Сумма по кредиту",
+            "20.01.2026,12x,",
+            "22.01.2026,,5.00",
+        ].join("\n");
+        let parse = || CSVFormat::from_read(&mut Cursor::new(data.as_bytes())).unwrap();
+
+        assert!(Statements::try_from(parse()).is_err());
+
+        let collected = parse().into_statements(ReadMode::Lenient).unwrap();
+        assert_eq!(collected.statements.statements[0].entries.len(), 1);
+        assert_eq!(collected.statements.statements[0].entries[0].amount, dec("5.00"));
+        assert_eq!(collected.diagnostics, vec![
+            RowDiagnostic::new(2, DEBIT_COLUMN, "12x", "не удалось разобрать сумму"),
+        ]);
+
+        // без колонки даты выписка не строится ни в каком режиме
+        assert!(CSVFormat::default().into_statements(ReadMode::Lenient).is_err());
+    }
+
     #[test]
     fn write_roundtrip_keeps_structure() {
         let data = minimal_csv();
@@ -327,7 +748,7 @@ mod tests {
         let fmt = CSVFormat::from_read(&mut cur).expect("parse");
 
         // Конвертация в Transaction
-        let txs = fmt.collect_transactions();
+        let txs = fmt.collect_transactions().unwrap();
 
         assert_eq!(txs.len(), 2);
 
@@ -342,4 +763,86 @@ mod tests {
         assert_eq!(txs[1].amount, dec("10.00"));
     }
 
+    #[test]
+    fn collect_transactions_fills_counterparty_and_purpose() {
+        let mut file = std::fs::File::open("examples/data/csv.exmpl").unwrap();
+        let fmt = CSVFormat::from_read(&mut file).unwrap();
+        let txs = fmt.collect_transactions().unwrap();
+
+        let first = &txs[0];
+        assert_eq!(first.operation_type, DebitOrCredit::Debit);
+        assert_eq!(first.document_number.as_deref(), Some("1"));
+        assert_eq!(first.bank_tx_code.as_deref(), Some("1"));
+        assert_eq!(first.booking_date, NaiveDate::from_ymd_opt(2024, 2, 20));
+        assert!(first.description.as_deref().unwrap().starts_with("Оплата по СЧЁТ"));
+
+        let counterparty = first.counterparty.as_ref().unwrap();
+        assert_eq!(counterparty.account.as_deref(), Some("40702810600014448120"));
+        assert_eq!(counterparty.inn.as_deref(), Some("7733573894"));
+        assert_eq!(counterparty.name.as_deref(), Some("АО \"РСИЦ\""));
+
+        let credit = txs.iter().find(|t| t.operation_type == DebitOrCredit::Credit).unwrap();
+        assert_ne!(
+            credit.counterparty.as_ref().unwrap().account.as_deref(),
+            Some("40702810440000030888")
+        );
+    }
+
+    fn bank_csv() -> String {
+        [
+            "ВЫПИСКА ОПЕРАЦИЙ ПО ЛИЦЕВОМУ СЧЕТУ,40702810440000030888",
+            "Российский рубль,",
+            "Дата проводки,Сумма по дебету,Сумма по кредиту,№ документа,ВО,Назначение платежа",
+            "20.02.2024,1540.00,,1,01,Оплата по счёту",
+            "21.02.2024,,10000.00,616174,01,Предоставление займа",
+            ",,,,,",
+            "Входящий остаток,\"0,00\",1332.54,(П),01 января 2024 г.",
+            "Исходящий остаток,\"0,00\",9792.54,(П),31 декабря 2024 г.",
+        ].join("\n")
+    }
+
+    #[test]
+    fn csv_prologue_and_epilogue_give_account_and_balances() {
+        let fmt = CSVFormat::from_read(&mut Cursor::new(bank_csv().as_bytes())).expect("parse");
+
+        assert_eq!(fmt.account_id().as_deref(), Some("40702810440000030888"));
+        assert_eq!(fmt.currency().as_deref(), Some("RUB"));
+
+        let opening = fmt.opening_balance().unwrap();
+        assert_eq!(opening.debit_credit_indicator, DebitOrCredit::Credit);
+        assert_eq!(opening.amount, dec("1332.54"));
+        assert_eq!(opening.date, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(opening.currency, "RUB");
+
+        let closing = fmt.closing_balance().unwrap();
+        assert_eq!(closing.amount, dec("9792.54"));
+        assert_eq!(closing.date, NaiveDate::from_ymd_opt(2024, 12, 31).unwrap());
+
+        let entries: Vec<CsvEntry> = fmt.entries().unwrap().into_iter().map(Result::unwrap).collect();
+        assert_eq!(entries[0].document_no.as_deref(), Some("1"));
+        assert_eq!(entries[1].purpose.as_deref(), Some("Предоставление займа"));
+    }
+
+    #[test]
+    fn csv_to_mt940_and_back() {
+        let fmt = CSVFormat::from_read(&mut Cursor::new(bank_csv().as_bytes())).expect("parse");
+        let mt = MT940Format::try_from(fmt).unwrap();
+
+        let mut csv: CSVFormat = mt.into();
+        let txs = csv.collect_transactions().unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].amount, dec("1540.00"));
+        assert_eq!(txs[0].operation_type, DebitOrCredit::Debit);
+        assert_eq!(txs[1].amount, dec("10000.00"));
+        assert_eq!(txs[1].operation_type, DebitOrCredit::Credit);
+        assert_eq!(csv.account_id().as_deref(), Some("40702810440000030888"));
+        assert_eq!(csv.closing_balance().unwrap().amount, dec("9792.54"));
+
+        let mut out: Vec<u8> = Vec::new();
+        csv.write_to(&mut out).expect("write");
+        let reread = CSVFormat::from_read(&mut Cursor::new(out)).expect("parse written");
+        assert_eq!(reread.collect_transactions().unwrap().len(), 2);
+        assert_eq!(reread.opening_balance().unwrap().amount, dec("1332.54"));
+    }
+
 }
\ No newline at end of file
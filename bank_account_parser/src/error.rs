@@ -1,6 +1,8 @@
 use std::fmt;
 use std::fmt::Formatter;
 
+// имена вариантов — часть прежнего API
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Eq, PartialEq)]
 pub enum FormatError {
     DataFormatError(String),
@@ -23,6 +25,8 @@ impl fmt::Display for FormatError {
     }
 }
 
+impl std::error::Error for FormatError {}
+
 impl From<std::io::Error> for FormatError {
     fn from(error: std::io::Error) -> Self {
         FormatError::ReadWriteError(format!("Ошибка чтения/записии, {}", error))
@@ -0,0 +1,261 @@
+use crate::camt053_stream::Camt053Reader;
+use crate::csv_format::CSVFormat;
+use crate::error::FormatError;
+use crate::mt940_format::MT940Format;
+use crate::transactions_holder::{ReadMode, TransactionHolder};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Сколько байт из начала файла просматривается при определении формата.
+const DETECT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Поддерживаемые форматы выписок.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementFormat {
+    Mt940,
+    Camt052,
+    Camt053,
+    Camt054,
+    Csv,
+}
+
+/// Определить формат выписки по началу её содержимого.
+///
+/// Распознаются:
+/// - CAMT.053 — XML с корневым элементом `BkToCstmrStmt` (с обёрткой `Document` или без неё,
+///   в том числе с префиксом пространства имён), а также фрагменты, начинающиеся сразу с `Stmt`;
+///   объявление XML, комментарии и инструкции обработки перед корнем пропускаются;
+/// - camt.052 — так же по `BkToCstmrAcctRpt` или `Rpt`, camt.054 — по `BkToCstmrDbtCdtNtfctn` или `Ntfctn`;
+/// - MT940 — заголовочные блоки SWIFT `{1:}`..`{3:}`, за которыми идёт блок `{4:`, или первое
+///   поле сообщения `:20:` (перед ним могут быть строки заголовка банка);
+/// - CSV выгрузка банка — строка заголовка с колонкой `Дата проводки`.
+///
+/// Возвращает `None`, если формат определить не удалось.
+pub fn detect_format(buf: &[u8]) -> Option<StatementFormat> {
+    let text = String::from_utf8_lossy(buf);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+
+    if text.starts_with('<') {
+        return detect_camt(text);
+    }
+
+    let first_field = text.lines().map(str::trim_start).find(|l| l.starts_with(':'));
+    if starts_with_block4(text) || first_field.is_some_and(|l| l.starts_with(":20:")) {
+        return Some(StatementFormat::Mt940);
+    }
+
+    if text.contains("Дата проводки") {
+        return Some(StatementFormat::Csv);
+    }
+
+    None
+}
+
+/// Сообщение CAMT по корневому элементу, а у обёртки `Document` — по её первому дочернему.
+fn detect_camt(text: &str) -> Option<StatementFormat> {
+    let mut reader = Reader::from_str(text);
+    let mut in_document = false;
+    loop {
+        match reader.read_event().ok()? {
+            Event::Decl(_) | Event::PI(_) | Event::Comment(_) | Event::DocType(_) => (),
+            Event::Text(t) if t.iter().all(u8::is_ascii_whitespace) => (),
+            Event::Start(e) if !in_document && e.local_name().as_ref() == b"Document" => in_document = true,
+            Event::Start(e) | Event::Empty(e) => {
+                return match e.local_name().as_ref() {
+                    b"BkToCstmrStmt" | b"Stmt" => Some(StatementFormat::Camt053),
+                    b"BkToCstmrAcctRpt" | b"Rpt" => Some(StatementFormat::Camt052),
+                    b"BkToCstmrDbtCdtNtfctn" | b"Ntfctn" => Some(StatementFormat::Camt054),
+                    _ => None,
+                };
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Начинается ли текст с блоков SWIFT `{1:}`, `{2:}`, `{3:}` (любых из них), за которыми
+/// следует блок `{4:`. Вложенные скобки блока 3 (`{108:...}`) учитываются.
+fn starts_with_block4(text: &str) -> bool {
+    let mut rest = text;
+    loop {
+        let Some(block) = rest.strip_prefix('{') else {
+            return false;
+        };
+        let Some((id, _)) = block.split_once(':').filter(|(id, _)| matches!(*id, "1" | "2" | "3" | "4")) else {
+            return false;
+        };
+        if id == "4" {
+            return true;
+        }
+        let mut depth = 0;
+        let Some(end) = rest.find(|c| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => (),
+            }
+            depth == 0
+        }) else {
+            return false;
+        };
+        rest = rest[end + 1..].trim_start();
+    }
+}
+
+fn open_buffered(path: &Path) -> Result<BufReader<File>, FormatError> {
+    let file = File::open(path).map_err(|e| {
+        FormatError::ReadWriteError(format!("Не удалось открыть файл {}. {}", path.display(), e))
+    })?;
+    Ok(BufReader::with_capacity(DETECT_BUFFER_SIZE, file))
+}
+
+fn detect_buffered<R: std::io::Read>(reader: &mut BufReader<R>, path: &Path) -> Result<StatementFormat, FormatError> {
+    let buf = reader.fill_buf()?;
+    detect_format(buf).ok_or_else(|| {
+        FormatError::UnknownValueFormat(format!(
+            "Не удалось определить формат файла {}",
+            path.display()
+        ))
+    })
+}
+
+/// Определить формат файла по его содержимому (см. [`detect_format`]).
+pub fn detect_file_format<P: AsRef<Path>>(path: P) -> Result<StatementFormat, FormatError> {
+    let path = path.as_ref();
+    let mut reader = open_buffered(path)?;
+    detect_buffered(&mut reader, path)
+}
+
+/// Открыть выписку любого поддерживаемого формата и собрать из неё транзакции
+/// в строгом режиме.
+///
+/// # Ошибки
+/// Возвращает [`FormatError`], если файл не удалось открыть, формат не распознан
+/// или содержимое не разбирается соответствующим парсером.
+pub fn open_any<P: AsRef<Path>>(path: P) -> Result<TransactionHolder, FormatError> {
+    open_any_with_mode(path, ReadMode::Strict)
+}
+
+/// То же, что [`open_any`], с выбором режима обработки ошибочных строк.
+pub fn open_any_with_mode<P: AsRef<Path>>(path: P, mode: ReadMode) -> Result<TransactionHolder, FormatError> {
+    open_any_with(
+        path,
+        &OpenOptions {
+            mode,
+            ..Default::default()
+        },
+    )
+}
+
+/// Параметры чтения выписки в [`open_any_with`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OpenOptions {
+    /// Режим обработки ошибочных строк.
+    pub mode: ReadMode,
+    /// Разворачивать пакетные проводки CAMT по `TxDtls`,
+    /// см. [`crate::camt053_format::Camt053Format::set_expand_batches`].
+    pub expand_batches: bool,
+}
+
+/// То же, что [`open_any`], с параметрами чтения `options`.
+pub fn open_any_with<P: AsRef<Path>>(path: P, options: &OpenOptions) -> Result<TransactionHolder, FormatError> {
+    let path = path.as_ref();
+    let mode = options.mode;
+    let mut reader = open_buffered(path)?;
+    match detect_buffered(&mut reader, path)? {
+        StatementFormat::Mt940 => TransactionHolder::with_mode(MT940Format::from_read(&mut reader)?, mode),
+        // отчёты camt.052 и уведомления camt.054 читаются тем же потоковым разбором, что и выписки
+        StatementFormat::Camt052 | StatementFormat::Camt053 | StatementFormat::Camt054 => {
+            let mut camt = Camt053Reader::new(reader);
+            camt.set_expand_batches(options.expand_batches);
+            camt.into_transactions(mode).map(TransactionHolder::from)
+        }
+        StatementFormat::Csv => TransactionHolder::with_mode(CSVFormat::from_read(&mut reader)?, mode),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_camt053_with_and_without_document() {
+        let bare = br#"<BkToCstmrStmt><GrpHdr/></BkToCstmrStmt>"#;
+        assert_eq!(detect_format(bare), Some(StatementFormat::Camt053));
+
+        let wrapped = "\u{feff}<?xml version=\"1.0\"?>\n<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:camt.053.001.02\"><BkToCstmrStmt>";
+        assert_eq!(detect_format(wrapped.as_bytes()), Some(StatementFormat::Camt053));
+
+        let prefixed = br#"<ns2:Document><ns2:BkToCstmrStmt>"#;
+        assert_eq!(detect_format(prefixed), Some(StatementFormat::Camt053));
+
+        assert_eq!(detect_format(b"<Stmt><Ntry></Ntry></Stmt>"), Some(StatementFormat::Camt053));
+        assert_eq!(detect_format(b"<Document><Other/></Document>"), None);
+    }
+
+    #[test]
+    fn detects_camt052() {
+        let wrapped = br#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.052.001.08"><BkToCstmrAcctRpt>"#;
+        assert_eq!(detect_format(wrapped), Some(StatementFormat::Camt052));
+        assert_eq!(detect_format(b"<ns:Rpt><Id>1</Id></ns:Rpt>"), Some(StatementFormat::Camt052));
+        assert_eq!(detect_format(b"<Document><RptgSrc/></Document>"), None);
+        assert_eq!(open_any("examples/data/camt052.exmpl").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn detects_camt054() {
+        let wrapped = br#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.054.001.02"><BkToCstmrDbtCdtNtfctn>"#;
+        assert_eq!(detect_format(wrapped), Some(StatementFormat::Camt054));
+        assert_eq!(detect_format(b"<Ntfctn><Id>1</Id></Ntfctn>"), Some(StatementFormat::Camt054));
+        assert_eq!(open_any("examples/data/camt054.exmpl").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn detects_camt_by_root_element_only() {
+        let prolog = br#"<?xml version="1.0"?>
+            <!-- <Stmt> in a comment -->
+            <?app note="<Rpt>"?>
+            <Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.054.001.02">
+                <!-- <BkToCstmrStmt> -->
+                <BkToCstmrDbtCdtNtfctn><GrpHdr><AddtlInf>Stmt Rpt</AddtlInf></GrpHdr>"#;
+        assert_eq!(detect_format(prolog), Some(StatementFormat::Camt054));
+
+        // упоминание тега глубже корня не делает документ выпиской
+        let other = br#"<Document><pain.001><Note>&lt;Stmt&gt;</Note><ns:Stmt/></pain.001></Document>"#;
+        assert_eq!(detect_format(other), None);
+        assert_eq!(detect_format(b"<Invoice><Stmt/></Invoice>"), None);
+    }
+
+    #[test]
+    fn detects_mt940_and_csv() {
+        assert_eq!(detect_format(b"{1:F01}{2:I940}{4:\n:20:REF\n-}"), Some(StatementFormat::Mt940));
+        assert_eq!(
+            detect_format(b"{1:F01X}{2:O940X}{3:{108:MUR}}\n{4:\n:20:REF\n-}"),
+            Some(StatementFormat::Mt940)
+        );
+        assert_eq!(detect_format(b":20:REF\n:25:ACC\n"), Some(StatementFormat::Mt940));
+        assert_eq!(detect_format(b"ABNANL2A\n940\n:20:REF\n:25:ACC\n"), Some(StatementFormat::Mt940));
+        // блок 4 и поле :20: не в начале сообщения
+        assert_eq!(detect_format(b"note {4: here\n"), None);
+        assert_eq!(detect_format(b":25:ACC\n:20:REF\n"), None);
+        assert_eq!(
+            detect_format("Выписка\n,Дата проводки,Сумма по дебету\n".as_bytes()),
+            Some(StatementFormat::Csv)
+        );
+        assert_eq!(detect_format(b"hello"), None);
+    }
+
+    #[test]
+    fn open_any_reads_examples() {
+        let holder = open_any("examples/data/mt940.exmpl").unwrap();
+        assert_eq!(holder.len(), 1);
+        let holder = open_any("examples/data/camt053.exmpl").unwrap();
+        assert_eq!(holder.len(), 1);
+        assert!(!open_any("examples/data/csv.exmpl").unwrap().is_empty());
+        assert!(open_any("examples/data/missing.exmpl").is_err());
+    }
+}
@@ -1,7 +1,20 @@
+pub mod bank_tx_code;
+pub mod camt052_format;
 pub mod camt053_format;
-mod camt053_iterator;
+pub mod camt054_format;
+pub mod camt053_model;
+pub mod camt053_query;
+pub mod camt053_schema;
+pub mod camt053_stream;
+pub mod camt053_tree;
+pub mod camt053_iterator;
 mod common;
 pub mod csv_format;
+pub mod format_detection;
 pub mod mt940_format;
+pub mod mt942_format;
+pub mod reconcile;
+pub mod statement;
 pub mod transactions_holder;
+pub mod validation;
 mod error;
@@ -3,13 +3,17 @@ use mt940::{Field, ParseError, parse_fields};
 use regex::Regex;
 use rust_decimal::Decimal;
 use std::io::{BufRead, BufReader, Read, Write};
-use std::mem;
 use std::str::FromStr;
 use rust_decimal::prelude::Zero;
+use crate::bank_tx_code::BankTxCodeMap;
 use crate::camt053_format::Camt053Format;
+use crate::csv_format::CSVFormat;
 use crate::error::{FormatError, GeneratorFormatError};
 use crate::common::debit_credit::DebitOrCredit;
-use crate::transactions_holder::{Transaction, TransactionsReader};
+use crate::statement::{self, Account, Entry, Statement, Statements};
+use crate::transactions_holder::{
+    CollectedTransactions, Counterparty, EntryStatus, ReadMode, Transaction, TransactionsReader,
+};
 
 impl From<ParseError> for FormatError {
     fn from(error: ParseError) -> Self {
@@ -500,7 +504,7 @@ impl MT940Format {
         })
     }
 
-    fn write_message<W: Write>(writer: &mut W, tag: &str, value: &str, first: &mut bool) -> Result<(), FormatError> {
+    pub(crate) fn write_message<W: Write>(writer: &mut W, tag: &str, value: &str, first: &mut bool) -> Result<(), FormatError> {
         if *first {
             writer.write_all("{4:\n".as_bytes())?;
             *first = false;
@@ -528,7 +532,7 @@ impl MT940Format {
         Ok(())
     }
 
-    fn write_statement<W: Write>(writer: &mut W, statement: &StatementLine, first: &mut bool) -> Result<(), FormatError> {
+    pub(crate) fn write_statement<W: Write>(writer: &mut W, statement: &StatementLine, first: &mut bool) -> Result<(), FormatError> {
         let mut result = String::new();
         result += &statement.value_date.format("%y%m%d").to_string();
         if let Some(d) = statement.entry_date.as_ref() {
@@ -623,22 +627,6 @@ impl MT940Format {
 
         Ok(())
     }
-
-    fn get_avalbal<'a>(name: &String, message: &'a mut Message) -> Option<&'a mut AvailableBalance>{
-        match name.as_str() {
-            "CLBD" => Some(&mut message.closing_balance.balance),
-            "CLAV" => {
-                message.closing_available_balance = Some(AvailableBalance::default());
-                message.closing_available_balance.as_mut()
-            },
-            "FWAV" => {
-                message.forward_available_balance = Some(AvailableBalance::default());
-                message.forward_available_balance.as_mut()
-            },
-            "OPBD" | _ => Some(&mut message.opening_balance.balance),
-        }
-    }
-
 }
 
 impl<'a> IntoIterator for &'a MT940Format {
@@ -650,198 +638,432 @@ impl<'a> IntoIterator for &'a MT940Format {
     }
 }
 
-impl From<Camt053Format> for MT940Format {
-    fn from(value: Camt053Format) -> Self {
+impl From<&statement::Balance> for Balance {
+    fn from(value: &statement::Balance) -> Self {
+        Self {
+            is_intermediate: value.is_intermediate,
+            balance: value.into(),
+        }
+    }
+}
+
+impl From<&statement::Balance> for AvailableBalance {
+    fn from(value: &statement::Balance) -> Self {
+        Self {
+            debit_credit_indicator: value.debit_credit_indicator,
+            date: value.date,
+            iso_currency_code: value.currency.clone(),
+            amount: value.amount,
+        }
+    }
+}
+
+impl From<&AvailableBalance> for statement::Balance {
+    fn from(value: &AvailableBalance) -> Self {
+        Self {
+            debit_credit_indicator: value.debit_credit_indicator,
+            date: value.date,
+            currency: value.iso_currency_code.clone(),
+            amount: value.amount,
+            is_intermediate: false,
+        }
+    }
+}
 
-        let mut result = Vec::new();
-        let mut message = Message::default();
-        let mut base_orgn_msg_id = String::new();
+impl From<&Balance> for statement::Balance {
+    fn from(value: &Balance) -> Self {
+        Self {
+            is_intermediate: value.is_intermediate,
+            ..(&value.balance).into()
+        }
+    }
+}
 
-        let mut balance = Balance::default();
-        let mut balance_name = String::new();
+/// Поле `:86:` проводки со структурированными кодами, как в выписках Goldman Sachs:
+/// `/DRNM/`, `/DACT/`, `/DBIC/` — плательщик, `/CRNM/`, `/CACT/`, `/CBIC/` — получатель,
+/// `/REMI/` — назначение платежа, `/CREF/` — референс получателя, `/ADDI/` — прочая информация.
+///
+/// Каждый код пишется с новой строки, строки переносятся по 65 символов. Поле без кодов
+/// в начале — свободный текст, он целиком попадает в `additional_info`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OwnerInformation {
+    pub debtor: Counterparty,
+    pub creditor: Counterparty,
+    pub remittance_info: Option<String>,
+    pub creditor_ref: Option<String>,
+    /// Свободный текст, `/ADDI/` и коды, которые здесь не разбираются (`/EREF/` и т.п.).
+    pub additional_info: Option<String>,
+}
 
-        let mut statement: Option<StatementLine> = None;
+impl OwnerInformation {
+    /// Максимальная длина строки поля `:86:`.
+    const LINE_LENGTH: usize = 65;
+
+    /// Коды, которые разделяют значения поля и внутри строки; прочие коды `/XXXX/`
+    /// (`/OPRP/`, `/DCID/`...) разделяют значения, только если начинают строку.
+    const CODES: [&'static str; 12] = [
+        "DRNM", "DACT", "DBIC", "CRNM", "CACT", "CBIC", "REMI", "CREF", "ADDI", "EREF", "MREF", "PREF",
+    ];
+
+    /// Склеить строки поля: перенос внутри значения, обрезанного на [`Self::LINE_LENGTH`]
+    /// символах, убирается, короткая строка без кода в начале продолжается через пробел.
+    /// Вместе с текстом возвращаются позиции строк, начинающихся с кода.
+    fn join_lines(text: &str) -> (String, Vec<usize>) {
+        let mut joined = String::new();
+        let mut line_codes = Vec::new();
+        let mut previous_full = false;
+        for line in text.lines() {
+            let starts_code = !previous_full && Self::code_at(line, 0, true).is_some();
+            if starts_code {
+                line_codes.push(joined.len());
+            } else if !joined.is_empty() && !previous_full {
+                joined.push(' ');
+            }
+            joined.push_str(line);
+            previous_full = line.chars().count() >= Self::LINE_LENGTH;
+        }
+        (joined, line_codes)
+    }
+
+    /// Код `/XXXX/` в позиции `i`: из [`Self::CODES`] или, при `any`, любой из четырёх заглавных букв.
+    fn code_at(text: &str, i: usize, any: bool) -> Option<&str> {
+        let code = text.get(i..)?.strip_prefix('/')?.get(..5)?.strip_suffix('/')?;
+        let known = Self::CODES.contains(&code);
+        (known || any && code.bytes().all(|b| b.is_ascii_uppercase())).then_some(code)
+    }
 
-        for tag in value.get_iter() {
-            let path = tag.path();
-            let Some(s) = path.find("/Stmt") else {
-                if tag.path().as_str() == "/BkToCstmrStmt/GrpHdr/OrgnlBizQry/MsgId" {
-                    base_orgn_msg_id = tag.text()
-                };
+    /// Разобрать значение поля `:86:`.
+    pub fn parse(text: &str) -> Self {
+        let (joined, line_codes) = Self::join_lines(text);
+        let mut codes: Vec<(usize, &str)> = Vec::new();
+        for (i, _) in joined.match_indices('/') {
+            // код внутри кода предыдущего значения (`/REMI/ADDI/`) не считается
+            if codes.last().is_some_and(|&(last, _)| i < last + 6) {
                 continue;
+            }
+            if let Some(code) = Self::code_at(&joined, i, line_codes.contains(&i)) {
+                codes.push((i, code));
+            }
+        }
+        if codes.first().is_none_or(|&(i, _)| i != 0) {
+            return Self {
+                additional_info: Some(text.to_string()),
+                ..Default::default()
             };
+        }
 
-            match &path[s..] {
-                "/Stmt" => {
-                    if !message.transaction_ref_no.is_empty() {
-                        if !base_orgn_msg_id.is_empty() {
-                            message.ref_to_related_msg = Some(base_orgn_msg_id.clone());
-                        }
-                        result.push(mem::take(&mut message));
-                    }
-                    else if !base_orgn_msg_id.is_empty() {
-                        message.ref_to_related_msg = Some(base_orgn_msg_id.clone());
-                    }
-                }
-                "/Stmt/Id" => message.transaction_ref_no = tag.text(),
-                "/Stmt/Acct/Id/IBAN" | "Stmt/Acct/Id/Othr/Id" => message.account_id = tag.text(),
-                "/Stmt/ElctrncSeqNb" => message.statement_no = tag.text(),
-                "/Stmt/LglSeqNb" => {
-                    if !tag.text().is_empty() {
-                        message.sequence_no = Some(tag.text());
-                    }
-                }
-                "/Stmt/AddtlStmtInf" => {
-                    if !tag.text().is_empty() {
-                        message.information_to_account_owner = Some(tag.text());
-                    }
-                }
-                "/Stmt/Bal" => {
-                    if !balance_name.is_empty() && let Some(a) = Self::get_avalbal(&balance_name, &mut message) {
-                        a.merge(&balance.balance);
-                    }
-                    balance = Balance::default();
-                    balance_name.clear();
-                }
-                "/Stmt/Bal/Tp/CdOrPrtry/Cd" => {
-                    balance_name = tag.text();
-                }
-                "/Stmt/Bal/CdtDbtInd" => {
-
-                    let _type = match tag.text().as_str() {
-                        "DBIT" => DebitOrCredit::Debit,
-                        "CRDT" => DebitOrCredit::Credit,
-                        _ => DebitOrCredit::Debit,
-                    };
-                    balance.balance.debit_credit_indicator = _type;
-                },
-                "/Stmt/Bal/Dt/Dt" => {
-                    if let Ok(d) = NaiveDate::parse_from_str(tag.text().as_str(), "%Y-%m-%d") {
-                        balance.balance.date = d;
-                    }
-                }
-                "/Stmt/Bal/Amt" => {
-                    if let Ok(amount) = tag.text().replace(",", ".").parse() {
-                        balance.balance.amount = amount;
-                    }
-                    if let Some(curr) = tag.get_attr("Ccy") {
-                        balance.balance.iso_currency_code = curr;
-                    }
-                }
-                "/Stmt/Ntry" => {
-                    if let Some(c) = &mut statement {
-                        message.statement_lines.push(mem::take(c));
-                    }
-                    else {
-                        statement = Some(StatementLine::default());
-                    }
-                }
-                "/Stmt/Ntry/ValDt/Dt" => {
-                    if let Some(st) = &mut statement
-                        && let Ok(d) = NaiveDate::parse_from_str(tag.text().as_str(), "%Y-%m-%d")
-                    {
-                        st.value_date = d;
-                    }
-                }
-                "/Stmt/Ntry/BookgDt/Dt" => {
-                    if let Some(st) = &mut statement
-                        && let Ok(d) = NaiveDate::parse_from_str(tag.text().as_str(), "%Y-%m-%d")
-                    {
-                        st.entry_date = Some(d);
-                    }
-                }
-                "/Stmt/Ntry/CdtDbtInd" => {
-                    if let Some(st) = &mut statement {
-                        match tag.text().as_str() {
-                            "DBIT" => st.ext_debit_credit_indicator = DebitOrCredit::Debit,
-                            "CRDT" => st.ext_debit_credit_indicator = DebitOrCredit::Credit,
-                            _ => st.ext_debit_credit_indicator = DebitOrCredit::Debit,
-                        }
-                    }
-                }
-                "/Stmt/Ntry/Amt" => {
-                    if let Some(st) = &mut statement
-                        && let Ok(amount) = tag.text().replace(",", ".").parse()
-                    {
-                        st.amount = amount;
-                        st.funds_code = tag.get_attr("Ccy");
-                    }
-                }
-                "/Stmt/Ntry/BkTxCd/Prtry/Issr" => {
-                    if let Some(st) = &mut statement {
-                        st.transaction_type_ident_code = tag.text();
-                    }
-                }
-                "/Stmt/Ntry/NtryDtls/TxDtls/Refs/EndToEndId"
-                | "/Stmt/Ntry/NtryDtls/TxDtls/Refs/MndtId"
-                | "/Stmt/Ntry/NtryDtls/TxDtls/Refs/InstrId"
-                | "/Stmt/Ntry/NtryDtls/TxDtls/Refs/PmtInfId" => {
-                    if let Some(st) = &mut statement {
-                        st.customer_ref = tag.text();
-                    }
-                }
-                "/Stmt/Ntry/NtryDtls/TxDtls/Refs/AcctSvcrRef"
-                | "/Stmt/Ntry/NtryDtls/TxDtls/Refs/TxId" => {
-                    if let Some(st) = &mut statement {
-                        st.bank_ref = Some(tag.text());
-                    }
-                }
-                "/Stmt/Ntry/AddtlTxInf" => {
-                    if let Some(st) = &mut statement {
-                        st.supplementary_details = Some(tag.text());
-                    }
-                }
-                "/Stmt/Ntry/NtryDtls/TxDtls/AddtlTxInf" => {
-                    if let Some(st) = &mut statement {
-                        if let Some(_exists) = &mut st.information_to_account_owner {
-                            _exists.push(' ');
-                            _exists.push_str(tag.text().as_str());
-                        } else {
-                            st.information_to_account_owner = Some(tag.text());
-                        }
-                    }
+        let mut info = Self::default();
+        let mut other = Vec::new();
+        for (n, &(start, code)) in codes.iter().enumerate() {
+            let end = codes.get(n + 1).map_or(joined.len(), |&(next, _)| next);
+            let Some(value) = joined.get(start + 6..end).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            let value = value.to_string();
+            match code {
+                "DRNM" => info.debtor.name = Some(value),
+                "DACT" => info.debtor.account = Some(value),
+                "DBIC" => info.debtor.bic = Some(value),
+                "CRNM" => info.creditor.name = Some(value),
+                "CACT" => info.creditor.account = Some(value),
+                "CBIC" => info.creditor.bic = Some(value),
+                "REMI" => info.remittance_info = Some(value),
+                "CREF" => info.creditor_ref = Some(value),
+                "ADDI" => other.push(value),
+                _ => other.push(format!("/{code}/{value}")),
+            }
+        }
+        info.additional_info = (!other.is_empty()).then(|| other.join(" "));
+        info
+    }
+
+    /// Значение поля `:86:`; без контрагентов, назначения и референса — просто `additional_info`.
+    pub fn to_field(&self) -> Option<String> {
+        let coded = [
+            ("DRNM", &self.debtor.name),
+            ("DACT", &self.debtor.account),
+            ("DBIC", &self.debtor.bic),
+            ("CRNM", &self.creditor.name),
+            ("CACT", &self.creditor.account),
+            ("CBIC", &self.creditor.bic),
+            ("REMI", &self.remittance_info),
+            ("CREF", &self.creditor_ref),
+        ];
+        if coded.iter().all(|(_, v)| v.is_none()) {
+            return self.additional_info.clone();
+        }
+
+        let mut lines = Vec::new();
+        let coded = coded.into_iter().chain([("ADDI", &self.additional_info)]);
+        for (code, value) in coded.filter_map(|(c, v)| Some((c, v.as_ref()?))) {
+            let mut line = format!("/{code}/");
+            for c in value.chars().map(|c| if c == '\n' || c == '\r' { ' ' } else { c }) {
+                if line.chars().count() == Self::LINE_LENGTH {
+                    lines.push(std::mem::take(&mut line));
                 }
-                _ => continue,
+                line.push(c);
+            }
+            lines.push(line);
+        }
+        Some(lines.join("\n"))
+    }
+
+    /// Контрагент проводки с направлением `direction`: плательщик для поступлений, получатель для списаний.
+    pub fn counterparty(&self, direction: DebitOrCredit) -> Option<Counterparty> {
+        let party = match direction {
+            DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => &self.debtor,
+            DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => &self.creditor,
+        };
+        (!party.is_empty()).then(|| party.clone())
+    }
+}
+
+/// Контрагент проводки пишется плательщиком для поступлений и получателем для списаний.
+impl From<&Entry> for OwnerInformation {
+    fn from(entry: &Entry) -> Self {
+        let mut info = Self {
+            remittance_info: entry.remittance_info.clone(),
+            creditor_ref: entry.creditor_ref.clone(),
+            additional_info: entry.additional_info.clone(),
+            ..Default::default()
+        };
+        if let Some(party) = entry.counterparty.clone() {
+            match entry.debit_credit_indicator {
+                DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => info.debtor = party,
+                DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => info.creditor = party,
             }
         }
-        if !balance_name.is_empty() && let Some(a) = Self::get_avalbal(&balance_name, &mut message) {
-            a.merge(&balance.balance);
+        info
+    }
+}
+
+impl From<Message> for Statement {
+    fn from(value: Message) -> Self {
+        let currency = value.opening_balance.balance.iso_currency_code.clone();
+        let balance = |b: &Balance| (*b != Balance::default()).then(|| b.into());
+
+        Self {
+            opening_balance: balance(&value.opening_balance),
+            closing_balance: balance(&value.closing_balance),
+            closing_available_balance: value.closing_available_balance.as_ref().map(Into::into),
+            forward_available_balance: value.forward_available_balance.as_ref().map(Into::into),
+            id: value.transaction_ref_no,
+            related_ref: value.ref_to_related_msg,
+            account: Account {
+                id: value.account_id,
+                currency: (!currency.is_empty()).then_some(currency),
+            },
+            statement_no: value.statement_no,
+            sequence_no: value.sequence_no,
+            created: None,
+            entries: value
+                .statement_lines
+                .into_iter()
+                .map(|line| {
+                    let info = line
+                        .information_to_account_owner
+                        .as_deref()
+                        .map(OwnerInformation::parse)
+                        .unwrap_or_default();
+                    Entry {
+                        value_date: line.value_date,
+                        booking_date: line.entry_date,
+                        debit_credit_indicator: line.ext_debit_credit_indicator,
+                        status: EntryStatus::Booked,
+                        amount: line.amount,
+                        currency: None,
+                        funds_code: line.funds_code,
+                        transaction_type_code: line.transaction_type_ident_code,
+                        domain_code: None,
+                        customer_ref: line.customer_ref,
+                        bank_ref: line.bank_ref,
+                        document_number: None,
+                        supplementary_details: line.supplementary_details,
+                        counterparty: info.counterparty(line.ext_debit_credit_indicator),
+                        remittance_info: info.remittance_info,
+                        creditor_ref: info.creditor_ref,
+                        additional_info: info.additional_info,
+                        batch: None,
+                    }
+                })
+                .collect(),
+            additional_info: value.information_to_account_owner,
+        }
+    }
+}
+
+impl From<Statement> for Message {
+    fn from(value: Statement) -> Self {
+        Self::from_statement(value, &BankTxCodeMap::default())
+    }
+}
+
+impl Message {
+    /// Сообщение по выписке; коды SWIFT проводок без них берутся из таблицы `codes`.
+    fn from_statement(value: Statement, codes: &BankTxCodeMap) -> Self {
+        let currency = value.currency().unwrap_or_default().to_string();
+        let with_currency = |b: &statement::Balance| {
+            let mut b = b.clone();
+            if b.currency.is_empty() {
+                b.currency = currency.clone();
+            }
+            b
+        };
+        let balance = |b: &Option<statement::Balance>| {
+            b.as_ref().map(|b| (&with_currency(b)).into()).unwrap_or_default()
+        };
+        let available = |b: &Option<statement::Balance>| b.as_ref().map(|b| (&with_currency(b)).into());
+
+        Self {
+            opening_balance: balance(&value.opening_balance),
+            closing_balance: balance(&value.closing_balance),
+            closing_available_balance: available(&value.closing_available_balance),
+            forward_available_balance: available(&value.forward_available_balance),
+            transaction_ref_no: value.id,
+            ref_to_related_msg: value.related_ref,
+            account_id: value.account.id,
+            statement_no: value.statement_no,
+            sequence_no: value.sequence_no,
+            // ожидающие проведения и информационные проводки (CAMT Sts PDNG, INFO) в MT940 не попадают
+            statement_lines: value
+                .entries
+                .into_iter()
+                .filter(|e| e.status.is_booked())
+                .map(|e| StatementLine::from_entry(e, codes))
+                .collect(),
+            information_to_account_owner: value.additional_info,
         }
+    }
+}
+
+/// Код SWIFT проводки; если его нет — по коду ISO из таблицы `codes`, иначе `MSC`.
+fn transaction_type_ident_code(entry: &Entry, codes: &BankTxCodeMap) -> String {
+    if !entry.transaction_type_code.is_empty() {
+        return entry.transaction_type_code.clone();
+    }
+    entry
+        .domain_code
+        .as_ref()
+        .and_then(|d| codes.to_swift(d).map(str::to_string))
+        .unwrap_or_else(|| "MSC".to_string())
+}
 
-        if let Some(c) = &mut statement {
-            message.statement_lines.push(mem::take(c));
+impl From<Entry> for StatementLine {
+    fn from(entry: Entry) -> Self {
+        Self::from_entry(entry, &BankTxCodeMap::default())
+    }
+}
+
+impl StatementLine {
+    /// Строка `:61:` с `:86:` по проводке, код SWIFT — см. [`transaction_type_ident_code`].
+    pub(crate) fn from_entry(entry: Entry, codes: &BankTxCodeMap) -> Self {
+        let information_to_account_owner = OwnerInformation::from(&entry).to_field();
+        let transaction_type_ident_code = transaction_type_ident_code(&entry, codes);
+        Self {
+            value_date: entry.value_date,
+            entry_date: entry.booking_date,
+            ext_debit_credit_indicator: entry.debit_credit_indicator,
+            funds_code: entry.funds_code,
+            amount: entry.amount,
+            transaction_type_ident_code,
+            customer_ref: entry.customer_ref,
+            bank_ref: entry.bank_ref,
+            supplementary_details: entry.supplementary_details,
+            information_to_account_owner,
         }
+    }
+}
 
-        if message != Message::default() {
-            result.push(mem::take(&mut message));
+impl From<MT940Format> for Statements {
+    fn from(value: MT940Format) -> Self {
+        Self {
+            statements: value.transactions.into_iter().map(Into::into).collect(),
         }
+    }
+}
+
+impl From<Statements> for MT940Format {
+    fn from(value: Statements) -> Self {
+        Self::from_statements_with(value, &BankTxCodeMap::default())
+    }
+}
 
+impl MT940Format {
+    /// Выписки в MT940, как `From<Statements>`, но код SWIFT проводки без него берётся по коду
+    /// ISO из таблицы `codes`, а не из встроенной.
+    pub fn from_statements_with(value: Statements, codes: &BankTxCodeMap) -> Self {
         Self {
             other_data: vec!["{3:}".into()],
-            transactions: result,
+            transactions: value
+                .statements
+                .into_iter()
+                .map(|s| Message::from_statement(s, codes))
+                .collect(),
         }
     }
 }
 
+impl From<Camt053Format> for MT940Format {
+    fn from(value: Camt053Format) -> Self {
+        Statements::from(value).into()
+    }
+}
+
+impl TryFrom<CSVFormat> for MT940Format {
+    type Error = FormatError;
+
+    fn try_from(value: CSVFormat) -> Result<Self, Self::Error> {
+        Statements::try_from(value).map(Into::into)
+    }
+}
+
 impl TransactionsReader for MT940Format {
-    fn collect_transactions(&self) -> Vec<Transaction> {
+    fn read_transactions(&self, _mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
         let mut transactions = Vec::new();
         for msg in &self.transactions {
             for statement in &msg.statement_lines {
+                let code = &statement.transaction_type_ident_code;
+                let info = statement
+                    .information_to_account_owner
+                    .as_deref()
+                    .map(OwnerInformation::parse)
+                    .unwrap_or_default();
+                let description: Vec<&str> = [&info.remittance_info, &info.additional_info]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .filter(|d| !d.is_empty())
+                    .collect();
                 transactions.push(Transaction {
                     amount: statement.amount,
                     operation_type: statement.ext_debit_credit_indicator,
                     date: statement.value_date,
-                    currency: msg.opening_balance.balance.iso_currency_code.clone()
+                    currency: msg.opening_balance.balance.iso_currency_code.clone(),
+                    booking_date: statement.entry_date,
+                    value_date: Some(statement.value_date),
+                    description: (!description.is_empty())
+                        .then(|| description.join(" "))
+                        .or_else(|| statement.supplementary_details.clone().filter(|d| !d.is_empty())),
+                    counterparty: info.counterparty(statement.ext_debit_credit_indicator),
+                    creditor_ref: info.creditor_ref.clone(),
+                    customer_ref: Some(statement.customer_ref.clone())
+                        .filter(|r| !r.is_empty() && r != "NONREF" && r != "NOTPROVIDED"),
+                    bank_ref: statement.bank_ref.clone().filter(|r| !r.is_empty()),
+                    bank_tx_code: (!code.is_empty()).then(|| code.clone()),
+                    ..Default::default()
                 });
             }
         }
-        transactions
+        Ok(CollectedTransactions {
+            transactions,
+            ..Default::default()
+        })
     }
 }
 
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
     use std::io::Cursor;
@@ -1154,7 +1376,7 @@ mod tests {
         };
 
         // 2) Вызываем collect_transactions
-        let txs = fmt.collect_transactions();
+        let txs = fmt.collect_transactions().unwrap();
 
         // 3) Проверяем что кол-во совпало
         assert_eq!(txs.len(), 2);
@@ -1179,16 +1401,37 @@ mod tests {
         assert_eq!(txs[1].currency, "EUR");
     }
 
+    #[test]
+    fn collect_transactions_fills_references_and_description() {
+        let mut msg = get_message();
+        msg.statement_lines[0].customer_ref = "PO-42".to_string();
+        let fmt = MT940Format {
+            transactions: vec![msg],
+            ..Default::default()
+        };
+
+        let txs = fmt.collect_transactions().unwrap();
+        assert_eq!(txs[0].description.as_deref(), Some("TEST PAYMENT"));
+        assert_eq!(txs[0].customer_ref.as_deref(), Some("PO-42"));
+        assert_eq!(txs[0].bank_ref.as_deref(), Some("ABC123"));
+        assert_eq!(txs[0].bank_tx_code.as_deref(), Some("TRF"));
+        assert_eq!(txs[0].booking_date, NaiveDate::from_ymd_opt(2024, 1, 2));
+        assert_eq!(txs[0].value_date, NaiveDate::from_ymd_opt(2024, 1, 2));
+
+        let txs = MT940Format {
+            transactions: vec![get_message()],
+            ..Default::default()
+        }
+        .collect_transactions().unwrap();
+        assert_eq!(txs[0].customer_ref, None);
+    }
+
     fn find_text(camt: &Camt053Format, path: &str) -> Option<String> {
-        camt.get_iter()
-            .find(|t| t.path().as_str() == path)
-            .map(|t| t.text())
+        camt.select_first(path).unwrap().map(|t| t.text())
     }
 
     fn find_attr(camt: &Camt053Format, path: &str, attr: &str) -> Option<String> {
-        camt.get_iter()
-            .find(|t| t.path().as_str() == path)
-            .and_then(|t| t.get_attr(attr))
+        camt.select_first(path).unwrap().and_then(|t| t.get_attr(attr))
     }
 
     #[test]
@@ -1201,19 +1444,19 @@ mod tests {
 
         // 1) AccountId у вас кладётся в IBAN (если выглядит как IBAN)
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/Acct/Id/IBAN").as_deref(),
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Acct/Id/IBAN").as_deref(),
             Some("DE12500105170648489890")
         );
 
         // 2) statement_no и sequence_no:
         // Stmt/Id = "{statement_no}/{sequence_no}"
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/Id").as_deref(),
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Id").as_deref(),
             Some("TRN123456")
         );
         // Stmt/ElctrncSeqNb = sequence_no
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/ElctrncSeqNb").as_deref(),
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/ElctrncSeqNb").as_deref(),
             Some("00001")
         );
 
@@ -1221,10 +1464,10 @@ mod tests {
         // и что Amt имеет Ccy="EUR"
         // (у вас баланс строится как Stmt/Bal/... и код баланса в .../Tp/.../Cd)
         let has_opbd = camt.get_iter().any(|t| {
-            t.path().as_str() == "/BkToCstmrStmt/Stmt/Bal/Tp/CdOrPrtry/Cd" && t.text() == "OPBD"
+            t.path().as_str() == "/Document/BkToCstmrStmt/Stmt/Bal/Tp/CdOrPrtry/Cd" && t.text() == "OPBD"
         });
         let has_clbd = camt.get_iter().any(|t| {
-            t.path().as_str() == "/BkToCstmrStmt/Stmt/Bal/Tp/CdOrPrtry/Cd" && t.text() == "CLBD"
+            t.path().as_str() == "/Document/BkToCstmrStmt/Stmt/Bal/Tp/CdOrPrtry/Cd" && t.text() == "CLBD"
         });
 
         assert!(has_opbd, "OPBD (opening balance) must exist in CAMT");
@@ -1232,37 +1475,130 @@ mod tests {
 
         // Amt currency: в вашем коде для Ntry/Amt Ccy берётся из opening_balance.iso_currency_code
         assert_eq!(
-            find_attr(&camt, "/BkToCstmrStmt/Stmt/Ntry/Amt", "Ccy").as_deref(),
+            find_attr(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/Amt", "Ccy").as_deref(),
             Some("EUR")
         );
 
         // 4) Транзакция: amount и направление
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/Ntry/Amt").as_deref(),
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/Amt").as_deref(),
             Some("1.23")
         );
         // Debit -> "DBIT"
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/Ntry/CdtDbtInd").as_deref(),
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/CdtDbtInd").as_deref(),
             Some("DBIT")
         );
 
         // 5) Банк-референс должен оказаться в AcctSvcrRef
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/Ntry/AcctSvcrRef").as_deref(),
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/AcctSvcrRef").as_deref(),
             Some("ABC123")
         );
 
         // 6) InformationToAccountOwner -> RmtInf/Ustrd
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/Ntry/NtryDtls/TxDtls/AddtlTxInf").as_deref(),
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/NtryDtls/TxDtls/AddtlTxInf").as_deref(),
             Some("TEST PAYMENT")
         );
 
-        // 7) customer_ref + supplementary_details у вас собираются в BkTxCd/Prtry/Cd как "NONREF/SUP"
+        // 7) код SWIFT пишется в BkTxCd/Prtry с Issr SWIFT, код ISO для списания TRF — по встроенной таблице
+        assert_eq!(
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/BkTxCd/Prtry/Cd").as_deref(),
+            Some("NTRF")
+        );
         assert_eq!(
-            find_text(&camt, "/BkToCstmrStmt/Stmt/Ntry/BkTxCd/Prtry/Cd").as_deref(),
-            Some("NONREF/SUP")
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/BkTxCd/Prtry/Issr").as_deref(),
+            Some("SWIFT")
         );
+        assert_eq!(
+            find_text(&camt, "/Document/BkToCstmrStmt/Stmt/Ntry/BkTxCd/Domn/Fmly/Cd").as_deref(),
+            Some("ICDT")
+        );
+
+        let back: MT940Format = Statements::from(camt).into();
+        assert_eq!(back.transactions[0].statement_lines[0].transaction_type_ident_code, "TRF");
+    }
+
+    #[test]
+    fn owner_information_parses_codes_and_writes_them_back() {
+        // первая строка обрезана на 65 символах посреди BIC
+        let text = "/DRNM/ACME Corporation Holding/DACT/DE89370400440532013000/DBIC/C\nOBADEFFXXX\n/REMI/Invoice 17/CREF/RF18539007547034/EREF/E2E-1";
+        let info = OwnerInformation::parse(text);
+        assert_eq!(info.debtor.name.as_deref(), Some("ACME Corporation Holding"));
+        assert_eq!(info.debtor.account.as_deref(), Some("DE89370400440532013000"));
+        assert_eq!(info.debtor.bic.as_deref(), Some("COBADEFFXXX"));
+        assert_eq!(info.remittance_info.as_deref(), Some("Invoice 17"));
+        assert_eq!(info.creditor_ref.as_deref(), Some("RF18539007547034"));
+        assert_eq!(info.additional_info.as_deref(), Some("/EREF/E2E-1"));
+        assert_eq!(info.counterparty(DebitOrCredit::Credit), Some(info.debtor.clone()));
+        assert_eq!(info.counterparty(DebitOrCredit::Debit), None);
+
+        let field = info.to_field().unwrap();
+        assert!(field.lines().all(|l| l.chars().count() <= 65));
+        assert_eq!(OwnerInformation::parse(&field), info);
+
+        let free = OwnerInformation::parse("TEST PAYMENT /REMI/ not a code");
+        assert_eq!(free.additional_info.as_deref(), Some("TEST PAYMENT /REMI/ not a code"));
+        assert_eq!(free.to_field().as_deref(), Some("TEST PAYMENT /REMI/ not a code"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn owner_information_keeps_unknown_slashes_and_short_line_breaks() {
+        let rent = OwnerInformation::parse("/REMI/RENT/MARCH");
+        assert_eq!(rent.remittance_info.as_deref(), Some("RENT/MARCH"));
+        assert_eq!(rent.additional_info, None);
+
+        let empty = OwnerInformation::parse("/REMI//X");
+        assert_eq!(empty.remittance_info.as_deref(), Some("/X"));
+
+        // код сразу после кода — часть значения, а не новое значение
+        let nested = OwnerInformation::parse("/REMI/ADDI/X");
+        assert_eq!(nested.remittance_info.as_deref(), Some("ADDI/X"));
+
+        // короткая строка не была обрезана, перенос — это пробел
+        let wrapped = OwnerInformation::parse("/REMI/Payment for\ninvoice 17\n/CREF/RF18");
+        assert_eq!(wrapped.remittance_info.as_deref(), Some("Payment for invoice 17"));
+        assert_eq!(wrapped.creditor_ref.as_deref(), Some("RF18"));
+
+        // незнакомый код отделяет значение, только если начинает строку
+        let other = OwnerInformation::parse("/REMI/Payment\n/OPRP/Tag Payment");
+        assert_eq!(other.remittance_info.as_deref(), Some("Payment"));
+        assert_eq!(other.additional_info.as_deref(), Some("/OPRP/Tag Payment"));
+    }
+
+    #[test]
+    fn structured_information_goes_to_camt_parties_and_back() {
+        let mut msg = get_message();
+        msg.statement_lines[0].information_to_account_owner =
+            Some("/CRNM/Supplier Ltd/CACT/DE44500105175407324931/CBIC/INGDDEFFXXX\n/REMI/Invoice 17/CREF/RF18539007547034".to_string());
+        let mt = MT940Format {
+            transactions: vec![msg],
+            other_data: vec!["".to_string()],
+        };
+        let camt: Camt053Format = mt.into();
+        assert!(crate::camt053_schema::validate(&camt).is_empty());
+
+        let tx = "/Document/BkToCstmrStmt/Stmt/Ntry/NtryDtls/TxDtls";
+        assert_eq!(find_text(&camt, &format!("{tx}/RltdPties/Cdtr/Nm")).as_deref(), Some("Supplier Ltd"));
+        assert_eq!(
+            find_text(&camt, &format!("{tx}/RltdPties/CdtrAcct/Id/IBAN")).as_deref(),
+            Some("DE44500105175407324931")
+        );
+        assert_eq!(find_text(&camt, &format!("{tx}/RltdAgts/CdtrAgt/FinInstnId/BIC")).as_deref(), Some("INGDDEFFXXX"));
+        assert_eq!(find_text(&camt, &format!("{tx}/RmtInf/Ustrd")).as_deref(), Some("Invoice 17"));
+        assert_eq!(find_text(&camt, &format!("{tx}/RmtInf/Strd/CdtrRefInf/Ref")).as_deref(), Some("RF18539007547034"));
+
+        let txs = camt.collect_transactions().unwrap();
+        let party = txs[0].counterparty.as_ref().unwrap();
+        assert_eq!(party.name.as_deref(), Some("Supplier Ltd"));
+        assert_eq!(party.bic.as_deref(), Some("INGDDEFFXXX"));
+        assert_eq!(txs[0].creditor_ref.as_deref(), Some("RF18539007547034"));
+
+        let back: MT940Format = Statements::from(camt).into();
+        let field = back.transactions[0].statement_lines[0].information_to_account_owner.clone().unwrap();
+        assert_eq!(OwnerInformation::parse(&field), OwnerInformation::parse(
+            "/CRNM/Supplier Ltd/CACT/DE44500105175407324931/CBIC/INGDDEFFXXX/REMI/Invoice 17/CREF/RF18539007547034"
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+//! MT942 — отчёт по счёту в течение дня (Interim Transaction Report).
+//!
+//! Отчёт содержит те же строки `:61:`/`:86:`, что и MT940, но вместо остатков в нём
+//! нижняя граница сумм `:34F:`, время формирования `:13D:` и итоги по дебету и кредиту
+//! `:90D:`/`:90C:`. Отчёт строится из формато-независимых выписок, например из camt.052.
+
+use crate::bank_tx_code::BankTxCodeMap;
+use crate::camt052_format::Camt052Format;
+use crate::common::debit_credit::DebitOrCredit;
+use crate::error::{FormatError, GeneratorFormatError};
+use crate::mt940_format::{MT940Format, StatementLine};
+use crate::statement::{Statement, Statements};
+use crate::transactions_holder::EntryStatus;
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+use std::io::Write;
+
+#[derive(Default, Eq, PartialEq, Debug)]
+pub struct Message {
+    pub transaction_ref_no: String,
+    pub ref_to_related_msg: Option<String>,
+    pub account_id: String,
+    pub statement_no: String,
+    pub sequence_no: Option<String>,
+    /// Валюта счёта (`:34F:`, `:90D:`, `:90C:`).
+    pub currency: String,
+    /// Нижняя граница сумм проводок, попавших в отчёт (`:34F:`).
+    pub floor_limit: Decimal,
+    /// Время формирования отчёта (`:13D:`).
+    pub date_time: DateTime<FixedOffset>,
+    pub statement_lines: Vec<StatementLine>,
+    pub information_to_account_owner: Option<String>,
+}
+
+#[derive(Default)]
+pub struct MT942Format {
+    pub(crate) messages: Vec<Message>,
+}
+
+impl GeneratorFormatError for MT942Format {
+    const ERROR_PREFIX: &'static str = "Ошибка записи формата mt942";
+}
+
+impl MT942Format {
+    /// Записывает отчёты MT942 в `writer`, каждый в своём блоке `{4: ... -}`.
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> Result<(), FormatError> {
+        for message in &self.messages {
+            let mut first_write = true;
+            MT940Format::write_message(writer, "20", &message.transaction_ref_no, &mut first_write)?;
+            if let Some(x) = message.ref_to_related_msg.as_ref() {
+                MT940Format::write_message(writer, "21", x, &mut first_write)?;
+            }
+            MT940Format::write_message(writer, "25", &message.account_id, &mut first_write)?;
+
+            let mut number = message.statement_no.clone();
+            if let Some(v) = message.sequence_no.as_ref() {
+                number += "/";
+                number.push_str(v);
+            }
+            MT940Format::write_message(writer, "28C", &number, &mut first_write)?;
+            let floor_limit = format!("{}{}", message.currency, message.floor_limit);
+            MT940Format::write_message(writer, "34F", &floor_limit, &mut first_write)?;
+            let date_time = message.date_time.format("%y%m%d%H%M%z").to_string();
+            MT940Format::write_message(writer, "13D", &date_time, &mut first_write)?;
+
+            for line in &message.statement_lines {
+                MT940Format::write_statement(writer, line, &mut first_write)?;
+            }
+
+            let (credits, debits): (Vec<&StatementLine>, Vec<&StatementLine>) = message
+                .statement_lines
+                .iter()
+                .partition(|l| matches!(l.ext_debit_credit_indicator, DebitOrCredit::Credit | DebitOrCredit::ReverseCredit));
+            for (tag, lines) in [("90D", debits), ("90C", credits)] {
+                if lines.is_empty() {
+                    continue;
+                }
+                let sum: Decimal = lines.iter().map(|l| l.amount).sum();
+                let value = format!("{}{}{}", lines.len(), message.currency, sum);
+                MT940Format::write_message(writer, tag, &value, &mut first_write)?;
+            }
+            if let Some(i) = message.information_to_account_owner.as_ref() {
+                MT940Format::write_message(writer, "86", i, &mut first_write)?;
+            }
+            writer.write_all("-}\n".as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a MT942Format {
+    type Item = &'a Message;
+    type IntoIter = std::slice::Iter<'a, Message>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.iter()
+    }
+}
+
+/// Время формирования отчёта — `CreDtTm` выписки, а без него полночь (UTC) даты
+/// последнего остатка или последней проводки.
+impl From<Statement> for Message {
+    fn from(value: Statement) -> Self {
+        Self::from_statement(value, &BankTxCodeMap::default())
+    }
+}
+
+impl Message {
+    /// Отчёт по выписке; коды SWIFT проводок без них берутся из таблицы `codes`.
+    fn from_statement(value: Statement, codes: &BankTxCodeMap) -> Self {
+        let currency = value.currency().unwrap_or_default().to_string();
+        let date_time = value.created.unwrap_or_else(|| {
+            value
+                .closing_balance
+                .as_ref()
+                .or(value.opening_balance.as_ref())
+                .map(|b| b.date)
+                .or_else(|| value.entries.iter().map(|e| e.value_date).max())
+                .unwrap_or_default()
+                .and_hms_opt(0, 0, 0)
+                .unwrap_or_default()
+                .and_utc()
+                .fixed_offset()
+        });
+
+        Self {
+            transaction_ref_no: value.id,
+            ref_to_related_msg: value.related_ref,
+            account_id: value.account.id,
+            statement_no: value.statement_no,
+            sequence_no: value.sequence_no,
+            currency,
+            floor_limit: Decimal::ZERO,
+            date_time,
+            // промежуточный отчёт показывает и ожидающие проведения проводки, но не информационные
+            statement_lines: value
+                .entries
+                .into_iter()
+                .filter(|e| e.status != EntryStatus::Info)
+                .map(|e| StatementLine::from_entry(e, codes))
+                .collect(),
+            information_to_account_owner: value.additional_info,
+        }
+    }
+}
+
+impl From<Statements> for MT942Format {
+    fn from(value: Statements) -> Self {
+        Self::from_statements_with(value, &BankTxCodeMap::default())
+    }
+}
+
+impl MT942Format {
+    /// Выписки в отчёты MT942, как `From<Statements>`, с таблицей кодов операций `codes`.
+    pub fn from_statements_with(value: Statements, codes: &BankTxCodeMap) -> Self {
+        Self {
+            messages: value
+                .statements
+                .into_iter()
+                .map(|s| Message::from_statement(s, codes))
+                .collect(),
+        }
+    }
+}
+
+impl From<Camt052Format> for MT942Format {
+    fn from(value: Camt052Format) -> Self {
+        Statements::from(value).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statement::Balance;
+    use chrono::NaiveDate;
+    use std::fs::File;
+
+    #[test]
+    fn camt052_is_written_as_mt942() {
+        let camt = Camt052Format::from_read(&mut File::open("./examples/data/camt052.exmpl").unwrap()).unwrap();
+        let mut mt942 = MT942Format::from(camt);
+        let mut written = Vec::new();
+        mt942.write_to(&mut written).unwrap();
+
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "{4:\n\
+             :20:RPT-0001\n\
+             :25:NL81ASNB9999999999\n\
+             :28C:3\n\
+             :34F:EUR0\n\
+             :13D:2001011230+0100\n\
+             :61:2001010101C250.00NTRFINV-1//BANKREF1\n\
+             :86:Invoice 1\n\
+             :61:2001010101D50.00NCHG//BANKREF2\n\
+             :90D:1EUR50.00\n\
+             :90C:1EUR250.00\n\
+             :86:Intraday report\n\
+             -}\n"
+        );
+    }
+
+    #[test]
+    fn report_time_defaults_to_balance_date() {
+        let statement = Statement {
+            closing_balance: Some(Balance {
+                date: NaiveDate::from_ymd_opt(2020, 1, 2).unwrap(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let message = Message::from(statement);
+        assert_eq!(message.date_time.format("%y%m%d%H%M%z").to_string(), "2001020000+0000");
+    }
+}
@@ -0,0 +1,676 @@
+use crate::common::debit_credit::DebitOrCredit;
+use crate::transactions_holder::{Transaction, TransactionHolder};
+use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
+use std::fmt;
+
+/// Максимальное количество различающихся полей, при котором пара транзакций
+/// считается «почти совпадением», а не двумя несвязанными записями.
+const NEAR_MATCH_MAX_DIFFERENCES: usize = 1;
+
+/// Сколько ближайших по дате транзакций рассматривается при подборе группы.
+/// Ограничивает перебор подмножеств на больших выписках.
+const GROUP_MAX_CANDIDATES: usize = 20;
+
+/// Наибольший допустимый [`MatchRules::max_group_size`]. Для каждой несопоставленной
+/// транзакции перебираются подмножества из не более чем `GROUP_MAX_CANDIDATES` кандидатов,
+/// то есть до C(20, k) сумм для группы из `k` элементов: при 5 это около 20 тысяч, а при 10 —
+/// уже около 185 тысяч на транзакцию.
+pub const MAX_GROUP_SIZE: usize = 5;
+
+/// Наибольшее окно дат для разбора параметров: больше года сверка по дате теряет смысл.
+pub const MAX_DATE_WINDOW_DAYS: u32 = 366;
+
+/// Поле транзакции, участвующее в сверке.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionField {
+    Date,
+    Amount,
+    Currency,
+    OperationType,
+}
+
+impl fmt::Display for TransactionField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TransactionField::Date => "дата",
+            TransactionField::Amount => "сумма",
+            TransactionField::Currency => "валюта",
+            TransactionField::OperationType => "тип операции",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Расхождение одного поля в паре транзакций.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDifference {
+    pub field: TransactionField,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for FieldDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} / {}", self.field, self.left, self.right)
+    }
+}
+
+/// Правила сопоставления транзакций.
+///
+/// Значение по умолчанию требует точного совпадения даты, суммы, валюты и типа операции.
+#[derive(Debug, Clone)]
+pub struct MatchRules {
+    /// Допустимое расхождение дат в днях (в обе стороны).
+    pub date_window_days: u32,
+    /// Допустимое абсолютное расхождение суммы.
+    pub amount_tolerance: Decimal,
+    /// Допустимое относительное расхождение суммы (доля от большей из сумм, `0.01` = 1%).
+    pub amount_tolerance_ratio: Decimal,
+    /// Сравнивать валюту.
+    pub check_currency: bool,
+    /// Сравнивать тип операции (дебет/кредит).
+    pub check_direction: bool,
+    /// Максимальный размер группы транзакций, сумма которой сопоставляется с одной транзакцией
+    /// другой стороны. Значения `0` и `1` отключают групповое сопоставление, значения больше
+    /// [`MAX_GROUP_SIZE`] ограничиваются им.
+    pub max_group_size: usize,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            date_window_days: 0,
+            amount_tolerance: Decimal::ZERO,
+            amount_tolerance_ratio: Decimal::ZERO,
+            check_currency: true,
+            check_direction: true,
+            max_group_size: 1,
+        }
+    }
+}
+
+impl MatchRules {
+    fn date_matches(&self, left: &Transaction, right: &Transaction) -> bool {
+        (right.date - left.date).num_days().abs() <= i64::from(self.date_window_days)
+    }
+
+    fn amount_matches(&self, left: &Transaction, right: &Transaction) -> bool {
+        self.amounts_match(left.amount, right.amount)
+    }
+
+    fn amounts_match(&self, left: Decimal, right: Decimal) -> bool {
+        let diff = (left - right).abs();
+        if diff <= self.amount_tolerance {
+            return true;
+        }
+        let base = left.abs().max(right.abs());
+        diff <= base * self.amount_tolerance_ratio
+    }
+
+    /// Может ли транзакция `part` входить в группу, сумма которой сопоставляется с `whole`.
+    fn can_be_grouped(&self, whole: &Transaction, part: &Transaction) -> bool {
+        self.date_matches(whole, part)
+            && (!self.check_direction || whole.operation_type == part.operation_type)
+            && (!self.check_currency
+                || whole.currency.is_empty()
+                || part.currency.is_empty()
+                || whole.currency == part.currency)
+    }
+}
+
+/// Насколько отличаются стороны в сопоставленной паре.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchDelta {
+    /// Дата справа минус дата слева, в днях.
+    pub days: i64,
+    /// Сумма справа минус сумма слева.
+    pub amount: Decimal,
+}
+
+impl MatchDelta {
+    fn between(left: &Transaction, right: &Transaction) -> Self {
+        Self {
+            days: (right.date - left.date).num_days(),
+            amount: right.amount - left.amount,
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.days == 0 && self.amount.is_zero()
+    }
+}
+
+/// Пара транзакций, совпавших при сверке.
+#[derive(Debug)]
+pub struct MatchedPair<'a> {
+    pub left: &'a Transaction,
+    pub right: &'a Transaction,
+    pub delta: MatchDelta,
+}
+
+/// Группа транзакций одной стороны, сумма которой совпала с транзакцией (или группой) другой стороны.
+///
+/// Типичный случай — банк разбивает один платёж учётной системы на несколько строк выписки.
+#[derive(Debug)]
+pub struct GroupedMatch<'a> {
+    pub left: Vec<&'a Transaction>,
+    pub right: Vec<&'a Transaction>,
+    /// Сумма справа минус сумма слева. Суммы считаются со знаком относительно одиночной
+    /// транзакции: операции обратного типа (при `check_direction = false`) её уменьшают.
+    pub amount_delta: Decimal,
+}
+
+/// Пара транзакций, которые отличаются небольшим количеством полей.
+#[derive(Debug)]
+pub struct NearMatch<'a> {
+    pub left: &'a Transaction,
+    pub right: &'a Transaction,
+    pub differences: Vec<FieldDifference>,
+}
+
+/// Итоговый вердикт сверки.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Identical,
+    Different,
+}
+
+/// Результат сверки двух наборов транзакций.
+#[derive(Debug, Default)]
+pub struct Reconciliation<'a> {
+    pub matched: Vec<MatchedPair<'a>>,
+    pub grouped: Vec<GroupedMatch<'a>>,
+    pub near_matches: Vec<NearMatch<'a>>,
+    pub unmatched_left: Vec<&'a Transaction>,
+    pub unmatched_right: Vec<&'a Transaction>,
+}
+
+impl Reconciliation<'_> {
+    /// Наборы совпадают, если каждая транзакция попала в пару или группу. Группы
+    /// ([`MatchRules::max_group_size`] больше 1) считаются совпадением, как и пары в пределах
+    /// допусков: их разрешают сами правила сверки, а в отчёте они перечисляются отдельно.
+    pub fn verdict(&self) -> Verdict {
+        if self.near_matches.is_empty()
+            && self.unmatched_left.is_empty()
+            && self.unmatched_right.is_empty()
+        {
+            Verdict::Identical
+        } else {
+            Verdict::Different
+        }
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.verdict() == Verdict::Identical
+    }
+}
+
+/// Найти все поля, по которым различаются две транзакции при точном сравнении.
+///
+/// Валюта сравнивается только если она указана в обеих транзакциях:
+/// часть источников (например, выгрузка csv) валюту не содержит.
+pub fn differences(left: &Transaction, right: &Transaction) -> Vec<FieldDifference> {
+    differences_with(left, right, &MatchRules::default())
+}
+
+/// Найти все поля, которые выходят за допуски правил `rules`.
+pub fn differences_with(left: &Transaction, right: &Transaction, rules: &MatchRules) -> Vec<FieldDifference> {
+    let mut result = Vec::new();
+    if !rules.date_matches(left, right) {
+        result.push(FieldDifference {
+            field: TransactionField::Date,
+            left: left.date.to_string(),
+            right: right.date.to_string(),
+        });
+    }
+    if !rules.amount_matches(left, right) {
+        result.push(FieldDifference {
+            field: TransactionField::Amount,
+            left: left.amount.to_string(),
+            right: right.amount.to_string(),
+        });
+    }
+    if rules.check_currency
+        && !left.currency.is_empty()
+        && !right.currency.is_empty()
+        && left.currency != right.currency
+    {
+        result.push(FieldDifference {
+            field: TransactionField::Currency,
+            left: left.currency.clone(),
+            right: right.currency.clone(),
+        });
+    }
+    if rules.check_direction && left.operation_type != right.operation_type {
+        result.push(FieldDifference {
+            field: TransactionField::OperationType,
+            left: left.operation_type.to_string().to_string(),
+            right: right.operation_type.to_string().to_string(),
+        });
+    }
+    result
+}
+
+/// Сверить транзакции из двух источников, требуя точного совпадения полей.
+pub fn reconcile<'a>(left: &'a TransactionHolder, right: &'a TransactionHolder) -> Reconciliation<'a> {
+    reconcile_with(left, right, &MatchRules::default())
+}
+
+/// Сверить транзакции из двух источников по правилам `rules`.
+///
+/// Сверка выполняется в три прохода:
+/// 1) транзакции, укладывающиеся в допуски, объединяются в пары [`MatchedPair`];
+///    если кандидатов несколько, выбирается ближайший по дате, затем по сумме;
+/// 2) если `rules.max_group_size > 1`, для оставшихся транзакций ищутся группы одной стороны,
+///    сумма которых совпадает с одной транзакцией другой стороны в пределах окна дат ([`GroupedMatch`]);
+/// 3) из оставшихся транзакций подбираются пары, отличающиеся не более чем одним полем ([`NearMatch`]).
+///
+/// Всё, что не удалось сопоставить, попадает в `unmatched_left`/`unmatched_right`.
+/// Пары и группы считаются совпадениями, см. [`Reconciliation::verdict`].
+pub fn reconcile_with<'a>(
+    left: &'a TransactionHolder,
+    right: &'a TransactionHolder,
+    rules: &MatchRules,
+) -> Reconciliation<'a> {
+    let left = left.transactions();
+    let right = right.transactions();
+    let mut result = Reconciliation::default();
+
+    let mut used_left = vec![false; left.len()];
+    let mut used_right = vec![false; right.len()];
+    for (i, t) in left.iter().enumerate() {
+        let (from, to) = date_range(right, t.date, rules.date_window_days);
+        let best = (from..to)
+            .filter(|&j| !used_right[j] && differences_with(t, &right[j], rules).is_empty())
+            .min_by_key(|&j| {
+                let delta = MatchDelta::between(t, &right[j]);
+                (delta.days.abs(), delta.amount.abs())
+            });
+
+        if let Some(j) = best {
+            used_left[i] = true;
+            used_right[j] = true;
+            result.matched.push(MatchedPair {
+                left: t,
+                right: &right[j],
+                delta: MatchDelta::between(t, &right[j]),
+            });
+        }
+    }
+
+    if rules.max_group_size > 1 {
+        // Одна транзакция справа против нескольких слева
+        for (j, whole) in right.iter().enumerate() {
+            if used_right[j] {
+                continue;
+            }
+            if let Some(parts) = find_group(whole, left, &used_left, rules) {
+                used_right[j] = true;
+                parts.iter().for_each(|&i| used_left[i] = true);
+                result.grouped.push(group_match(
+                    whole,
+                    parts.iter().map(|&i| &left[i]).collect(),
+                    vec![whole],
+                ));
+            }
+        }
+        // Одна транзакция слева против нескольких справа
+        for (i, whole) in left.iter().enumerate() {
+            if used_left[i] {
+                continue;
+            }
+            if let Some(parts) = find_group(whole, right, &used_right, rules) {
+                used_left[i] = true;
+                parts.iter().for_each(|&j| used_right[j] = true);
+                result.grouped.push(group_match(
+                    whole,
+                    vec![whole],
+                    parts.iter().map(|&j| &right[j]).collect(),
+                ));
+            }
+        }
+    }
+
+    let rest_left: Vec<&Transaction> = left
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !used_left[*i])
+        .map(|(_, t)| t)
+        .collect();
+
+    for t in rest_left {
+        let best = right
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| !used_right[*j])
+            .map(|(j, r)| (j, differences_with(t, r, rules)))
+            .filter(|(_, d)| d.len() <= NEAR_MATCH_MAX_DIFFERENCES)
+            .min_by_key(|(_, d)| d.len());
+
+        match best {
+            Some((j, differences)) => {
+                used_right[j] = true;
+                result.near_matches.push(NearMatch { left: t, right: &right[j], differences });
+            }
+            None => result.unmatched_left.push(t),
+        }
+    }
+
+    result.unmatched_right = right
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| !used_right[*j])
+        .map(|(_, t)| t)
+        .collect();
+
+    result
+}
+
+/// Диапазон индексов транзакций, попадающих в окно `window_days` вокруг даты `date`.
+///
+/// Транзакции в [`TransactionHolder`] отсортированы по дате, поэтому диапазон ищется бинарным поиском.
+fn date_range(transactions: &[Transaction], date: NaiveDate, window_days: u32) -> (usize, usize) {
+    let window = Duration::days(i64::from(window_days));
+    let first = date.checked_sub_signed(window).unwrap_or(NaiveDate::MIN);
+    let last = date.checked_add_signed(window).unwrap_or(NaiveDate::MAX);
+    (
+        transactions.partition_point(|t| t.date < first),
+        transactions.partition_point(|t| t.date <= last),
+    )
+}
+
+fn is_incoming(t: &Transaction) -> bool {
+    matches!(t.operation_type, DebitOrCredit::Credit | DebitOrCredit::ReverseCredit)
+}
+
+/// Сумма `part` со знаком относительно `whole`: операция того же типа (дебет/кредит)
+/// положительна, обратного — отрицательна.
+fn amount_towards(whole: &Transaction, part: &Transaction) -> Decimal {
+    if is_incoming(whole) == is_incoming(part) {
+        part.amount
+    } else {
+        -part.amount
+    }
+}
+
+fn group_match<'a>(whole: &Transaction, left: Vec<&'a Transaction>, right: Vec<&'a Transaction>) -> GroupedMatch<'a> {
+    let left_sum: Decimal = left.iter().map(|t| amount_towards(whole, t)).sum();
+    let right_sum: Decimal = right.iter().map(|t| amount_towards(whole, t)).sum();
+    GroupedMatch {
+        left,
+        right,
+        amount_delta: right_sum - left_sum,
+    }
+}
+
+/// Найти среди неиспользованных транзакций `many` группу из 2..=`max_group_size` элементов,
+/// сумма которой совпадает с суммой `whole` с учётом допусков. Суммы берутся со знаком, см.
+/// [`amount_towards`]: без проверки типа операции возврат входит в группу с минусом.
+fn find_group(
+    whole: &Transaction,
+    many: &[Transaction],
+    used: &[bool],
+    rules: &MatchRules,
+) -> Option<Vec<usize>> {
+    let (from, to) = date_range(many, whole.date, rules.date_window_days);
+    let mut candidates: Vec<usize> = (from..to)
+        .filter(|&i| !used[i] && rules.can_be_grouped(whole, &many[i]))
+        .collect();
+    if candidates.len() < 2 {
+        return None;
+    }
+
+    candidates.sort_by_key(|&i| (many[i].date - whole.date).num_days().abs());
+    candidates.truncate(GROUP_MAX_CANDIDATES);
+    // По возрастанию суммы: перебор можно прекращать, как только сумма ушла за допуск
+    let mut candidates: Vec<(usize, Decimal)> =
+        candidates.into_iter().map(|i| (i, amount_towards(whole, &many[i]))).collect();
+    candidates.sort_by_key(|&(_, amount)| amount);
+
+    let max_size = rules.max_group_size.min(MAX_GROUP_SIZE);
+    let mut chosen = Vec::new();
+    if find_subset(&candidates, 0, Decimal::ZERO, whole.amount, rules, max_size, &mut chosen) {
+        Some(chosen)
+    } else {
+        None
+    }
+}
+
+fn find_subset(
+    candidates: &[(usize, Decimal)],
+    start: usize,
+    sum: Decimal,
+    target: Decimal,
+    rules: &MatchRules,
+    max_size: usize,
+    chosen: &mut Vec<usize>,
+) -> bool {
+    if chosen.len() >= 2 && rules.amounts_match(sum, target) {
+        return true;
+    }
+    if chosen.len() >= max_size {
+        return false;
+    }
+    for (pos, &(i, amount)) in candidates.iter().enumerate().skip(start) {
+        let next = sum + amount;
+        // дальше только неотрицательные суммы, которые могут лишь увеличить перебор
+        if amount >= Decimal::ZERO && next > target && !rules.amounts_match(next, target) {
+            break;
+        }
+        chosen.push(i);
+        if find_subset(candidates, pos + 1, next, target, rules, max_size, chosen) {
+            return true;
+        }
+        chosen.pop();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::debit_credit::DebitOrCredit;
+    use crate::error::FormatError;
+    use crate::transactions_holder::{CollectedTransactions, ReadMode, TransactionsReader};
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    struct Fixed(Vec<Transaction>);
+
+    impl TransactionsReader for Fixed {
+        fn read_transactions(&self, _mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+            Ok(CollectedTransactions {
+                transactions: self.0.clone(),
+                ..Default::default()
+            })
+        }
+    }
+
+    fn tx(amount: &str, o: DebitOrCredit, day: u32) -> Transaction {
+        Transaction::new(
+            Decimal::from_str(amount).unwrap(),
+            o,
+            NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+        )
+    }
+
+    fn holder(v: Vec<Transaction>) -> TransactionHolder {
+        TransactionHolder::new(Fixed(v)).unwrap()
+    }
+
+    #[test]
+    fn identical_sets_are_identical() {
+        let left = holder(vec![tx("10.00", DebitOrCredit::Debit, 1), tx("5", DebitOrCredit::Credit, 2)]);
+        let right = holder(vec![tx("5.00", DebitOrCredit::Credit, 2), tx("10", DebitOrCredit::Debit, 1)]);
+
+        let result = reconcile(&left, &right);
+        assert_eq!(result.matched.len(), 2);
+        assert_eq!(result.verdict(), Verdict::Identical);
+    }
+
+    #[test]
+    fn same_count_but_different_amount_is_reported() {
+        let left = holder(vec![tx("10.00", DebitOrCredit::Debit, 1)]);
+        let right = holder(vec![tx("11.00", DebitOrCredit::Debit, 1)]);
+
+        let result = reconcile(&left, &right);
+        assert!(!result.is_identical());
+        assert_eq!(result.near_matches.len(), 1);
+        assert_eq!(
+            result.near_matches[0].differences,
+            vec![FieldDifference {
+                field: TransactionField::Amount,
+                left: "10.00".to_string(),
+                right: "11.00".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn unmatched_entries_are_reported_on_each_side() {
+        let left = holder(vec![tx("10.00", DebitOrCredit::Debit, 1), tx("1.00", DebitOrCredit::Credit, 5)]);
+        let right = holder(vec![tx("10.00", DebitOrCredit::Debit, 1), tx("700.00", DebitOrCredit::Debit, 9)]);
+
+        let result = reconcile(&left, &right);
+        assert_eq!(result.matched.len(), 1);
+        assert!(result.near_matches.is_empty());
+        assert_eq!(result.unmatched_left.len(), 1);
+        assert_eq!(result.unmatched_left[0].amount, Decimal::from_str("1.00").unwrap());
+        assert_eq!(result.unmatched_right.len(), 1);
+        assert_eq!(result.unmatched_right[0].amount, Decimal::from_str("700.00").unwrap());
+    }
+
+    #[test]
+    fn empty_currency_is_not_a_difference() {
+        let mut l = tx("10.00", DebitOrCredit::Debit, 1);
+        l.currency = "RUB".to_string();
+        let r = tx("10.00", DebitOrCredit::Debit, 1);
+        assert!(differences(&l, &r).is_empty());
+
+        let mut r = r;
+        r.currency = "EUR".to_string();
+        assert_eq!(differences(&l, &r)[0].field, TransactionField::Currency);
+    }
+
+    #[test]
+    fn date_window_and_amount_tolerance_are_applied() {
+        let left = holder(vec![tx("100.00", DebitOrCredit::Debit, 1)]);
+        let right = holder(vec![tx("100.01", DebitOrCredit::Debit, 2)]);
+
+        assert!(!reconcile(&left, &right).is_identical());
+
+        let rules = MatchRules {
+            date_window_days: 1,
+            amount_tolerance: Decimal::from_str("0.01").unwrap(),
+            ..Default::default()
+        };
+        let result = reconcile_with(&left, &right, &rules);
+        assert!(result.is_identical());
+        assert_eq!(
+            result.matched[0].delta,
+            MatchDelta { days: 1, amount: Decimal::from_str("0.01").unwrap() }
+        );
+    }
+
+    #[test]
+    fn relative_tolerance_and_ignored_direction() {
+        let left = holder(vec![tx("1000.00", DebitOrCredit::Debit, 1)]);
+        let right = holder(vec![tx("1004.00", DebitOrCredit::Credit, 1)]);
+
+        let rules = MatchRules {
+            amount_tolerance_ratio: Decimal::from_str("0.005").unwrap(),
+            check_direction: false,
+            ..Default::default()
+        };
+        assert!(reconcile_with(&left, &right, &rules).is_identical());
+
+        let rules = MatchRules { check_direction: true, ..rules };
+        let result = reconcile_with(&left, &right, &rules);
+        assert_eq!(result.near_matches.len(), 1);
+        assert_eq!(result.near_matches[0].differences[0].field, TransactionField::OperationType);
+    }
+
+    #[test]
+    fn closest_candidate_in_window_is_chosen() {
+        let left = holder(vec![tx("50.00", DebitOrCredit::Credit, 10)]);
+        let right = holder(vec![tx("50.00", DebitOrCredit::Credit, 8), tx("50.00", DebitOrCredit::Credit, 11)]);
+
+        let rules = MatchRules { date_window_days: 3, ..Default::default() };
+        let result = reconcile_with(&left, &right, &rules);
+        assert_eq!(result.matched.len(), 1);
+        assert_eq!(result.matched[0].delta.days, 1);
+        assert_eq!(result.unmatched_right.len(), 1);
+
+        let rules = MatchRules { date_window_days: u32::MAX, ..Default::default() };
+        assert_eq!(reconcile_with(&left, &right, &rules).matched.len(), 1);
+    }
+
+    #[test]
+    fn split_payment_is_reported_as_grouped_match() {
+        let left = holder(vec![tx("300.00", DebitOrCredit::Debit, 5)]);
+        let right = holder(vec![
+            tx("100.00", DebitOrCredit::Debit, 5),
+            tx("150.00", DebitOrCredit::Debit, 6),
+            tx("50.00", DebitOrCredit::Debit, 6),
+            tx("75.00", DebitOrCredit::Credit, 6),
+        ]);
+
+        let rules = MatchRules { date_window_days: 1, max_group_size: 3, ..Default::default() };
+        let result = reconcile_with(&left, &right, &rules);
+
+        assert_eq!(result.grouped.len(), 1);
+        assert_eq!(result.grouped[0].left.len(), 1);
+        assert_eq!(result.grouped[0].right.len(), 3);
+        assert!(result.grouped[0].amount_delta.is_zero());
+        assert_eq!(result.unmatched_right.len(), 1);
+        assert_eq!(result.unmatched_right[0].operation_type, DebitOrCredit::Credit);
+    }
+
+    #[test]
+    fn aggregated_bank_entry_matches_several_ledger_entries() {
+        let left = holder(vec![tx("10.00", DebitOrCredit::Credit, 1), tx("20.01", DebitOrCredit::Credit, 1)]);
+        let right = holder(vec![tx("30.00", DebitOrCredit::Credit, 1)]);
+
+        assert!(!reconcile_with(&left, &right, &MatchRules { max_group_size: 2, ..Default::default() }).is_identical());
+
+        let rules = MatchRules {
+            max_group_size: 2,
+            amount_tolerance: Decimal::from_str("0.01").unwrap(),
+            ..Default::default()
+        };
+        let result = reconcile_with(&left, &right, &rules);
+        assert!(result.is_identical());
+        assert_eq!(result.grouped[0].left.len(), 2);
+        assert_eq!(result.grouped[0].amount_delta, Decimal::from_str("-0.01").unwrap());
+    }
+
+    #[test]
+    fn refund_counts_against_group_when_direction_is_ignored() {
+        let left = holder(vec![tx("70.00", DebitOrCredit::Credit, 3)]);
+        let right = holder(vec![
+            tx("100.00", DebitOrCredit::Credit, 3),
+            tx("30.00", DebitOrCredit::Debit, 3),
+            tx("40.00", DebitOrCredit::Credit, 3),
+        ]);
+
+        // без знака 30 + 40 совпали бы с 70, хотя списание уменьшает поступление
+        let rules = MatchRules { max_group_size: 2, check_direction: false, ..Default::default() };
+        let result = reconcile_with(&left, &right, &rules);
+        assert_eq!(result.grouped.len(), 1);
+        let amounts: Vec<Decimal> = result.grouped[0].right.iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, [Decimal::from(30), Decimal::from(100)]);
+        assert!(result.grouped[0].amount_delta.is_zero());
+        assert_eq!(result.unmatched_right.len(), 1);
+    }
+
+    #[test]
+    fn group_size_is_capped() {
+        let left = holder(vec![tx("6.00", DebitOrCredit::Credit, 1)]);
+        let right = holder((0..6).map(|_| tx("1.00", DebitOrCredit::Credit, 1)).collect());
+
+        let rules = MatchRules { max_group_size: 6, ..Default::default() };
+        assert!(reconcile_with(&left, &right, &rules).grouped.is_empty());
+    }
+}
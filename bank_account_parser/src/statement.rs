@@ -0,0 +1,350 @@
+//! Формато-независимая модель выписки.
+//!
+//! Каждый формат (MT940, CAMT.053, CSV) умеет читаться в [`Statements`] и записываться из него,
+//! поэтому преобразование между любыми двумя форматами выполняется через эту модель:
+//! `MT940Format -> Statements -> Camt053Format`. Для поддержки нового формата достаточно
+//! двух реализаций `From`: в [`Statements`] и из него.
+
+use crate::bank_tx_code::{BankTxCodeMap, DomainCode};
+use crate::common::debit_credit::DebitOrCredit;
+use crate::error::FormatError;
+use crate::transactions_holder::{
+    CollectedTransactions, Counterparty, EntryStatus, ReadMode, RowDiagnostic, Transaction, TransactionsReader,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use rust_decimal::Decimal;
+
+/// Счёт, по которому сформирована выписка.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Account {
+    /// Номер счёта (IBAN или внутренний номер банка).
+    pub id: String,
+    /// Валюта счёта.
+    pub currency: Option<String>,
+}
+
+/// Остаток на счёте на определённую дату.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Balance {
+    pub debit_credit_indicator: DebitOrCredit,
+    pub date: NaiveDate,
+    pub currency: String,
+    pub amount: Decimal,
+    /// Промежуточный остаток (MT940 `:60M:`/`:62M:`).
+    pub is_intermediate: bool,
+}
+
+/// Проводка по счёту.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub value_date: NaiveDate,
+    pub booking_date: Option<NaiveDate>,
+    /// Направление; сторно (CAMT `RvslInd`, MT940 `RC`/`RD`) — `ReverseDebit` и `ReverseCredit`.
+    pub debit_credit_indicator: DebitOrCredit,
+    /// Статус проводки (CAMT `Sts`). В MT940 пишутся только проведённые проводки, в MT942 — все, кроме `INFO`.
+    pub status: EntryStatus,
+    pub amount: Decimal,
+    /// Валюта суммы, если она указана в проводке.
+    pub currency: Option<String>,
+    /// Код средств MT940 (третий символ кода валюты).
+    pub funds_code: Option<String>,
+    /// Код типа операции SWIFT (MT940 `N...`, CAMT `BkTxCd/Prtry` с `Issr` `SWIFT`).
+    pub transaction_type_code: String,
+    /// Код операции ISO 20022 (CAMT `BkTxCd/Domn`).
+    pub domain_code: Option<DomainCode>,
+    /// Референс клиента (CAMT `EndToEndId`, без него — `PmtInfId`, `InstrId` или `MndtId`).
+    pub customer_ref: String,
+    /// Референс банка (CAMT `Ntry/AcctSvcrRef`, без него — `TxDtls/Refs`).
+    pub bank_ref: Option<String>,
+    /// Номер платёжного документа (CAMT `TxDtls/Refs/InstrId`, CSV `№ документа`).
+    pub document_number: Option<String>,
+    pub supplementary_details: Option<String>,
+    /// Информация для владельца счёта (MT940 `:86:`, CAMT `TxDtls/AddtlTxInf`).
+    pub additional_info: Option<String>,
+    /// Контрагент: плательщик для поступлений, получатель для списаний
+    /// (CAMT `RltdPties` и `RltdAgts`, MT940 коды `/NAME/`, `/IBAN/` и т.п. в `:86:`).
+    pub counterparty: Option<Counterparty>,
+    /// Назначение платежа (CAMT `RmtInf/Ustrd`, MT940 `/REMI/` в `:86:`).
+    pub remittance_info: Option<String>,
+    /// Референс получателя (CAMT `RmtInf/Strd/CdtrRefInf/Ref`, MT940 `/CREF/` в `:86:`).
+    pub creditor_ref: Option<String>,
+    /// Пакет, из которого выделена проводка при разворачивании пакетных `Ntry` CAMT
+    /// (`Btch/PmtInfId`, иначе `Ntry/AcctSvcrRef`, иначе номер `Ntry` в выписке). Соседние проводки одного пакета
+    /// при записи CAMT снова собираются в один `Ntry`.
+    pub batch: Option<String>,
+}
+
+impl Entry {
+    /// Назначение платежа и информация для владельца счёта через пробел, как в
+    /// [`Transaction::description`] транзакций CAMT.
+    pub fn description(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.remittance_info, &self.additional_info]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|s| !s.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+
+    /// Транзакция для сверки; `currency` — валюта выписки для проводки без своей валюты.
+    ///
+    /// Описание без назначения платежа берётся из дополнительных сведений (CAMT `AddtlNtryInf`,
+    /// MT940 `:61:`), код операции — код ISO, если он есть, иначе код SWIFT.
+    pub fn to_transaction(&self, currency: &str) -> Transaction {
+        Transaction {
+            amount: self.amount,
+            currency: self.currency.clone().unwrap_or_else(|| currency.to_string()),
+            date: self.value_date,
+            operation_type: self.debit_credit_indicator,
+            booking_date: self.booking_date,
+            value_date: Some(self.value_date),
+            description: self
+                .description()
+                .or_else(|| self.supplementary_details.clone().filter(|d| !d.is_empty())),
+            counterparty: self.counterparty.clone(),
+            creditor_ref: self.creditor_ref.clone(),
+            customer_ref: Some(self.customer_ref.clone())
+                .filter(|r| !r.is_empty() && r != "NONREF" && r != "NOTPROVIDED"),
+            bank_ref: self.bank_ref.clone(),
+            document_number: self.document_number.clone(),
+            bank_tx_code: self
+                .domain_code
+                .as_ref()
+                .map(DomainCode::to_string)
+                .or_else(|| Some(self.transaction_type_code.clone()).filter(|c| !c.is_empty())),
+            status: self.status,
+        }
+    }
+
+    /// Дополнить недостающий код операции, SWIFT или ISO, по таблице `codes`.
+    pub fn map_bank_tx_code(&mut self, codes: &BankTxCodeMap) {
+        match &self.domain_code {
+            Some(domain) if self.transaction_type_code.is_empty() => {
+                if let Some(swift) = codes.to_swift(domain) {
+                    self.transaction_type_code = swift.to_string();
+                }
+            }
+            None if !self.transaction_type_code.is_empty() => {
+                self.domain_code = codes.to_iso(&self.transaction_type_code, self.debit_credit_indicator);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Выписка по одному счёту за период.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Statement {
+    /// Референс выписки (MT940 `:20:`, CAMT `Stmt/Id`).
+    pub id: String,
+    /// Референс связанного сообщения (MT940 `:21:`, CAMT `GrpHdr/OrgnlBizQry/MsgId`).
+    pub related_ref: Option<String>,
+    pub account: Account,
+    /// Номер выписки (MT940 `:28C:`, CAMT `ElctrncSeqNb`).
+    pub statement_no: String,
+    /// Порядковый номер части выписки (MT940 `:28C:` после `/`, CAMT `LglSeqNb`).
+    pub sequence_no: Option<String>,
+    /// Время формирования (CAMT `Stmt/CreDtTm`, MT942 `:13D:`).
+    pub created: Option<DateTime<FixedOffset>>,
+    pub opening_balance: Option<Balance>,
+    pub closing_balance: Option<Balance>,
+    pub closing_available_balance: Option<Balance>,
+    pub forward_available_balance: Option<Balance>,
+    pub entries: Vec<Entry>,
+    /// Дополнительная информация по выписке.
+    pub additional_info: Option<String>,
+}
+
+impl Statement {
+    /// Валюта выписки: валюта счёта, иначе валюта входящего остатка.
+    pub fn currency(&self) -> Option<&str> {
+        self.account
+            .currency
+            .as_deref()
+            .filter(|c| !c.is_empty())
+            .or_else(|| {
+                self.opening_balance
+                    .as_ref()
+                    .map(|b| b.currency.as_str())
+                    .filter(|c| !c.is_empty())
+            })
+    }
+
+    /// Дополнить коды операций проводок, см. [`Entry::map_bank_tx_code`].
+    pub fn map_bank_tx_codes(&mut self, codes: &BankTxCodeMap) {
+        self.entries.iter_mut().for_each(|e| e.map_bank_tx_code(codes));
+    }
+}
+
+/// Набор выписок, прочитанных из одного документа.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Statements {
+    pub statements: Vec<Statement>,
+}
+
+/// Выписки, прочитанные в режиме [`ReadMode::Lenient`], и пропущенные строки.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CollectedStatements {
+    pub statements: Statements,
+    pub diagnostics: Vec<RowDiagnostic>,
+}
+
+impl Statements {
+    /// Дополнить коды операций проводок, см. [`Entry::map_bank_tx_code`].
+    ///
+    /// Чтение хранит коды как есть, при записи недостающий код берётся из встроенной таблицы;
+    /// вызов с таблицей, дополненной правилами пользователя, позволяет их переопределить.
+    pub fn map_bank_tx_codes(&mut self, codes: &BankTxCodeMap) {
+        self.statements.iter_mut().for_each(|s| s.map_bank_tx_codes(codes));
+    }
+}
+
+impl<'a> IntoIterator for &'a Statements {
+    type Item = &'a Statement;
+    type IntoIter = std::slice::Iter<'a, Statement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.statements.iter()
+    }
+}
+
+impl TransactionsReader for Statements {
+    fn read_transactions(&self, _mode: ReadMode) -> Result<CollectedTransactions, FormatError> {
+        let transactions = self
+            .statements
+            .iter()
+            .flat_map(|statement| {
+                let currency = statement.currency().unwrap_or_default();
+                statement.entries.iter().map(move |entry| entry.to_transaction(currency))
+            })
+            .collect();
+        Ok(CollectedTransactions {
+            transactions,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camt053_format::Camt053Format;
+    use crate::mt940_format::MT940Format;
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn mt940_example() -> Statements {
+        let mut file = File::open("examples/data/mt940.exmpl").unwrap();
+        MT940Format::from_read(&mut file).unwrap().into()
+    }
+
+    #[test]
+    fn mt940_reads_into_statement_model() {
+        let statements = mt940_example();
+        assert_eq!(statements.statements.len(), 1);
+
+        let st = &statements.statements[0];
+        assert_eq!(st.id, "0000000000");
+        assert_eq!(st.account.id, "NL81ASNB9999999999");
+        assert_eq!(st.currency(), Some("EUR"));
+        assert_eq!(st.statement_no, "1");
+        assert_eq!(st.sequence_no.as_deref(), Some("1"));
+        assert_eq!(st.opening_balance.as_ref().unwrap().amount, dec("444.29"));
+        assert_eq!(st.closing_balance.as_ref().unwrap().amount, dec("379.29"));
+        assert_eq!(st.entries.len(), 1);
+        assert_eq!(st.entries[0].amount, dec("65.00"));
+        assert_eq!(st.entries[0].debit_credit_indicator, DebitOrCredit::Debit);
+        assert_eq!(st.entries[0].additional_info.as_deref(), Some("NL47INGB9999999999 text"));
+    }
+
+    #[test]
+    fn mt940_transactions_match_statement_transactions() {
+        let mut file = File::open("examples/data/mt940.exmpl").unwrap();
+        let mt940 = MT940Format::from_read(&mut file).unwrap();
+        let direct = mt940.collect_transactions().unwrap();
+        assert_eq!(direct[0].bank_tx_code.as_deref(), Some("OVB"));
+
+        assert_eq!(Statements::from(mt940).collect_transactions().unwrap(), direct);
+    }
+
+    #[test]
+    fn camt053_transactions_match_statement_transactions() {
+        let xml = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02"><BkToCstmrStmt><Stmt>
+            <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy>EUR</Ccy></Acct>
+            <Ntry><Amt>10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><ValDt><Dt>2024-01-02</Dt></ValDt>
+              <AcctSvcrRef>BANK-1</AcctSvcrRef>
+              <BkTxCd><Domn><Cd>PMNT</Cd><Fmly><Cd>RCDT</Cd><SubFmlyCd>DMCT</SubFmlyCd></Fmly></Domn></BkTxCd>
+              <NtryDtls><TxDtls><Refs><InstrId>42</InstrId><EndToEndId>E2E</EndToEndId><MndtId>MAND</MndtId></Refs></TxDtls></NtryDtls>
+              <AddtlNtryInf>Entry info</AddtlNtryInf></Ntry>
+            <Ntry><Amt>5.00</Amt><CdtDbtInd>XXXX</CdtDbtInd><ValDt><Dt>2024-01-03</Dt></ValDt></Ntry>
+            </Stmt></BkToCstmrStmt></Document>"#;
+        let camt = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap();
+
+        let direct = camt.read_transactions(ReadMode::Lenient).unwrap();
+        let t = &direct.transactions[0];
+        assert_eq!(t.customer_ref.as_deref(), Some("E2E"));
+        assert_eq!(t.bank_ref.as_deref(), Some("BANK-1"));
+        assert_eq!(t.document_number.as_deref(), Some("42"));
+        assert_eq!(t.bank_tx_code.as_deref(), Some("PMNT/RCDT/DMCT"));
+        assert_eq!(t.description.as_deref(), Some("Entry info"));
+        assert_eq!(t.currency, "EUR");
+        assert_eq!(direct.diagnostics, vec![RowDiagnostic::new(2, "Ntry/CdtDbtInd", "XXXX", "ожидалось CRDT или DBIT")]);
+        assert!(camt.read_transactions(ReadMode::Strict).is_err());
+
+        let collected = camt.clone().into_statements(ReadMode::Lenient).unwrap();
+        assert_eq!(collected.diagnostics, direct.diagnostics);
+        assert_eq!(collected.statements.collect_transactions().unwrap(), direct.transactions);
+        assert_eq!(Statements::from(camt).collect_transactions().unwrap(), direct.transactions);
+    }
+
+    #[test]
+    fn statement_survives_camt053_round_trip() {
+        let statements = mt940_example();
+
+        let mut camt: Camt053Format = statements.clone().into();
+        let mut out = Vec::new();
+        camt.write_to(&mut out).unwrap();
+        let reread: Statements = Camt053Format::from_read(&mut Cursor::new(out)).unwrap().into();
+
+        let (before, after) = (&statements.statements[0], &reread.statements[0]);
+        assert_eq!(after.id, before.id);
+        assert_eq!(after.account.id, before.account.id);
+        assert_eq!(after.currency(), before.currency());
+        assert_eq!(after.opening_balance, before.opening_balance);
+        assert_eq!(after.closing_balance, before.closing_balance);
+        assert_eq!(after.entries.len(), before.entries.len());
+        assert_eq!(after.entries[0].amount, before.entries[0].amount);
+        assert_eq!(after.entries[0].value_date, before.entries[0].value_date);
+        assert_eq!(after.entries[0].additional_info, before.entries[0].additional_info);
+    }
+
+    #[test]
+    fn camt053_entries_stay_in_their_statement() {
+        let xml = r#"<BkToCstmrStmt>
+            <Stmt><Id>A</Id>
+                <Ntry><Amt Ccy="EUR">1.00</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry>
+                <Ntry><Amt Ccy="EUR">2.00</Amt><CdtDbtInd>DBIT</CdtDbtInd></Ntry>
+            </Stmt>
+            <Stmt><Id>B</Id>
+                <Ntry><Amt Ccy="USD">3.00</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry>
+            </Stmt>
+        </BkToCstmrStmt>"#;
+        let statements: Statements = Camt053Format::from_read(&mut Cursor::new(xml)).unwrap().into();
+
+        assert_eq!(statements.statements.len(), 2);
+        assert_eq!(statements.statements[0].id, "A");
+        assert_eq!(statements.statements[0].entries.len(), 2);
+        assert_eq!(statements.statements[1].id, "B");
+        assert_eq!(statements.statements[1].entries.len(), 1);
+
+        let transactions = statements.collect_transactions().unwrap();
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[2].currency, "USD");
+    }
+}
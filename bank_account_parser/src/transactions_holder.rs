@@ -1,23 +1,94 @@
 use crate::common::debit_credit::DebitOrCredit;
+use crate::error::FormatError;
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use std::fmt;
 
-#[derive(Default, PartialEq, Eq)]
+/// Контрагент по операции: плательщик для поступлений и получатель для списаний.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Counterparty {
+    pub name: Option<String>,
+    pub account: Option<String>,
+    pub inn: Option<String>,
+    /// BIC банка контрагента (CAMT `RltdAgts/DbtrAgt`, `RltdAgts/CdtrAgt`).
+    pub bic: Option<String>,
+}
+
+impl Counterparty {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.account.is_none() && self.inn.is_none() && self.bic.is_none()
+    }
+}
+
+/// Статус проводки (CAMT `Ntry/Sts`). Проводки MT940 и CSV всегда проведены.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntryStatus {
+    /// `BOOK` — проводка отражена по счёту.
+    #[default]
+    Booked,
+    /// `PDNG` (и `FUTR`) — ожидает проведения, может измениться или быть отменена.
+    Pending,
+    /// `INFO` — только для сведения, на остаток не влияет.
+    Info,
+}
+
+impl EntryStatus {
+    /// Статус по коду CAMT; неизвестные коды считаются ожидающими проведения.
+    pub fn from_code(code: &str) -> Self {
+        match code.trim() {
+            "BOOK" => EntryStatus::Booked,
+            "INFO" => EntryStatus::Info,
+            _ => EntryStatus::Pending,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            EntryStatus::Booked => "BOOK",
+            EntryStatus::Pending => "PDNG",
+            EntryStatus::Info => "INFO",
+        }
+    }
+
+    pub fn is_booked(self) -> bool {
+        self == EntryStatus::Booked
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Transaction {
     pub amount: Decimal,
     pub currency: String,
+    /// Дата операции, по которой транзакции сортируются и сопоставляются.
     pub date: NaiveDate,
     pub operation_type: DebitOrCredit,
+    /// Дата проводки (MT940 entry date, CAMT `BookgDt`, CSV `Дата проводки`).
+    pub booking_date: Option<NaiveDate>,
+    /// Дата валютирования (MT940 `:61:`, CAMT `ValDt`).
+    pub value_date: Option<NaiveDate>,
+    /// Назначение платежа (MT940 `:86:`, CAMT `AddtlTxInf`/`RmtInf`, CSV `Назначение платежа`).
+    pub description: Option<String>,
+    pub customer_ref: Option<String>,
+    pub bank_ref: Option<String>,
+    pub counterparty: Option<Counterparty>,
+    /// Структурированный референс получателя, например номер счёта на оплату
+    /// (CAMT `RmtInf/Strd/CdtrRefInf/Ref`).
+    pub creditor_ref: Option<String>,
+    /// Номер платёжного документа.
+    pub document_number: Option<String>,
+    /// Код банковской операции: код ISO вида `PMNT/RCDT/DMCT` (CAMT `BkTxCd/Domn`), иначе код SWIFT
+    /// без `N` (MT940 `:61:`, CAMT `BkTxCd/Prtry`), CSV `ВО`; см. [`crate::statement::Entry::to_transaction`].
+    pub bank_tx_code: Option<String>,
+    pub status: EntryStatus,
 }
 
 impl Transaction {
     pub fn new(a: Decimal, o: DebitOrCredit, d: NaiveDate) -> Self {
         Self {
             amount: a,
-            currency: String::new(),
             date: d,
             operation_type: o,
+            ..Default::default()
         }
     }
 }
@@ -28,26 +99,154 @@ impl fmt::Display for Transaction {
     }
 }
 
+/// Как поступать со строками выписки, которые не удалось разобрать.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Первая ошибочная строка прерывает чтение с ошибкой.
+    #[default]
+    Strict,
+    /// Ошибочные строки пропускаются и попадают в диагностику.
+    Lenient,
+}
+
+/// Проблема в одной строке (проводке) выписки.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowDiagnostic {
+    /// Номер строки файла для CSV или порядковый номер проводки для MT940/CAMT (с 1).
+    pub row: usize,
+    /// Колонка или тег, значение которого не удалось разобрать.
+    pub column: String,
+    /// Исходное значение.
+    pub raw_value: String,
+    /// Причина ошибки.
+    pub reason: String,
+}
+
+impl RowDiagnostic {
+    pub fn new(row: usize, column: &str, raw_value: &str, reason: &str) -> Self {
+        Self {
+            row,
+            column: column.to_string(),
+            raw_value: raw_value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RowDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "строка {}, {} = \"{}\": {}",
+            self.row, self.column, self.raw_value, self.reason
+        )
+    }
+}
+
+/// Результат чтения транзакций: разобранные транзакции и пропущенные строки.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CollectedTransactions {
+    pub transactions: Vec<Transaction>,
+    pub diagnostics: Vec<RowDiagnostic>,
+}
+
+impl CollectedTransactions {
+    /// Добавить результат разбора строки с учётом режима.
+    ///
+    /// В режиме [`ReadMode::Strict`] ошибка строки возвращается вызывающему,
+    /// в [`ReadMode::Lenient`] — сохраняется в `diagnostics`.
+    pub fn push(
+        &mut self,
+        row: Result<Transaction, RowDiagnostic>,
+        mode: ReadMode,
+    ) -> Result<(), RowDiagnostic> {
+        match row {
+            Ok(t) => self.transactions.push(t),
+            Err(d) if mode == ReadMode::Strict => return Err(d),
+            Err(d) => self.diagnostics.push(d),
+        }
+        Ok(())
+    }
+}
 
 pub trait TransactionsReader {
-    fn collect_transactions(&self) -> Vec<Transaction>;
+    /// Собрать транзакции.
+    ///
+    /// # Ошибки
+    /// Возвращает [`FormatError`], если данные непригодны целиком (например, нет обязательной
+    /// колонки), а в режиме [`ReadMode::Strict`] — также при первой ошибочной строке.
+    fn read_transactions(&self, mode: ReadMode) -> Result<CollectedTransactions, FormatError>;
+
+    /// Собрать транзакции в строгом режиме.
+    fn collect_transactions(&self) -> Result<Vec<Transaction>, FormatError> {
+        self.read_transactions(ReadMode::Strict).map(|c| c.transactions)
+    }
 }
 
 pub struct TransactionHolder {
     transactions: Vec<Transaction>,
+    diagnostics: Vec<RowDiagnostic>,
 }
 
-impl TransactionHolder {
-    pub fn new<T: TransactionsReader>(data: T) -> Self {
-        let mut transactions = data.collect_transactions();
+/// Транзакции, уже прочитанные из источника, например,
+/// [`crate::camt053_stream::Camt053Reader::into_transactions`].
+impl From<CollectedTransactions> for TransactionHolder {
+    fn from(value: CollectedTransactions) -> Self {
+        let CollectedTransactions {
+            mut transactions,
+            diagnostics,
+        } = value;
         transactions.sort_by_key(|x| x.date);
 
         Self {
             transactions,
+            diagnostics,
         }
     }
 }
 
+impl TransactionHolder {
+    /// Собрать транзакции в строгом режиме.
+    pub fn new<T: TransactionsReader>(data: T) -> Result<Self, FormatError> {
+        Self::with_mode(data, ReadMode::Strict)
+    }
+
+    pub fn with_mode<T: TransactionsReader>(data: T, mode: ReadMode) -> Result<Self, FormatError> {
+        data.read_transactions(mode).map(Self::from)
+    }
+
+    /// Добавить транзакции другого источника, например, всех уведомлений camt.054 за день,
+    /// чтобы сверить их с одной выпиской. Транзакции остаются отсортированными по дате.
+    pub fn merge(&mut self, other: TransactionHolder) {
+        self.transactions.extend(other.transactions);
+        self.transactions.sort_by_key(|x| x.date);
+        self.diagnostics.extend(other.diagnostics);
+    }
+
+    /// Оставить только проведённые транзакции, без ожидающих проведения и информационных.
+    pub fn retain_booked(&mut self) {
+        self.transactions.retain(|t| t.status.is_booked());
+    }
+
+    /// Строки, пропущенные при чтении в режиме [`ReadMode::Lenient`].
+    pub fn diagnostics(&self) -> &[RowDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Транзакции, отсортированные по дате.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+}
+
 
 impl<'a> IntoIterator for &'a TransactionHolder {
     type Item = &'a Transaction;
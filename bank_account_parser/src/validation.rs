@@ -0,0 +1,322 @@
+//! Проверка непрерывности остатков выписок.
+//!
+//! Для каждой выписки проверяется, что входящий остаток плюс кредиты минус дебеты равен
+//! исходящему, а для последовательности выписок по одному счёту — что исходящий остаток
+//! совпадает с входящим остатком следующей выписки и в нумерации нет пропусков.
+//!
+//! Проверка работает с формато-независимой моделью [`Statements`], поэтому применима
+//! к любому формату: `validate(&Statements::from(mt940))`.
+
+use crate::common::debit_credit::DebitOrCredit;
+use crate::statement::{Balance, Statement, Statements};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Остаток выписки, к которому относится нарушение.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceKind {
+    Opening,
+    Closing,
+}
+
+impl fmt::Display for BalanceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BalanceKind::Opening => "входящий остаток",
+            BalanceKind::Closing => "исходящий остаток",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Вид нарушения.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// В выписке нет остатка, поэтому арифметику проверить нельзя.
+    MissingBalance(BalanceKind),
+    /// Входящий остаток плюс обороты не равен исходящему.
+    BalanceMismatch { expected: Decimal, actual: Decimal },
+    /// Входящий остаток не равен исходящему остатку предыдущей выписки по счёту.
+    OpeningDiffersFromPreviousClosing { previous_closing: Decimal, opening: Decimal },
+    /// Пропущены номера выписок.
+    StatementNumberGap { expected: u64, actual: u64 },
+    /// Пропущены части выписки с тем же номером.
+    SequenceNumberGap { expected: u64, actual: u64 },
+}
+
+/// Нарушение, найденное в выписке.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Позиция выписки в [`Statements`] (с 0).
+    pub statement: usize,
+    /// Референс выписки.
+    pub statement_id: String,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "выписка {} ({}): ", self.statement + 1, self.statement_id)?;
+        match &self.kind {
+            ViolationKind::MissingBalance(kind) => write!(f, "нет остатка: {}", kind),
+            ViolationKind::BalanceMismatch { expected, actual } => write!(
+                f,
+                "исходящий остаток {} не равен входящему с учётом оборотов {}",
+                actual, expected
+            ),
+            ViolationKind::OpeningDiffersFromPreviousClosing {
+                previous_closing,
+                opening,
+            } => write!(
+                f,
+                "входящий остаток {} не равен исходящему остатку предыдущей выписки {}",
+                opening, previous_closing
+            ),
+            ViolationKind::StatementNumberGap { expected, actual } => {
+                write!(f, "номер выписки {} вместо ожидаемого {}", actual, expected)
+            }
+            ViolationKind::SequenceNumberGap { expected, actual } => {
+                write!(f, "номер части {} вместо ожидаемого {}", actual, expected)
+            }
+        }
+    }
+}
+
+/// Остаток со знаком: кредитовый положителен, дебетовый отрицателен.
+fn signed_balance(balance: &Balance) -> Decimal {
+    match balance.debit_credit_indicator {
+        DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => balance.amount,
+        DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => -balance.amount,
+    }
+}
+
+fn number(value: Option<&str>) -> Option<u64> {
+    value.and_then(|v| v.trim().parse().ok())
+}
+
+/// Проверить арифметику одной выписки: исходящий остаток равен входящему плюс обороты
+/// проведённых проводок; ожидающие и информационные проводки на остаток не влияют.
+pub fn validate_statement(index: usize, statement: &Statement) -> Vec<Violation> {
+    let violation = |kind| Violation {
+        statement: index,
+        statement_id: statement.id.clone(),
+        kind,
+    };
+
+    let (opening, closing) = match (&statement.opening_balance, &statement.closing_balance) {
+        (Some(o), Some(c)) => (o, c),
+        (o, c) => {
+            let mut violations = Vec::new();
+            if o.is_none() {
+                violations.push(violation(ViolationKind::MissingBalance(BalanceKind::Opening)));
+            }
+            if c.is_none() {
+                violations.push(violation(ViolationKind::MissingBalance(BalanceKind::Closing)));
+            }
+            return violations;
+        }
+    };
+
+    let turnover: Decimal = statement
+        .entries
+        .iter()
+        .filter(|e| e.status.is_booked())
+        .map(|e| match e.debit_credit_indicator {
+            DebitOrCredit::Credit | DebitOrCredit::ReverseCredit => e.amount,
+            DebitOrCredit::Debit | DebitOrCredit::ReverseDebit => -e.amount,
+        })
+        .sum();
+    let expected = signed_balance(opening) + turnover;
+    let actual = signed_balance(closing);
+
+    if expected == actual {
+        Vec::new()
+    } else {
+        vec![violation(ViolationKind::BalanceMismatch { expected, actual })]
+    }
+}
+
+/// Проверить выписки: арифметику каждой, а для идущих друг за другом выписок по одному
+/// счёту — совпадение остатков и отсутствие пропусков в `statement_no`/`sequence_no`.
+///
+/// Номера, которые не являются числами, не проверяются.
+pub fn validate(statements: &Statements) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut previous: HashMap<&str, &Statement> = HashMap::new();
+
+    for (index, statement) in statements.statements.iter().enumerate() {
+        violations.extend(validate_statement(index, statement));
+
+        let violation = |kind| Violation {
+            statement: index,
+            statement_id: statement.id.clone(),
+            kind,
+        };
+
+        if let Some(prev) = previous.insert(statement.account.id.as_str(), statement) {
+            if let (Some(closing), Some(opening)) = (&prev.closing_balance, &statement.opening_balance) {
+                let (previous_closing, opening) = (signed_balance(closing), signed_balance(opening));
+                if previous_closing != opening {
+                    violations.push(violation(ViolationKind::OpeningDiffersFromPreviousClosing {
+                        previous_closing,
+                        opening,
+                    }));
+                }
+            }
+
+            let prev_no = number(Some(&prev.statement_no));
+            let no = number(Some(&statement.statement_no));
+            if let (Some(prev_no), Some(no)) = (prev_no, no) {
+                if no == prev_no {
+                    let prev_seq = number(prev.sequence_no.as_deref());
+                    let seq = number(statement.sequence_no.as_deref());
+                    // после u64::MAX ожидать нечего, такой номер пропуском не считается
+                    if let (Some(expected), Some(seq)) = (prev_seq.and_then(|p| p.checked_add(1)), seq)
+                        && seq != expected
+                    {
+                        violations.push(violation(ViolationKind::SequenceNumberGap { expected, actual: seq }));
+                    }
+                } else if let Some(expected) = prev_no.checked_add(1)
+                    && no != expected
+                {
+                    violations.push(violation(ViolationKind::StatementNumberGap { expected, actual: no }));
+                }
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camt053_format::Camt053Format;
+    use crate::mt940_format::MT940Format;
+    use crate::statement::{Account, Entry};
+    use crate::transactions_holder::EntryStatus;
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn balance(amount: &str) -> Option<Balance> {
+        Some(Balance {
+            debit_credit_indicator: DebitOrCredit::Credit,
+            amount: dec(amount),
+            ..Default::default()
+        })
+    }
+
+    fn statement(no: &str, seq: &str, opening: &str, closing: &str, entries: &[(&str, DebitOrCredit)]) -> Statement {
+        Statement {
+            id: format!("{no}/{seq}"),
+            account: Account {
+                id: "ACC".to_string(),
+                currency: None,
+            },
+            statement_no: no.to_string(),
+            sequence_no: Some(seq.to_string()),
+            opening_balance: balance(opening),
+            closing_balance: balance(closing),
+            entries: entries
+                .iter()
+                .map(|(amount, dc)| Entry {
+                    amount: dec(amount),
+                    debit_credit_indicator: *dc,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn example_statements_are_consistent() {
+        let mt: Statements = MT940Format::from_read(&mut File::open("examples/data/mt940.exmpl").unwrap())
+            .unwrap()
+            .into();
+        assert_eq!(validate(&mt), vec![]);
+
+        let mut camt: Camt053Format = mt.clone().into();
+        let mut out = Vec::new();
+        camt.write_to(&mut out).unwrap();
+        let camt: Statements = Camt053Format::from_read(&mut Cursor::new(out)).unwrap().into();
+        assert_eq!(validate(&camt), vec![]);
+    }
+
+    #[test]
+    fn detects_arithmetic_error_and_missing_balance() {
+        let mut broken = statement("1", "1", "100", "60", &[("50", DebitOrCredit::Debit), ("5", DebitOrCredit::Credit)]);
+        let violations = validate_statement(0, &broken);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, ViolationKind::BalanceMismatch { expected: dec("55"), actual: dec("60") });
+
+        broken.closing_balance = None;
+        assert_eq!(
+            validate_statement(0, &broken)[0].kind,
+            ViolationKind::MissingBalance(BalanceKind::Closing)
+        );
+    }
+
+    #[test]
+    fn pending_and_info_entries_do_not_change_balance() {
+        let mut st = statement("1", "1", "100", "90", &[("10", DebitOrCredit::Debit), ("50", DebitOrCredit::Credit)]);
+        st.entries[1].status = EntryStatus::Pending;
+        assert_eq!(validate_statement(0, &st), vec![]);
+
+        st.entries[1].status = EntryStatus::Info;
+        assert_eq!(validate_statement(0, &st), vec![]);
+
+        st.entries[1].status = EntryStatus::Booked;
+        assert_eq!(
+            validate_statement(0, &st)[0].kind,
+            ViolationKind::BalanceMismatch { expected: dec("140"), actual: dec("90") }
+        );
+    }
+
+    #[test]
+    fn checks_continuity_and_numbering_across_statements() {
+        let statements = Statements {
+            statements: vec![
+                statement("1", "1", "100", "100", &[]),
+                statement("1", "2", "100", "110", &[("10", DebitOrCredit::Credit)]),
+                statement("1", "4", "110", "110", &[]),
+                statement("3", "1", "120", "120", &[]),
+            ],
+        };
+
+        let kinds: Vec<(usize, ViolationKind)> =
+            validate(&statements).into_iter().map(|v| (v.statement, v.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (2, ViolationKind::SequenceNumberGap { expected: 3, actual: 4 }),
+                (
+                    3,
+                    ViolationKind::OpeningDiffersFromPreviousClosing {
+                        previous_closing: dec("110"),
+                        opening: dec("120"),
+                    }
+                ),
+                (3, ViolationKind::StatementNumberGap { expected: 2, actual: 3 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn largest_numbers_do_not_overflow() {
+        let max = u64::MAX.to_string();
+        let statements = Statements {
+            statements: vec![
+                statement(&max, &max, "100", "100", &[]),
+                statement(&max, "1", "100", "100", &[]),
+                statement("1", "1", "100", "100", &[]),
+            ],
+        };
+        assert_eq!(validate(&statements), vec![]);
+    }
+}
@@ -1,18 +1,32 @@
+mod report;
+
 use std::{io};
 use std::fs::File;
+use std::io::Write;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
 use anyhow::{bail, Result};
-use bank_account_parser::camt053_format::Camt053Format;
+use bank_account_parser::camt052_format::Camt052Format;
+use bank_account_parser::camt053_stream::Camt053Reader;
+use bank_account_parser::camt054_format::Camt054Format;
 use bank_account_parser::csv_format::CSVFormat;
+use bank_account_parser::format_detection::{open_any_with, OpenOptions};
 use bank_account_parser::mt940_format::MT940Format;
-use bank_account_parser::transactions_holder::TransactionHolder;
+use bank_account_parser::reconcile::{reconcile_with, MatchRules, MAX_DATE_WINDOW_DAYS, MAX_GROUP_SIZE};
+use bank_account_parser::transactions_holder::{ReadMode, TransactionHolder};
 use clap::{Parser, ValueEnum};
+use rust_decimal::Decimal;
+
+use crate::report::{Report, ReportFormat};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum InputFormat {
     Mt940,
+    Camt052,
     Camt053,
+    Camt054,
     CSV
 }
 
@@ -20,59 +34,187 @@ enum InputFormat {
 #[command(
     name = "comparer",
     version,
-    about = "Bank statement converter (prints result to stdout)"
+    about = "Bank statement comparer (prints report to stdout)"
 )]
 struct Cli {
-    #[arg(long)]
-    file1: PathBuf,
+    /// Первый файл; можно указать несколько, например, все уведомления camt.054 за день
+    #[arg(long, required = true, num_args = 1..)]
+    file1: Vec<PathBuf>,
+    /// Формат первого файла (по умолчанию определяется по содержимому)
     #[arg(long, value_enum)]
-    file1_format: InputFormat,
+    file1_format: Option<InputFormat>,
 
-    #[arg(long)]
-    file2: PathBuf,
+    /// Второй файл; как и первых, их может быть несколько
+    #[arg(long, required = true, num_args = 1..)]
+    file2: Vec<PathBuf>,
+    /// Формат второго файла (по умолчанию определяется по содержимому)
     #[arg(long, value_enum)]
-    file2_format: InputFormat,
+    file2_format: Option<InputFormat>,
+
+    /// Допустимое расхождение дат в днях (не больше 366)
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_parser = clap::builder::RangedU64ValueParser::<u32>::new().range(0..=MAX_DATE_WINDOW_DAYS as u64)
+    )]
+    date_window: u32,
+    /// Допустимое абсолютное расхождение суммы
+    #[arg(long, default_value_t = Decimal::ZERO)]
+    amount_tolerance: Decimal,
+    /// Допустимое относительное расхождение суммы в процентах
+    #[arg(long, default_value_t = Decimal::ZERO)]
+    amount_tolerance_pct: Decimal,
+    /// Не сравнивать валюту
+    #[arg(long)]
+    ignore_currency: bool,
+    /// Не сравнивать тип операции (дебет/кредит)
+    #[arg(long)]
+    ignore_direction: bool,
+    /// Максимальный размер группы транзакций, сопоставляемой с одной транзакцией другого файла
+    /// (1 - групповое сопоставление отключено, не больше 5: перебор растёт комбинаторно)
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=MAX_GROUP_SIZE as u64)
+    )]
+    max_group_size: usize,
+
+    /// Пропускать строки, которые не удалось разобрать, вместо завершения с ошибкой
+    #[arg(long)]
+    lenient: bool,
+    /// Разворачивать пакетные проводки CAMT (несколько `TxDtls` в одном `Ntry`) в отдельные
+    /// транзакции, чтобы сверять зарплатные и инкассовые пакеты построчно
+    #[arg(long)]
+    expand_batches: bool,
+    /// Сверять только проведённые проводки: ожидающие проведения и информационные
+    /// (CAMT `Sts` `PDNG`, `INFO`) пропускаются
+    #[arg(long)]
+    booked_only: bool,
+
+    /// Формат отчёта
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+    /// Файл для записи отчёта (по умолчанию stdout)
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+impl Cli {
+    fn match_rules(&self) -> MatchRules {
+        MatchRules {
+            date_window_days: self.date_window,
+            amount_tolerance: self.amount_tolerance,
+            amount_tolerance_ratio: self.amount_tolerance_pct / Decimal::ONE_HUNDRED,
+            check_currency: !self.ignore_currency,
+            check_direction: !self.ignore_direction,
+            max_group_size: self.max_group_size,
+        }
+    }
+
+    fn read_mode(&self) -> ReadMode {
+        if self.lenient {
+            ReadMode::Lenient
+        } else {
+            ReadMode::Strict
+        }
+    }
 }
 
 
-fn main() -> Result<()> {
+/// Транзакции в файлах совпадают.
+const EXIT_IDENTICAL: u8 = 0;
+/// Найдены расхождения.
+const EXIT_DIFFERENT: u8 = 1;
+/// Не удалось прочитать входные данные или записать отчёт.
+const EXIT_INPUT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    let get_holder = |f: InputFormat, b: &PathBuf| -> Result<TransactionHolder> {
-        let res = match f {
-            InputFormat::Mt940 => holder_4_mt940(b)?,
-            InputFormat::Camt053 => holder_4_camt053(b)?,
-            InputFormat::CSV => holder_4_csv(b)?,
+    let result = run(&cli);
+    if let Err(e) = &result {
+        eprintln!("Ошибка: {e}");
+    }
+    ExitCode::from(exit_code(&result))
+}
+
+/// Код завершения по результату [`run`].
+fn exit_code(result: &Result<bool>) -> u8 {
+    match result {
+        Ok(true) => EXIT_IDENTICAL,
+        Ok(false) => EXIT_DIFFERENT,
+        Err(_) => EXIT_INPUT_ERROR,
+    }
+}
+
+fn run(cli: &Cli) -> Result<bool> {
+    let mode = cli.read_mode();
+    let expand = cli.expand_batches;
+    let get_holder = |f: Option<InputFormat>, b: &PathBuf| -> Result<TransactionHolder> {
+        let mut res = match f {
+            Some(InputFormat::Mt940) => holder_4_mt940(b, mode)?,
+            Some(InputFormat::Camt052) => holder_4_camt052(b, mode, expand)?,
+            Some(InputFormat::Camt053) => holder_4_camt053(b, mode, expand)?,
+            Some(InputFormat::Camt054) => holder_4_camt054(b, mode, expand)?,
+            Some(InputFormat::CSV) => holder_4_csv(b, mode)?,
+            None => match open_any_with(b, &OpenOptions { mode, expand_batches: expand }) {
+                Ok(h) => h,
+                Err(e) => bail!(e.to_string()),
+            },
         };
+        for d in res.diagnostics() {
+            eprintln!("Пропущена строка в {}: {d}", b.display());
+        }
+        if cli.booked_only {
+            res.retain_booked();
+        }
         Ok(res)
     };
 
-    let holder1 = get_holder(cli.file1_format, &cli.file1)?;
-    let mut iter_holder1 = holder1.into_iter();
+    // транзакции всех файлов одной стороны сверяются вместе
+    let get_side = |f: Option<InputFormat>, files: &[PathBuf]| -> Result<TransactionHolder> {
+        let mut files = files.iter();
+        let Some(first) = files.next() else {
+            bail!("Не указан ни один файл");
+        };
+        let mut holder = get_holder(f, first)?;
+        for file in files {
+            holder.merge(get_holder(f, file)?);
+        }
+        Ok(holder)
+    };
+    let names = |files: &[PathBuf]| {
+        files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(", ")
+    };
 
-    let holder2= get_holder(cli.file2_format, &cli.file2)?;
-    let mut iter_holder2 = holder2.into_iter();
+    let holder1 = get_side(cli.file1_format, &cli.file1)?;
+    let holder2 = get_side(cli.file2_format, &cli.file2)?;
 
-    loop {
-        if let Some(t1) = iter_holder1.next() {
-            let Some(t2) = iter_holder2.next() else {
-                bail!("Транзакция ({}) есть в {} но нет в {}", t1, cli.file1.display(), cli.file2.display());
+    let result = reconcile_with(&holder1, &holder2, &cli.match_rules());
+    let report = Report::new(
+        names(&cli.file1),
+        names(&cli.file2),
+        &holder1,
+        &holder2,
+        &result,
+    );
+
+    match &cli.output {
+        Some(path) => {
+            let Ok(file) = File::create(path) else {
+                bail!("Не удалось создать файл {}", path.display());
             };
-            if t1 == t2 {
-                continue;
-            }
-        } else if let Some(t2) = iter_holder2.next() {
-            bail!("Транзакция {} есть в {} но нет в {}", t2, cli.file2.display(), cli.file1.display())
+            let mut writer = io::BufWriter::new(file);
+            report.write(cli.report_format, &mut writer)?;
+            writer.flush()?;
         }
-        else {
-            break
-        };
+        None => report.write(cli.report_format, &mut io::stdout().lock())?,
     }
-    println!("Транзакции идентичны");
-    Ok(())
+
+    Ok(report.identical)
 }
 
-fn holder_4_mt940(input: &PathBuf) -> Result<TransactionHolder> {
+fn holder_4_mt940(input: &PathBuf, mode: ReadMode) -> Result<TransactionHolder> {
 
     let Ok(file) = File::open(input) else {
         bail!("Не удалось открыть файл {}", input.display());
@@ -85,25 +227,66 @@ fn holder_4_mt940(input: &PathBuf) -> Result<TransactionHolder> {
         Err(e) => bail!(e.to_string())
     };
 
-    Ok(TransactionHolder::new(obj))
+    match TransactionHolder::with_mode(obj, mode) {
+        Ok(h) => Ok(h),
+        Err(e) => bail!(e.to_string()),
+    }
 }
 
-fn holder_4_camt053(input: &PathBuf) -> Result<TransactionHolder> {
+fn holder_4_camt052(input: &PathBuf, mode: ReadMode, expand_batches: bool) -> Result<TransactionHolder> {
     let Ok(file) = File::open(input) else {
         bail!("Не удалось открыть файл {}", input.display());
     };
 
     let mut reader = io::BufReader::new(file);
 
-    let obj = match Camt053Format::from_read(&mut reader) {
+    let mut obj = match Camt052Format::from_read(&mut reader) {
         Ok(o) => o,
         Err(e) => bail!(e.to_string())
     };
+    obj.document_mut().set_expand_batches(expand_batches);
 
-    Ok(TransactionHolder::new(obj))
+    match TransactionHolder::with_mode(obj, mode) {
+        Ok(h) => Ok(h),
+        Err(e) => bail!(e.to_string()),
+    }
 }
 
-fn holder_4_csv(input: &PathBuf) -> Result<TransactionHolder> {
+fn holder_4_camt053(input: &PathBuf, mode: ReadMode, expand_batches: bool) -> Result<TransactionHolder> {
+    let Ok(file) = File::open(input) else {
+        bail!("Не удалось открыть файл {}", input.display());
+    };
+
+    // Проводки читаются потоком, без построения дерева документа.
+    let mut obj = Camt053Reader::new(io::BufReader::new(file));
+    obj.set_expand_batches(expand_batches);
+
+    match obj.into_transactions(mode) {
+        Ok(c) => Ok(TransactionHolder::from(c)),
+        Err(e) => bail!(e.to_string()),
+    }
+}
+
+fn holder_4_camt054(input: &PathBuf, mode: ReadMode, expand_batches: bool) -> Result<TransactionHolder> {
+    let Ok(file) = File::open(input) else {
+        bail!("Не удалось открыть файл {}", input.display());
+    };
+
+    let mut reader = io::BufReader::new(file);
+
+    let mut obj = match Camt054Format::from_read(&mut reader) {
+        Ok(o) => o,
+        Err(e) => bail!(e.to_string())
+    };
+    obj.document_mut().set_expand_batches(expand_batches);
+
+    match TransactionHolder::with_mode(obj, mode) {
+        Ok(h) => Ok(h),
+        Err(e) => bail!(e.to_string()),
+    }
+}
+
+fn holder_4_csv(input: &PathBuf, mode: ReadMode) -> Result<TransactionHolder> {
     let Ok(file) = File::open(input) else {
         bail!("Не удалось открыть файл {}", input.display());
     };
@@ -115,5 +298,70 @@ fn holder_4_csv(input: &PathBuf) -> Result<TransactionHolder> {
         Err(e) => bail!(e.to_string())
     };
 
-    Ok(TransactionHolder::new(obj))
+    match TransactionHolder::with_mode(obj, mode) {
+        Ok(h) => Ok(h),
+        Err(e) => bail!(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    const MT940: &str = "../bank_account_parser/examples/data/mt940.exmpl";
+    const CSV: &str = "../bank_account_parser/examples/data/csv.exmpl";
+
+    /// Сверить `file1` и `file2`, записав отчёт в `report` во временном каталоге.
+    fn compare(file1: &str, file2: &str, report: &str) -> Result<bool> {
+        let output = std::env::temp_dir().join(format!("comparer-{}-{report}", std::process::id()));
+        let cli = Cli::try_parse_from([
+            "comparer", "--file1", file1, "--file2", file2, "--report-format", "json",
+            "--output", output.to_str().unwrap(),
+        ])?;
+        let result = run(&cli);
+        if result.is_ok() {
+            let json: serde_json::Value = serde_json::from_reader(File::open(&output)?)?;
+            assert_eq!(json["identical"].as_bool(), result.as_ref().ok().copied());
+            std::fs::remove_file(&output)?;
+        }
+        result
+    }
+
+    #[test]
+    fn exit_codes_follow_result() {
+        assert_eq!(exit_code(&Ok(true)), 0);
+        assert_eq!(exit_code(&Ok(false)), 1);
+        assert_eq!(exit_code(&Err(anyhow!("нет файла"))), 2);
+    }
+
+    #[test]
+    fn run_reports_identical_and_different_files() {
+        assert!(compare(MT940, MT940, "same.json").unwrap());
+        assert!(!compare(MT940, CSV, "different.json").unwrap());
+
+        let missing = compare(MT940, "no-such-file.sta", "missing.json");
+        assert_eq!(exit_code(&missing), EXIT_INPUT_ERROR);
+    }
+
+    #[test]
+    fn group_size_is_limited() {
+        let parse = |size: &str| {
+            Cli::try_parse_from(["comparer", "--file1", MT940, "--file2", CSV, "--max-group-size", size])
+        };
+        assert_eq!(parse("5").unwrap().match_rules().max_group_size, MAX_GROUP_SIZE);
+        assert!(parse("6").is_err());
+        assert!(parse("0").is_err());
+    }
+
+    #[test]
+    fn date_window_is_limited() {
+        let parse = |days: &str| {
+            Cli::try_parse_from(["comparer", "--file1", MT940, "--file2", CSV, "--date-window", days])
+        };
+        assert_eq!(parse("366").unwrap().match_rules().date_window_days, MAX_DATE_WINDOW_DAYS);
+        assert!(parse("367").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("99999999999").is_err());
+    }
 }
@@ -0,0 +1,575 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+use bank_account_parser::reconcile::{Reconciliation, Verdict};
+use bank_account_parser::transactions_holder::{Transaction, TransactionHolder};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+    Md,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportTransaction {
+    pub date: String,
+    pub amount: String,
+    pub currency: String,
+    pub operation_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bank_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty_account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty_inn: Option<String>,
+    /// Статус непроведённой проводки CAMT (`PDNG`, `INFO`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+impl From<&Transaction> for ReportTransaction {
+    fn from(t: &Transaction) -> Self {
+        let counterparty = t.counterparty.clone().unwrap_or_default();
+        Self {
+            date: t.date.to_string(),
+            amount: t.amount.to_string(),
+            currency: t.currency.clone(),
+            operation_type: t.operation_type.to_string().to_string(),
+            description: t.description.clone(),
+            document_number: t.document_number.clone(),
+            customer_ref: t.customer_ref.clone(),
+            bank_ref: t.bank_ref.clone(),
+            counterparty_name: counterparty.name,
+            counterparty_account: counterparty.account,
+            counterparty_inn: counterparty.inn,
+            status: (!t.status.is_booked()).then(|| t.status.code().to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportPair {
+    #[serde(skip)]
+    pub exact: bool,
+    pub left: ReportTransaction,
+    pub right: ReportTransaction,
+    pub days_delta: i64,
+    pub amount_delta: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportGroup {
+    pub left: Vec<ReportTransaction>,
+    pub right: Vec<ReportTransaction>,
+    pub amount_delta: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportDifference {
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportNearMatch {
+    pub left: ReportTransaction,
+    pub right: ReportTransaction,
+    pub differences: Vec<ReportDifference>,
+}
+
+/// Итог по одному типу операции и валюте.
+#[derive(Debug, Serialize)]
+pub struct ReportTotal {
+    pub operation_type: String,
+    pub currency: String,
+    pub count: usize,
+    pub amount: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportSummary {
+    pub matched: usize,
+    pub grouped: usize,
+    pub near_matches: usize,
+    pub unmatched_left: usize,
+    pub unmatched_right: usize,
+    pub left_totals: Vec<ReportTotal>,
+    pub right_totals: Vec<ReportTotal>,
+}
+
+/// Полный отчёт о сверке двух файлов.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub file1: String,
+    pub file2: String,
+    pub identical: bool,
+    pub matched: Vec<ReportPair>,
+    pub grouped: Vec<ReportGroup>,
+    pub near_matches: Vec<ReportNearMatch>,
+    pub unmatched_left: Vec<ReportTransaction>,
+    pub unmatched_right: Vec<ReportTransaction>,
+    pub summary: ReportSummary,
+}
+
+fn totals(holder: &TransactionHolder) -> Vec<ReportTotal> {
+    let mut map: BTreeMap<(&str, &str), (usize, Decimal)> = BTreeMap::new();
+    for t in holder {
+        let entry = map
+            .entry((t.operation_type.to_string(), t.currency.as_str()))
+            .or_default();
+        entry.0 += 1;
+        entry.1 += t.amount;
+    }
+    map.into_iter()
+        .map(|((operation_type, currency), (count, amount))| ReportTotal {
+            operation_type: operation_type.to_string(),
+            currency: currency.to_string(),
+            count,
+            amount: amount.to_string(),
+        })
+        .collect()
+}
+
+fn to_vec(v: &[&Transaction]) -> Vec<ReportTransaction> {
+    v.iter().map(|&t| t.into()).collect()
+}
+
+impl Report {
+    pub fn new(
+        file1: String,
+        file2: String,
+        holder1: &TransactionHolder,
+        holder2: &TransactionHolder,
+        result: &Reconciliation,
+    ) -> Self {
+        Self {
+            file1,
+            file2,
+            identical: result.verdict() == Verdict::Identical,
+            matched: result
+                .matched
+                .iter()
+                .map(|p| ReportPair {
+                    exact: p.delta.is_exact(),
+                    left: p.left.into(),
+                    right: p.right.into(),
+                    days_delta: p.delta.days,
+                    amount_delta: p.delta.amount.to_string(),
+                })
+                .collect(),
+            grouped: result
+                .grouped
+                .iter()
+                .map(|g| ReportGroup {
+                    left: to_vec(&g.left),
+                    right: to_vec(&g.right),
+                    amount_delta: g.amount_delta.to_string(),
+                })
+                .collect(),
+            near_matches: result
+                .near_matches
+                .iter()
+                .map(|n| ReportNearMatch {
+                    left: n.left.into(),
+                    right: n.right.into(),
+                    differences: n
+                        .differences
+                        .iter()
+                        .map(|d| ReportDifference {
+                            field: d.field.to_string(),
+                            left: d.left.clone(),
+                            right: d.right.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            unmatched_left: to_vec(&result.unmatched_left),
+            unmatched_right: to_vec(&result.unmatched_right),
+            summary: ReportSummary {
+                matched: result.matched.len(),
+                grouped: result.grouped.len(),
+                near_matches: result.near_matches.len(),
+                unmatched_left: result.unmatched_left.len(),
+                unmatched_right: result.unmatched_right.len(),
+                left_totals: totals(holder1),
+                right_totals: totals(holder2),
+            },
+        }
+    }
+
+    pub fn write<W: Write>(&self, format: ReportFormat, w: &mut W) -> Result<()> {
+        match format {
+            ReportFormat::Text => self.write_text(w),
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut *w, self)?;
+                writeln!(w)?;
+                Ok(())
+            }
+            ReportFormat::Csv => self.write_csv(w),
+            ReportFormat::Md => self.write_md(w),
+        }
+    }
+
+    fn write_text<W: Write>(&self, w: &mut W) -> Result<()> {
+        let show = |t: &ReportTransaction| format!("{} от {}", t.amount, t.date);
+        let join = |v: &[ReportTransaction]| v.iter().map(show).collect::<Vec<_>>().join(", ");
+
+        writeln!(w, "Совпало транзакций: {}, групп: {}", self.matched.len(), self.grouped.len())?;
+        for pair in self.matched.iter().filter(|p| !p.exact) {
+            writeln!(
+                w,
+                "Транзакция ({}) из {} сопоставлена с ({}) из {}: расхождение дат {} дн., суммы {}",
+                show(&pair.left), self.file1, show(&pair.right), self.file2, pair.days_delta, pair.amount_delta
+            )?;
+        }
+        for group in &self.grouped {
+            writeln!(
+                w,
+                "Транзакции ({}) из {} сопоставлены с ({}) из {}: расхождение суммы {}",
+                join(&group.left), self.file1, join(&group.right), self.file2, group.amount_delta
+            )?;
+        }
+        for near in &self.near_matches {
+            let diff: Vec<String> = near
+                .differences
+                .iter()
+                .map(|d| format!("{}: {} / {}", d.field, d.left, d.right))
+                .collect();
+            writeln!(
+                w,
+                "Транзакция ({}) из {} отличается от ({}) из {}: {}",
+                show(&near.left), self.file1, show(&near.right), self.file2, diff.join("; ")
+            )?;
+        }
+        for t in &self.unmatched_left {
+            writeln!(w, "Транзакция ({}) есть в {} но нет в {}", show(t), self.file1, self.file2)?;
+        }
+        for t in &self.unmatched_right {
+            writeln!(w, "Транзакция ({}) есть в {} но нет в {}", show(t), self.file2, self.file1)?;
+        }
+
+        if self.identical {
+            writeln!(w, "Транзакции идентичны")?;
+        } else {
+            writeln!(
+                w,
+                "Найдены расхождения: отличающихся пар {}, только в {} - {}, только в {} - {}",
+                self.near_matches.len(),
+                self.file1,
+                self.unmatched_left.len(),
+                self.file2,
+                self.unmatched_right.len()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Таблица csv: одна строка на транзакцию или пару транзакций.
+    ///
+    /// Колонка `kind` принимает значения `matched`, `grouped`, `near_match`, `unmatched_left`,
+    /// `unmatched_right`, а также `total_left`/`total_right` для итогов по типу операции и валюте.
+    /// Строки одной группы объединяются номером в колонке `group`.
+    fn write_csv<W: Write>(&self, w: &mut W) -> Result<()> {
+        let mut wtr = csv::Writer::from_writer(w);
+        wtr.write_record([
+            "kind", "group",
+            "left_date", "left_amount", "left_currency", "left_operation",
+            "right_date", "right_amount", "right_currency", "right_operation",
+            "details",
+        ])?;
+
+        let empty = ["", "", "", ""];
+        let cells = |t: &ReportTransaction| [t.date.clone(), t.amount.clone(), t.currency.clone(), t.operation_type.clone()];
+        let mut row = |kind: &str, group: String, left: Option<&ReportTransaction>, right: Option<&ReportTransaction>, details: String| -> Result<()> {
+            let mut record = vec![kind.to_string(), group];
+            match left {
+                Some(t) => record.extend(cells(t)),
+                None => record.extend(empty.map(String::from)),
+            }
+            match right {
+                Some(t) => record.extend(cells(t)),
+                None => record.extend(empty.map(String::from)),
+            }
+            record.push(details);
+            wtr.write_record(&record)?;
+            Ok(())
+        };
+
+        for p in &self.matched {
+            row("matched", String::new(), Some(&p.left), Some(&p.right), format!("days={};amount={}", p.days_delta, p.amount_delta))?;
+        }
+        for (index, g) in self.grouped.iter().enumerate() {
+            let details = format!("amount={}", g.amount_delta);
+            for t in &g.left {
+                row("grouped", (index + 1).to_string(), Some(t), None, details.clone())?;
+            }
+            for t in &g.right {
+                row("grouped", (index + 1).to_string(), None, Some(t), details.clone())?;
+            }
+        }
+        for n in &self.near_matches {
+            let details: Vec<String> = n.differences.iter().map(|d| d.field.clone()).collect();
+            row("near_match", String::new(), Some(&n.left), Some(&n.right), details.join(";"))?;
+        }
+        for t in &self.unmatched_left {
+            row("unmatched_left", String::new(), Some(t), None, String::new())?;
+        }
+        for t in &self.unmatched_right {
+            row("unmatched_right", String::new(), None, Some(t), String::new())?;
+        }
+
+        let total = |t: &ReportTotal| ReportTransaction {
+            date: String::new(),
+            amount: t.amount.clone(),
+            currency: t.currency.clone(),
+            operation_type: t.operation_type.clone(),
+            description: None,
+            document_number: None,
+            customer_ref: None,
+            bank_ref: None,
+            counterparty_name: None,
+            counterparty_account: None,
+            counterparty_inn: None,
+            status: None,
+        };
+        for t in &self.summary.left_totals {
+            row("total_left", String::new(), Some(&total(t)), None, format!("count={}", t.count))?;
+        }
+        for t in &self.summary.right_totals {
+            row("total_right", String::new(), None, Some(&total(t)), format!("count={}", t.count))?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    fn write_md<W: Write>(&self, w: &mut W) -> Result<()> {
+        let show = |t: &ReportTransaction| {
+            format!("{} | {} | {} | {}", t.date, t.amount, t.currency, t.operation_type)
+        };
+
+        writeln!(w, "# Сверка {} и {}\n", self.file1, self.file2)?;
+        writeln!(w, "Результат: **{}**\n", if self.identical { "транзакции идентичны" } else { "найдены расхождения" })?;
+
+        writeln!(w, "## Итоги\n")?;
+        writeln!(w, "| Совпало | Групп | Отличаются | Только в {} | Только в {} |", self.file1, self.file2)?;
+        writeln!(w, "|---|---|---|---|---|")?;
+        writeln!(
+            w,
+            "| {} | {} | {} | {} | {} |\n",
+            self.summary.matched, self.summary.grouped, self.summary.near_matches,
+            self.summary.unmatched_left, self.summary.unmatched_right
+        )?;
+        writeln!(w, "| Файл | Операция | Валюта | Количество | Сумма |")?;
+        writeln!(w, "|---|---|---|---|---|")?;
+        for (file, list) in [(&self.file1, &self.summary.left_totals), (&self.file2, &self.summary.right_totals)] {
+            for t in list {
+                writeln!(w, "| {} | {} | {} | {} | {} |", file, t.operation_type, t.currency, t.count, t.amount)?;
+            }
+        }
+
+        if !self.matched.is_empty() {
+            writeln!(w, "\n## Совпавшие транзакции\n")?;
+            writeln!(w, "| Дата | Сумма | Валюта | Операция | Дата | Сумма | Валюта | Операция | Дни | Разница |")?;
+            writeln!(w, "|---|---|---|---|---|---|---|---|---|---|")?;
+            for p in &self.matched {
+                writeln!(w, "| {} | {} | {} | {} |", show(&p.left), show(&p.right), p.days_delta, p.amount_delta)?;
+            }
+        }
+        if !self.grouped.is_empty() {
+            writeln!(w, "\n## Сгруппированные транзакции\n")?;
+            writeln!(w, "| Группа | Файл | Дата | Сумма | Валюта | Операция |")?;
+            writeln!(w, "|---|---|---|---|---|---|")?;
+            for (index, g) in self.grouped.iter().enumerate() {
+                for t in &g.left {
+                    writeln!(w, "| {} | {} | {} |", index + 1, self.file1, show(t))?;
+                }
+                for t in &g.right {
+                    writeln!(w, "| {} | {} | {} |", index + 1, self.file2, show(t))?;
+                }
+            }
+        }
+        if !self.near_matches.is_empty() {
+            writeln!(w, "\n## Отличающиеся транзакции\n")?;
+            writeln!(w, "| Дата | Сумма | Валюта | Операция | Дата | Сумма | Валюта | Операция | Расхождения |")?;
+            writeln!(w, "|---|---|---|---|---|---|---|---|---|")?;
+            for n in &self.near_matches {
+                let diff: Vec<String> = n.differences.iter().map(|d| format!("{}: {} / {}", d.field, d.left, d.right)).collect();
+                writeln!(w, "| {} | {} | {} |", show(&n.left), show(&n.right), diff.join("; "))?;
+            }
+        }
+        for (title, list) in [(&self.file1, &self.unmatched_left), (&self.file2, &self.unmatched_right)] {
+            if list.is_empty() {
+                continue;
+            }
+            writeln!(w, "\n## Только в {}\n", title)?;
+            writeln!(w, "| Дата | Сумма | Валюта | Операция |")?;
+            writeln!(w, "|---|---|---|---|")?;
+            for t in list {
+                writeln!(w, "| {} |", show(t))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bank_account_parser::reconcile::{reconcile_with, MatchRules};
+    use bank_account_parser::transactions_holder::CollectedTransactions;
+    use std::str::FromStr;
+
+    /// Транзакция в EUR; `operation` — обозначение MT940 (`C`, `D`).
+    fn tx(amount: &str, operation: &str, date: &str) -> Transaction {
+        Transaction {
+            currency: "EUR".to_string(),
+            ..Transaction::new(Decimal::from_str(amount).unwrap(), operation.parse().unwrap(), date.parse().unwrap())
+        }
+    }
+
+    fn holder(transactions: Vec<Transaction>) -> TransactionHolder {
+        TransactionHolder::from(CollectedTransactions {
+            transactions,
+            ..Default::default()
+        })
+    }
+
+    /// Совпадение, группа, отличие суммы и по одной транзакции только в одном из файлов.
+    fn holders() -> (TransactionHolder, TransactionHolder) {
+        let left = holder(vec![
+            tx("100.00", "C", "2024-01-02"),
+            tx("50.00", "D", "2024-01-03"),
+            Transaction {
+                currency: "\"RUB\",old".to_string(),
+                ..tx("7.00", "D", "2024-01-05")
+            },
+            tx("30.00", "C", "2024-01-10"),
+            tx("20.00", "C", "2024-01-10"),
+        ]);
+        let right = holder(vec![
+            tx("100.00", "C", "2024-01-02"),
+            tx("51.00", "D", "2024-01-03"),
+            tx("50.00", "C", "2024-01-10"),
+            tx("9.00", "C", "2024-02-01"),
+        ]);
+        (left, right)
+    }
+
+    fn write(left: &TransactionHolder, right: &TransactionHolder, format: ReportFormat) -> String {
+        let rules = MatchRules {
+            max_group_size: 2,
+            ..Default::default()
+        };
+        let result = reconcile_with(left, right, &rules);
+        let report = Report::new("a.sta".to_string(), "b.xml".to_string(), left, right, &result);
+        let mut out = Vec::new();
+        report.write(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json_report_has_all_sections_and_summary() {
+        let (left, right) = holders();
+        let json: serde_json::Value = serde_json::from_str(&write(&left, &right, ReportFormat::Json)).unwrap();
+
+        assert_eq!(json["file1"], "a.sta");
+        assert_eq!(json["identical"], false);
+        assert_eq!(json["matched"][0]["left"]["amount"], "100.00");
+        assert_eq!(json["matched"][0]["days_delta"], 0);
+        assert!(json["matched"][0].get("exact").is_none());
+        assert!(json["matched"][0]["left"].get("status").is_none());
+        assert_eq!(json["grouped"][0]["left"].as_array().unwrap().len(), 2);
+        assert_eq!(json["grouped"][0]["amount_delta"], "0.00");
+        assert_eq!(json["near_matches"][0]["differences"][0]["field"], "сумма");
+        assert_eq!(json["unmatched_left"][0]["amount"], "7.00");
+        assert_eq!(json["unmatched_right"][0]["amount"], "9.00");
+
+        let summary = &json["summary"];
+        let counts: Vec<u64> = ["matched", "grouped", "near_matches", "unmatched_left", "unmatched_right"]
+            .iter()
+            .map(|k| summary[k].as_u64().unwrap())
+            .collect();
+        assert_eq!(counts, [1, 1, 1, 1, 1]);
+        assert_eq!(summary["left_totals"][0]["operation_type"], "C");
+        assert_eq!(summary["left_totals"][0]["count"], 3);
+        assert_eq!(summary["left_totals"][0]["amount"], "150.00");
+    }
+
+    #[test]
+    fn csv_report_has_one_row_per_transaction_and_escapes_cells() {
+        let (left, right) = holders();
+        let csv = write(&left, &right, ReportFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "kind,group,left_date,left_amount,left_currency,left_operation,right_date,right_amount,right_currency,right_operation,details"
+        );
+        assert_eq!(lines[1], "matched,,2024-01-02,100.00,EUR,C,2024-01-02,100.00,EUR,C,days=0;amount=0.00");
+        assert_eq!(lines[2], "grouped,1,2024-01-10,20.00,EUR,C,,,,,amount=0.00");
+        assert_eq!(lines[3], "grouped,1,2024-01-10,30.00,EUR,C,,,,,amount=0.00");
+        assert_eq!(lines[4], "grouped,1,,,,,2024-01-10,50.00,EUR,C,amount=0.00");
+        assert_eq!(lines[5], "near_match,,2024-01-03,50.00,EUR,D,2024-01-03,51.00,EUR,D,сумма");
+        assert_eq!(lines[6], r#"unmatched_left,,2024-01-05,7.00,"""RUB"",old",D,,,,,"#);
+        assert_eq!(lines[7], "unmatched_right,,,,,,2024-02-01,9.00,EUR,C,");
+        assert!(lines[8..].iter().all(|l| l.starts_with("total_left,") || l.starts_with("total_right,")));
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert!(records.iter().all(|r| r.len() == 11));
+        assert_eq!(&records[5][4], "\"RUB\",old");
+    }
+
+    #[test]
+    fn markdown_report_tables_are_well_formed() {
+        let (left, right) = holders();
+        let md = write(&left, &right, ReportFormat::Md);
+
+        assert!(md.starts_with("# Сверка a.sta и b.xml\n\nРезультат: **найдены расхождения**\n"));
+        for section in ["## Итоги", "## Совпавшие транзакции", "## Сгруппированные транзакции",
+            "## Отличающиеся транзакции", "## Только в a.sta", "## Только в b.xml"] {
+            assert!(md.contains(section), "{section}");
+        }
+        assert!(md.contains("| 1 | 1 | 1 | 1 | 1 |"));
+        assert!(md.contains("| 2024-01-03 | 50.00 | EUR | D | 2024-01-03 | 51.00 | EUR | D | сумма: 50.00 / 51.00 |"));
+
+        // в каждой таблице у всех строк столько же колонок, сколько в заголовке
+        let mut width = None;
+        for line in md.lines() {
+            if !line.starts_with('|') {
+                width = None;
+                continue;
+            }
+            let cells = line.matches('|').count();
+            assert_eq!(*width.get_or_insert(cells), cells, "{line}");
+        }
+
+        let same = write(&left, &left, ReportFormat::Md);
+        assert!(same.contains("Результат: **транзакции идентичны**"));
+        assert!(!same.contains("## Только в"));
+    }
+
+    #[test]
+    fn text_report_ends_with_verdict() {
+        let (left, right) = holders();
+        let text = write(&left, &right, ReportFormat::Text);
+        assert!(text.starts_with("Совпало транзакций: 1, групп: 1\n"));
+        assert!(text.ends_with("Найдены расхождения: отличающихся пар 1, только в a.sta - 1, только в b.xml - 1\n"));
+        assert!(write(&left, &left, ReportFormat::Text).ends_with("Транзакции идентичны\n"));
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use anyhow::Result;
+use bank_account_parser::statement::{Balance, Entry, Statement, Statements};
+use bank_account_parser::transactions_holder::Counterparty;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct JsonBalance {
+    pub date: String,
+    pub amount: String,
+    pub currency: String,
+    pub operation_type: String,
+}
+
+impl From<&Balance> for JsonBalance {
+    fn from(b: &Balance) -> Self {
+        Self {
+            date: b.date.to_string(),
+            amount: b.amount.to_string(),
+            currency: b.currency.clone(),
+            operation_type: b.debit_credit_indicator.to_string().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonCounterparty {
+    pub name: Option<String>,
+    pub account: Option<String>,
+    pub inn: Option<String>,
+    pub bic: Option<String>,
+}
+
+impl From<&Counterparty> for JsonCounterparty {
+    fn from(c: &Counterparty) -> Self {
+        Self {
+            name: c.name.clone(),
+            account: c.account.clone(),
+            inn: c.inn.clone(),
+            bic: c.bic.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonEntry {
+    pub value_date: String,
+    pub booking_date: Option<String>,
+    pub amount: String,
+    pub currency: Option<String>,
+    pub operation_type: String,
+    pub status: String,
+    pub transaction_type: String,
+    pub customer_ref: String,
+    pub bank_ref: Option<String>,
+    pub supplementary_details: Option<String>,
+    pub information: Option<String>,
+    pub counterparty: Option<JsonCounterparty>,
+    pub creditor_ref: Option<String>,
+}
+
+impl From<&Entry> for JsonEntry {
+    fn from(e: &Entry) -> Self {
+        Self {
+            value_date: e.value_date.to_string(),
+            booking_date: e.booking_date.map(|d| d.to_string()),
+            amount: e.amount.to_string(),
+            currency: e.currency.clone(),
+            operation_type: e.debit_credit_indicator.to_string().to_string(),
+            status: e.status.code().to_string(),
+            transaction_type: e.transaction_type_code.clone(),
+            customer_ref: e.customer_ref.clone(),
+            bank_ref: e.bank_ref.clone(),
+            supplementary_details: e.supplementary_details.clone(),
+            information: e.description(),
+            counterparty: e.counterparty.as_ref().map(Into::into),
+            creditor_ref: e.creditor_ref.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonStatement {
+    pub reference: String,
+    pub related_reference: Option<String>,
+    pub account: String,
+    pub currency: Option<String>,
+    pub statement_no: String,
+    pub sequence_no: Option<String>,
+    pub opening_balance: Option<JsonBalance>,
+    pub closing_balance: Option<JsonBalance>,
+    pub closing_available_balance: Option<JsonBalance>,
+    pub forward_available_balance: Option<JsonBalance>,
+    pub entries: Vec<JsonEntry>,
+    pub information: Option<String>,
+}
+
+impl From<&Statement> for JsonStatement {
+    fn from(s: &Statement) -> Self {
+        Self {
+            reference: s.id.clone(),
+            related_reference: s.related_ref.clone(),
+            account: s.account.id.clone(),
+            currency: s.currency().map(str::to_string),
+            statement_no: s.statement_no.clone(),
+            sequence_no: s.sequence_no.clone(),
+            opening_balance: s.opening_balance.as_ref().map(Into::into),
+            closing_balance: s.closing_balance.as_ref().map(Into::into),
+            closing_available_balance: s.closing_available_balance.as_ref().map(Into::into),
+            forward_available_balance: s.forward_available_balance.as_ref().map(Into::into),
+            entries: s.entries.iter().map(Into::into).collect(),
+            information: s.additional_info.clone(),
+        }
+    }
+}
+
+/// Записать выписки в виде JSON-массива.
+pub fn write_json<W: Write>(statements: &Statements, w: &mut W) -> Result<()> {
+    let statements: Vec<JsonStatement> = statements.into_iter().map(Into::into).collect();
+    serde_json::to_writer_pretty(&mut *w, &statements)?;
+    writeln!(w)?;
+    Ok(())
+}
@@ -1,91 +1,423 @@
-use std::{io};
+mod json_output;
+
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Result};
+use anyhow::{Context, Result};
 use clap::{Parser, ValueEnum};
 
-use bank_account_parser::camt053_format::Camt053Format;
+use bank_account_parser::bank_tx_code::BankTxCodeMap;
+use bank_account_parser::camt052_format::Camt052Format;
+use bank_account_parser::camt053_format::{Camt053Format, Camt053Version, CamtMessage, XmlWriteOptions};
+use bank_account_parser::camt053_schema;
+use bank_account_parser::camt053_stream::Camt053Reader;
+use bank_account_parser::camt054_format::Camt054Format;
+use bank_account_parser::csv_format::CSVFormat;
+use bank_account_parser::format_detection::{detect_file_format, StatementFormat};
 use bank_account_parser::mt940_format::MT940Format;
+use bank_account_parser::mt942_format::MT942Format;
+use bank_account_parser::statement::{CollectedStatements, Statements};
+use bank_account_parser::transactions_holder::ReadMode;
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 enum InputFormat {
     Mt940,
+    Camt052,
+    Camt053,
+    Camt054,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Mt940,
+    Mt942,
+    Camt052,
     Camt053,
+    Camt054,
+    Csv,
+    Json,
 }
 
 #[derive(Debug, Parser)]
 #[command(
     name = "converter",
     version,
-    about = "Bank statement converter (prints result to stdout)"
+    about = "Bank statement converter (prints result to stdout or --output file)"
 )]
 struct Cli {
     #[arg(long)]
     input: PathBuf,
 
+    /// Формат входного файла (по умолчанию определяется по содержимому)
     #[arg(long, value_enum)]
-    input_format: InputFormat,
+    input_format: Option<InputFormat>,
+
+    /// Формат результата (по умолчанию MT940 -> CAMT.053, camt.052 -> MT942, остальные -> MT940)
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+
+    /// Файл для результата (по умолчанию stdout)
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Версия CAMT.053 (camt.052, camt.054) для результата, например 001.08 (по умолчанию версия исходного файла)
+    #[arg(long)]
+    camt053_version: Option<Camt053Version>,
+
+    /// Отступ вложенных тегов CAMT.053 в пробелах (по умолчанию документ пишется в одну строку)
+    #[arg(long, default_value_t = 0)]
+    indent: usize,
+
+    /// Записать CAMT.053 в каноническом виде: атрибуты по алфавиту, пробелы в тексте схлопнуты
+    #[arg(long)]
+    canonical: bool,
+
+    /// Не писать XML-декларацию в CAMT.053
+    #[arg(long)]
+    no_xml_declaration: bool,
+
+    /// Разворачивать пакетные проводки CAMT (несколько `TxDtls` в одном `Ntry`) в отдельные
+    /// проводки; при записи CAMT проводки одного пакета снова собираются в один `Ntry`
+    #[arg(long)]
+    expand_batches: bool,
+
+    /// Файл правил соответствия кодов операций ISO 20022 и SWIFT вида `PMNT/RCDT/DMCT=TRF`,
+    /// дополняющих и переопределяющих встроенную таблицу
+    #[arg(long)]
+    bank_tx_codes: Option<PathBuf>,
+
+    /// Пропускать строки CSV и проводки CAMT, которые не удалось разобрать, вместо завершения с ошибкой
+    #[arg(long)]
+    lenient: bool,
 }
 
+impl Cli {
+    fn xml_options(&self) -> XmlWriteOptions {
+        XmlWriteOptions {
+            indent: self.indent,
+            declaration: self.no_xml_declaration.then_some(false),
+            canonical: self.canonical,
+            ..Default::default()
+        }
+    }
+
+    fn bank_tx_codes(&self) -> Result<BankTxCodeMap> {
+        let Some(path) = &self.bank_tx_codes else {
+            return Ok(BankTxCodeMap::default());
+        };
+        let mut file = File::open(path)
+            .with_context(|| format!("Не удалось открыть файл {}", path.display()))?;
+        Ok(BankTxCodeMap::from_read(&mut file)?)
+    }
+
+    fn read_mode(&self) -> ReadMode {
+        if self.lenient {
+            ReadMode::Lenient
+        } else {
+            ReadMode::Strict
+        }
+    }
+}
+
+/// Разобранный входной документ.
+enum Document {
+    Mt940(MT940Format),
+    Camt052(Camt052Format),
+    Camt053(Camt053Format),
+    Camt054(Camt054Format),
+    Csv(CSVFormat),
+}
+
+impl Document {
+    fn format(&self) -> OutputFormat {
+        match self {
+            Document::Mt940(_) => OutputFormat::Mt940,
+            Document::Camt052(_) => OutputFormat::Camt052,
+            Document::Camt053(_) => OutputFormat::Camt053,
+            Document::Camt054(_) => OutputFormat::Camt054,
+            Document::Csv(_) => OutputFormat::Csv,
+        }
+    }
+
+    fn into_statements(self, mode: ReadMode) -> Result<CollectedStatements> {
+        Ok(match self {
+            Document::Mt940(mt) => CollectedStatements {
+                statements: mt.into(),
+                diagnostics: Vec::new(),
+            },
+            Document::Camt052(camt) => camt.into_statements(mode)?,
+            Document::Camt053(camt) => camt.into_statements(mode)?,
+            Document::Camt054(camt) => camt.into_statements(mode)?,
+            Document::Csv(csv) => csv.into_statements(mode)?,
+        })
+    }
+
+    fn write_as<W: Write>(self, format: OutputFormat, cli: &Cli, codes: &BankTxCodeMap, w: &mut W) -> Result<()> {
+        let write_camt = |mut camt: Camt053Format, w: &mut W| {
+            if let Some(version) = cli.camt053_version {
+                camt.set_message_version(camt.message().unwrap_or(CamtMessage::Statement), version);
+            }
+            camt.write_with(w, &cli.xml_options())
+        };
+
+        // без пропуска строк и своих кодов операций документ того же формата пишется как прочитан
+        if format == self.format() && !cli.lenient && cli.bank_tx_codes.is_none() {
+            match self {
+                Document::Mt940(mut mt) => mt.write_to(w)?,
+                Document::Camt052(camt) => write_camt(camt.into_document(), w)?,
+                Document::Camt053(camt) => write_camt(camt, w)?,
+                Document::Camt054(camt) => write_camt(camt.into_document(), w)?,
+                Document::Csv(mut csv) => csv.write_to(w)?,
+            }
+            return Ok(());
+        }
+
+        let CollectedStatements { mut statements, diagnostics } = self.into_statements(cli.read_mode())?;
+        for d in diagnostics {
+            eprintln!("Пропущена строка в {}: {d}", cli.input.display());
+        }
+        statements.map_bank_tx_codes(codes);
+        match format {
+            OutputFormat::Mt940 => MT940Format::from(statements).write_to(w)?,
+            OutputFormat::Mt942 => MT942Format::from(statements).write_to(w)?,
+            OutputFormat::Camt052 | OutputFormat::Camt053 | OutputFormat::Camt054 => {
+                let camt = match format {
+                    OutputFormat::Camt052 => Camt052Format::from(statements).into_document(),
+                    OutputFormat::Camt054 => Camt054Format::from(statements).into_document(),
+                    _ => Camt053Format::from(statements),
+                };
+                for issue in camt053_schema::validate(&camt) {
+                    eprintln!("Предупреждение: {}", issue);
+                }
+                write_camt(camt, w)?
+            }
+            OutputFormat::Csv => CSVFormat::from(statements).write_to(w)?,
+            OutputFormat::Json => json_output::write_json(&statements, w)?,
+        }
+        Ok(())
+    }
+}
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let output = match cli.input_format {
-        InputFormat::Mt940 => convert_mt940(&cli.input),
-        InputFormat::Camt053 => convert_camt053(&cli.input),
+    let input_format = match cli.input_format {
+        Some(f) => f,
+        None => match detect_file_format(&cli.input)? {
+            StatementFormat::Mt940 => InputFormat::Mt940,
+            StatementFormat::Camt052 => InputFormat::Camt052,
+            StatementFormat::Camt053 => InputFormat::Camt053,
+            StatementFormat::Camt054 => InputFormat::Camt054,
+            StatementFormat::Csv => InputFormat::Csv,
+        },
     };
-    let Ok(output) = output else {
-        bail!("Не удалось выполнить конвертацию форматов");
-    };
-    println!("\n{output}");
+    let output_format = cli.output_format.unwrap_or(match input_format {
+        InputFormat::Mt940 => OutputFormat::Camt053,
+        InputFormat::Camt052 => OutputFormat::Mt942,
+        _ => OutputFormat::Mt940,
+    });
+
+    match &cli.output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Не удалось создать файл {}", path.display()))?;
+            let mut writer = BufWriter::new(file);
+            convert(&cli, input_format, output_format, &mut writer)?;
+            writer.flush()?;
+        }
+        None => {
+            let mut writer = io::stdout().lock();
+            convert(&cli, input_format, output_format, &mut writer)?;
+            writer.flush()?;
+        }
+    }
+
+    eprintln!(
+        "{:?}({}) конвертирован в {:?}{}",
+        input_format,
+        cli.input.display(),
+        output_format,
+        cli.output
+            .as_ref()
+            .map(|p| format!(" ({})", p.display()))
+            .unwrap_or_default()
+    );
     Ok(())
 }
 
-fn convert_mt940(input: &PathBuf) -> Result<String> {
-    let Ok(file) = File::open(input) else {
-        bail!("Не удалось открыть файл {}", input.display())
-    };
+fn convert<W: Write>(cli: &Cli, input_format: InputFormat, output_format: OutputFormat, w: &mut W) -> Result<()> {
+    let camt_input = matches!(input_format, InputFormat::Camt052 | InputFormat::Camt053 | InputFormat::Camt054);
+    let camt_output = matches!(output_format, OutputFormat::Camt052 | OutputFormat::Camt053 | OutputFormat::Camt054);
+    let codes = cli.bank_tx_codes()?;
+    if camt_input && !camt_output {
+        return convert_camt053_stream(cli, output_format, &codes, w);
+    }
+    read_document(&cli.input, input_format, cli.expand_batches)?.write_as(output_format, cli, &codes, w)
+}
+
+/// CAMT.053 (camt.052, camt.054) в другой формат без построения дерева документа: в MT940 и MT942
+/// выписки пишутся по мере чтения, для CSV и JSON собираются только выписки с проводками.
+fn convert_camt053_stream<W: Write>(cli: &Cli, format: OutputFormat, codes: &BankTxCodeMap, w: &mut W) -> Result<()> {
+    let file = File::open(&cli.input)
+        .with_context(|| format!("Не удалось открыть файл {}", cli.input.display()))?;
+    let mut reader = Camt053Reader::new(io::BufReader::new(file));
+    reader.set_expand_batches(cli.expand_batches);
+    reader.set_read_mode(cli.read_mode());
+
+    let mut statements = Vec::new();
+    while let Some(mut statement) = reader.next_statement()? {
+        for d in reader.take_diagnostics() {
+            eprintln!("Пропущена строка в {}: {d}", cli.input.display());
+        }
+        statement.map_bank_tx_codes(codes);
+        match format {
+            OutputFormat::Mt940 => MT940Format::from(Statements { statements: vec![statement] }).write_to(w)?,
+            OutputFormat::Mt942 => MT942Format::from(Statements { statements: vec![statement] }).write_to(w)?,
+            _ => statements.push(statement),
+        }
+    }
+
+    let statements = Statements { statements };
+    match format {
+        OutputFormat::Csv => CSVFormat::from(statements).write_to(w)?,
+        OutputFormat::Json => json_output::write_json(&statements, w)?,
+        OutputFormat::Mt940
+        | OutputFormat::Mt942
+        | OutputFormat::Camt052
+        | OutputFormat::Camt053
+        | OutputFormat::Camt054 => (),
+    }
+    Ok(())
+}
 
+fn read_document(input: &Path, format: InputFormat, expand_batches: bool) -> Result<Document> {
+    let file = File::open(input)
+        .with_context(|| format!("Не удалось открыть файл {}", input.display()))?;
     let mut reader = io::BufReader::new(file);
 
-    let mt = match MT940Format::from_read(&mut reader) {
-        Ok(c) => c,
-        Err(e) => bail!(e.to_string())
+    let mut document = match format {
+        InputFormat::Mt940 => Document::Mt940(MT940Format::from_read(&mut reader)?),
+        InputFormat::Camt052 => Document::Camt052(Camt052Format::from_read(&mut reader)?),
+        InputFormat::Camt053 => Document::Camt053(Camt053Format::from_read(&mut reader)?),
+        InputFormat::Camt054 => Document::Camt054(Camt054Format::from_read(&mut reader)?),
+        InputFormat::Csv => Document::Csv(CSVFormat::from_read(&mut reader)?),
+    };
+    let camt = match &mut document {
+        Document::Camt052(camt) => Some(camt.document_mut()),
+        Document::Camt053(camt) => Some(camt),
+        Document::Camt054(camt) => Some(camt.document_mut()),
+        Document::Mt940(_) | Document::Csv(_) => None,
     };
+    if let Some(camt) = camt {
+        camt.set_expand_batches(expand_batches);
+    }
+    Ok(document)
+}
 
-    let mut camt: Camt053Format = mt.into();
-    let mut out = io::stdout();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bank_account_parser::statement::Balance;
+    use bank_account_parser::transactions_holder::TransactionsReader;
 
-    match camt.write_to(&mut out) {
-        Ok(_) => (),
-        Err(e) => bail!(e.to_string())
+    const EXAMPLES: &str = "../example_data";
+
+    /// Конвертировать `input` из `from` в `to` с дополнительными аргументами `args`.
+    fn run(input: &Path, from: InputFormat, to: OutputFormat, args: &[&str]) -> Vec<u8> {
+        let cli = Cli::try_parse_from(["converter", "--input", input.to_str().unwrap()].iter().chain(args)).unwrap();
+        let mut out = Vec::new();
+        convert(&cli, from, to, &mut out).unwrap();
+        out
     }
 
-    Ok(format!("Mt940({}) конвертирован в Camt053", input.display()))
-}
+    fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("converter-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
 
-fn convert_camt053(input: &PathBuf) -> Result<String> {
+    #[test]
+    fn mt940_survives_camt053_round_trip() {
+        for name in ["MT940 github 1.mt940", "mt 940 gs.mt940", "new_file.mt940"] {
+            let input = Path::new(EXAMPLES).join(name);
+            let camt = temp_file(&format!("{name}.xml"), &run(&input, InputFormat::Mt940, OutputFormat::Camt053, &[]));
+            let back = run(&camt, InputFormat::Camt053, OutputFormat::Mt940, &[]);
+            std::fs::remove_file(&camt).unwrap();
 
-    let Ok(file) = File::open(input) else {
-        bail!("Не удалось открыть файл {}", input.display())
-    };
+            let original = MT940Format::from_read(&mut File::open(&input).unwrap()).unwrap();
+            let back = MT940Format::from_read(&mut back.as_slice()).unwrap();
+            // свободный текст :86: переносится заново, поэтому пробелы в нём сравниваются схлопнутыми
+            let transactions = |mt: &MT940Format| {
+                let mut transactions = mt.collect_transactions().unwrap();
+                for t in &mut transactions {
+                    t.description = t.description.take().map(|d| d.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+                transactions
+            };
+            assert_eq!(transactions(&back), transactions(&original), "{name}");
+            // промежуточные остатки :60M:/:62M: пишутся в CAMT как OPBD/CLBD и читаются окончательными
+            let balances = |mt: MT940Format| {
+                let flat = |b: Option<Balance>| b.map(|b| Balance { is_intermediate: false, ..b });
+                Statements::from(mt)
+                    .statements
+                    .into_iter()
+                    .map(|s| (s.account.id, flat(s.opening_balance), flat(s.closing_balance)))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(balances(back), balances(original), "{name}");
+        }
+    }
 
-    let mut reader = io::BufReader::new(file);
+    #[test]
+    fn user_bank_tx_codes_are_applied() {
+        let rules = temp_file("codes.txt", b"PMNT/MCRD/POSP=IDB\n");
+        let input = Path::new(EXAMPLES).join("MT940 github 1.mt940");
+        let args = ["--bank-tx-codes", rules.to_str().unwrap()];
+        let camt = String::from_utf8(run(&input, InputFormat::Mt940, OutputFormat::Camt053, &args)).unwrap();
+        std::fs::remove_file(&rules).unwrap();
 
-    let camt = match Camt053Format::from_read(&mut reader) {
-        Ok(mt) => mt,
-        Err(e) => bail!(e.to_string())
-    };
+        assert!(camt.contains("<Cd>MCRD</Cd><SubFmlyCd>POSP</SubFmlyCd>"));
+        assert!(!run(&input, InputFormat::Mt940, OutputFormat::Camt053, &[]).windows(4).any(|w| w == b"POSP"));
+    }
 
-    let mut mt: MT940Format = camt.into();
-    let mut out = io::stdout();
+    #[test]
+    fn csv_converts_to_json() {
+        let input = Path::new(EXAMPLES).join("Пример выписки по счёту 1.csv");
+        let json: serde_json::Value =
+            serde_json::from_slice(&run(&input, InputFormat::Csv, OutputFormat::Json, &[])).unwrap();
 
-    match mt.write_to(&mut out) {
-        Ok(_) => (),
-        Err(e) => bail!(e.to_string())
+        let csv = CSVFormat::from_read(&mut File::open(&input).unwrap()).unwrap();
+        let transactions = csv.collect_transactions().unwrap();
+        let statements = json.as_array().unwrap();
+        assert_eq!(statements.len(), 1);
+        let entries = statements[0]["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), transactions.len());
+        for (entry, t) in entries.iter().zip(&transactions) {
+            assert_eq!(entry["amount"], t.amount.to_string());
+            assert_eq!(entry["value_date"], t.date.to_string());
+            assert_eq!(entry["operation_type"], t.operation_type.to_string());
+            assert_eq!(entry["status"], "BOOK");
+        }
+        assert_eq!(statements[0]["currency"], "RUB");
     }
 
-    Ok(format!("Camt053({}) конвертирован в Mt940", input.display()))
-}
\ No newline at end of file
+    #[test]
+    fn csv_with_bad_row_fails_unless_lenient() {
+        let input = Path::new(EXAMPLES).join("Пример выписки по счёту 1.csv");
+        let text = std::fs::read_to_string(&input).unwrap().replacen("20.02.2024", "31.02.2024", 1);
+        let broken = temp_file("broken.csv", text.as_bytes());
+
+        let cli = Cli::try_parse_from(["converter", "--input", broken.to_str().unwrap()]).unwrap();
+        assert!(convert(&cli, InputFormat::Csv, OutputFormat::Json, &mut Vec::new()).is_err());
+        let json: serde_json::Value =
+            serde_json::from_slice(&run(&broken, InputFormat::Csv, OutputFormat::Json, &["--lenient"])).unwrap();
+        let csv = run(&broken, InputFormat::Csv, OutputFormat::Csv, &["--lenient"]);
+        std::fs::remove_file(&broken).unwrap();
+
+        let all = CSVFormat::from_read(&mut File::open(&input).unwrap()).unwrap().collect_transactions().unwrap();
+        assert_eq!(json[0]["entries"].as_array().unwrap().len(), all.len() - 1);
+        assert!(!String::from_utf8(csv).unwrap().contains("31.02.2024"));
+    }
+}